@@ -1,16 +1,22 @@
-use std::sync::Arc;
+use std::{ops::RangeInclusive, sync::Arc};
 
 use anyhow::Result;
 use tokio::sync::mpsc::Sender;
 
 use crate::db::db::{TxnLink, TxnMap};
 use crate::db::request_queue::TaskQueueRequest;
-use crate::execute::request::{Command, Request};
+use crate::execute::request::{dedupe_spanset, Command, Request, SpanSet};
+use crate::hlc::timestamp::Timestamp;
 use crate::latch_manager::latch_manager::{LatchGuard, LatchManager};
-use crate::lock_table::lock_table::{LockTable, LockTableGuardLink, UpdateLock, WaitForGuardError};
+use crate::lock_table::lock_table::{
+    LockTable, LockTableGuardLink, UpdateLock, WaitForGuardError, WaitPolicy,
+};
 use crate::storage::mvcc::KVStore;
 use crate::storage::Key;
+use crate::{StorageError, StorageResult};
+use tokio::time::Instant;
 use tracing::error;
+use uuid::Uuid;
 
 pub struct ConcurrencyManager {
     latch_manager: LatchManager<Key>,
@@ -28,6 +34,10 @@ pub struct Guard {
 pub enum SequenceReqError {
     TxnAborted,
     TxnCommitted,
+    // the request can't wait for the lock held or reserved by holder_txn
+    LockConflict { key: Key, holder_txn: Uuid },
+    // the storage failed to write the push of the lock holder
+    StorageError(StorageError),
 }
 
 impl ConcurrencyManager {
@@ -43,22 +53,61 @@ impl ConcurrencyManager {
         }
     }
 
+    /**
+     * The lock timeout of a Block wait policy applies to the total time the request
+     * waits in the lockTable, across rescans.
+     */
     pub async fn sequence_req(&self, request: &Request) -> Result<Guard, SequenceReqError> {
         let spans_to_acquire = request
             .request_union
             .collect_spans(request.metadata.txn.clone());
+        let lock_deadline = match request.metadata.wait_policy {
+            WaitPolicy::Block {
+                timeout: Some(timeout),
+            } => Some(Instant::now() + timeout),
+            _ => None,
+        };
         loop {
             let latch_guard = self.latch_manager.acquire(spans_to_acquire.clone()).await;
             let (should_wait, lock_guard) = self.lock_table.scan_and_enqueue(request).await;
             if should_wait {
+                // a read pushes the intent's txn above its read timestamp instead of waiting
+                let push_res = self.push_lock_holder(request, &lock_guard);
                 self.latch_manager.release(latch_guard);
-                let wait_res = self.lock_table.wait_for(lock_guard).await;
+                let is_pushed = match push_res {
+                    Ok(is_pushed) => is_pushed,
+                    Err(err) => {
+                        self.lock_table.dequeue(lock_guard).await;
+                        return Err(SequenceReqError::StorageError(err));
+                    }
+                };
+                if is_pushed {
+                    self.lock_table.dequeue(lock_guard).await;
+                    continue;
+                }
+                if !matches!(request.metadata.wait_policy, WaitPolicy::Block { .. }) {
+                    let conflict = lock_guard.get_conflict();
+                    self.lock_table.dequeue(lock_guard).await;
+                    match conflict {
+                        Some((key, holder_txn)) => {
+                            return Err(SequenceReqError::LockConflict { key, holder_txn })
+                        }
+                        // no other txn holds the lock anymore, so the lockTable is rescanned
+                        None => continue,
+                    }
+                }
+                let lock_timeout = lock_deadline
+                    .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                let wait_res = self.lock_table.wait_for(lock_guard, lock_timeout).await;
                 if let Err(err) = wait_res {
                     match err {
                         WaitForGuardError::TxnAborted => return Err(SequenceReqError::TxnAborted),
                         WaitForGuardError::TxnCommitted => {
                             return Err(SequenceReqError::TxnCommitted)
                         }
+                        WaitForGuardError::LockTimeout { key, holder_txn } => {
+                            return Err(SequenceReqError::LockConflict { key, holder_txn })
+                        }
                     }
                 };
 
@@ -73,6 +122,32 @@ impl ConcurrencyManager {
         }
     }
 
+    /**
+     * Pushes the timestamp of the txn holding the lock the read request waits for above
+     * the read timestamp. Must be called while the latches are held.
+     */
+    fn push_lock_holder(
+        &self,
+        request: &Request,
+        lock_guard: &LockTableGuardLink,
+    ) -> StorageResult<bool> {
+        if !request.request_union.is_read_only() {
+            return Ok(false);
+        }
+        let Some((key, holder_txn)) = lock_guard.get_conflict() else {
+            return Ok(false);
+        };
+        let push_timestamp = request
+            .metadata
+            .txn
+            .read()
+            .unwrap()
+            .read_timestamp
+            .next_logical_timestamp();
+        self.lock_table
+            .push_holder_timestamp(&key, holder_txn, push_timestamp)
+    }
+
     /**
      * Release latches and dequeues the request from any lock tables.
      */
@@ -81,14 +156,46 @@ impl ConcurrencyManager {
         self.lock_table.dequeue(guard.lock_guard.clone()).await;
     }
 
-    fn get_txn_lock_spans(txn_link: TxnLink) -> Vec<Key> {
+    /**
+     * Resolves the intents of the committed txn within its lock spans in a single write
+     * batch. The latches on the spans are held so that the resolution doesn't race with
+     * requests resolving the same intents or writing the keys.
+     */
+    pub async fn resolve_intents(
+        &self,
+        txn_id: Uuid,
+        commit_timestamp: Timestamp,
+        ignored_sequences: &[RangeInclusive<u64>],
+        mut lock_spans: SpanSet<Key>,
+    ) -> StorageResult<()> {
+        dedupe_spanset(&mut lock_spans);
+        let latch_guard = self.latch_manager.acquire(lock_spans.clone()).await;
+        let keys = lock_spans
+            .iter()
+            .flat_map(|span| self.store.mvcc_scan_intents(&span.start_key, &span.end_key))
+            .filter(|intent| intent.txn_meta.txn_id == txn_id)
+            .map(|intent| intent.key)
+            .collect::<Vec<Key>>();
+        let res = self
+            .store
+            .resolve_intents(txn_id, commit_timestamp, ignored_sequences, &keys);
+        self.latch_manager.release(latch_guard);
+        res
+    }
+
+    fn get_txn_lock_spans(txn_link: TxnLink) -> SpanSet<Key> {
         let txn = txn_link.read().unwrap();
-        let keys = txn.lock_spans.read().unwrap();
-        keys.clone()
+        let spans = txn.lock_spans.read().unwrap();
+        spans.clone()
     }
 
     pub async fn update_txn_locks(&self, txn: TxnLink, update_lock: UpdateLock) {
-        let keys = ConcurrencyManager::get_txn_lock_spans(txn.clone());
+        let spans = ConcurrencyManager::get_txn_lock_spans(txn.clone());
+        let keys = spans
+            .iter()
+            .flat_map(|span| self.lock_table.get_lock_states_in_span(span))
+            .map(|(key, _)| key)
+            .collect::<Vec<Key>>();
 
         for key in keys.iter() {
             println!("Updating lock for key: {:?}", key);
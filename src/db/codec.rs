@@ -0,0 +1,125 @@
+use std::fmt;
+
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::storage::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecError(pub String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+pub type CodecResult<T> = Result<T, CodecError>;
+
+/**
+ * Encodes the values of type T written to the DB.
+ */
+pub trait ValueEncoder<T: ?Sized> {
+    fn encode(&self, value: &T) -> CodecResult<Value>;
+}
+
+/**
+ * Decodes the values of type T read from the DB.
+ */
+pub trait ValueDecoder<T> {
+    fn decode(&self, value: &[u8]) -> CodecResult<T>;
+}
+
+/**
+ * The codec of the values of a DB, chosen when the DB is opened. Values must be read
+ * with the codec they were written with.
+ *
+ * Every codec encodes integers since increment reads and writes them on the executor.
+ */
+pub trait ValueCodec: ValueEncoder<i64> + ValueDecoder<i64> + Send + Sync + 'static {}
+
+/**
+ * Encodes any serde value as JSON. The default codec.
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonCodec;
+
+/**
+ * Encodes any serde value with bincode, which is more compact than JSON.
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BincodeCodec;
+
+/**
+ * Stores byte values as is, without any encoding overhead. Integers are stored as
+ * 8 bytes in big-endian.
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RawBytesCodec;
+
+impl<T: Serialize + ?Sized> ValueEncoder<T> for JsonCodec {
+    fn encode(&self, value: &T) -> CodecResult<Value> {
+        serde_json::to_vec(value).map_err(|err| CodecError(err.to_string()))
+    }
+}
+
+impl<T: DeserializeOwned> ValueDecoder<T> for JsonCodec {
+    fn decode(&self, value: &[u8]) -> CodecResult<T> {
+        serde_json::from_slice(value).map_err(|err| CodecError(err.to_string()))
+    }
+}
+
+impl ValueCodec for JsonCodec {}
+
+impl<T: Serialize + ?Sized> ValueEncoder<T> for BincodeCodec {
+    fn encode(&self, value: &T) -> CodecResult<Value> {
+        bincode::DefaultOptions::new()
+            .serialize(value)
+            .map_err(|err| CodecError(err.to_string()))
+    }
+}
+
+impl<T: DeserializeOwned> ValueDecoder<T> for BincodeCodec {
+    fn decode(&self, value: &[u8]) -> CodecResult<T> {
+        bincode::DefaultOptions::new()
+            .deserialize(value)
+            .map_err(|err| CodecError(err.to_string()))
+    }
+}
+
+impl ValueCodec for BincodeCodec {}
+
+impl ValueEncoder<[u8]> for RawBytesCodec {
+    fn encode(&self, value: &[u8]) -> CodecResult<Value> {
+        Ok(value.to_vec())
+    }
+}
+
+impl ValueEncoder<Vec<u8>> for RawBytesCodec {
+    fn encode(&self, value: &Vec<u8>) -> CodecResult<Value> {
+        Ok(value.clone())
+    }
+}
+
+impl ValueDecoder<Vec<u8>> for RawBytesCodec {
+    fn decode(&self, value: &[u8]) -> CodecResult<Vec<u8>> {
+        Ok(value.to_vec())
+    }
+}
+
+impl ValueEncoder<i64> for RawBytesCodec {
+    fn encode(&self, value: &i64) -> CodecResult<Value> {
+        Ok(value.to_be_bytes().to_vec())
+    }
+}
+
+impl ValueDecoder<i64> for RawBytesCodec {
+    fn decode(&self, value: &[u8]) -> CodecResult<i64> {
+        let bytes = value
+            .try_into()
+            .map_err(|_| CodecError(format!("expected 8 bytes, found {}", value.len())))?;
+        Ok(i64::from_be_bytes(bytes))
+    }
+}
+
+impl ValueCodec for RawBytesCodec {}
@@ -1,45 +1,67 @@
 use std::{
     collections::HashMap,
     future::Future,
-    sync::{Arc, RwLock},
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{Arc, RwLock, Weak},
+    task::{Context, Poll},
+    time::Duration,
 };
 
 use rand::distributions::{Alphanumeric, DistString};
-use serde::{de::DeserializeOwned, Serialize};
 use std::panic;
 use tokio::sync::mpsc;
+use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     execute::{
         executor::{ExecuteError, Executor},
         request::{
-            AbortTxnRequest, BeginTxnRequest, CommitTxnRequest, GetRequest, PutRequest, Request,
-            RequestMetadata, RequestUnion, ResponseUnion,
+            AbortTxnRequest, BatchRequest, BeginTxnRequest, CommitTxnRequest,
+            ConditionalPutRequest, DeleteRangeRequest, DeleteRequest, GetForUpdateRequest,
+            GetRequest, IncrementRequest, PutRequest, Request, RequestMetadata, RequestUnion,
+            ResponseUnion, ScanRequest,
         },
     },
     hlc::{
         clock::{Clock, ManualClock},
         timestamp::Timestamp as HLCTimestamp,
     },
-    storage::{str_to_key, txn::Txn},
+    lock_table::lock_table::LockStrength,
+    storage::{
+        str_to_key,
+        txn::{TransactionStatus, Txn},
+    },
+    StorageError,
 };
 
+pub use crate::lock_table::lock_table::WaitPolicy;
+pub use crate::storage::mvcc::GcStats;
+pub use crate::storage::txn::TxnPriority;
+pub use crate::storage::{Key, Value};
+
+use super::codec::{CodecError, JsonCodec, ValueCodec, ValueDecoder, ValueEncoder};
 use super::request_queue::{TaskQueue, TaskQueueRequest};
 
 pub type TxnLink = Arc<RwLock<Txn>>;
 
 pub type TxnMap = Arc<RwLock<HashMap<Uuid, TxnLink>>>;
 
-pub struct InternalDB {
+pub struct InternalDB<C = JsonCodec> {
     executor: Arc<Executor>,
+    codec: Arc<C>,
     txns: TxnMap,
     clock: RwLock<ManualClock>,
     thread_pool: TaskQueue,
 }
 
-pub struct DB {
-    db: Arc<InternalDB>,
+/**
+ * The values are encoded with the codec C, which is JSON unless the DB is opened
+ * with another codec.
+ */
+pub struct DB<C = JsonCodec> {
+    db: Arc<InternalDB<C>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -47,18 +69,166 @@ pub struct Timestamp {
     pub value: u64,
 }
 
-pub enum CommitTxnResult {
-    Success(CommitTxnSuccess),
-    Fail(CommitTxnFailureReason),
-}
-
 pub struct CommitTxnSuccess {
     pub commit_timestamp: HLCTimestamp,
 }
 
-pub enum CommitTxnFailureReason {
-    ReadRefreshFail,
+/**
+ * A point in a transaction that the transaction can roll back to.
+ * Savepoints are identified by the sequence number of the txn's latest write.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    txn_id: Uuid,
+    sequence: u64,
+}
+
+/**
+ * Determines how many times run_txn attempts to run a transaction whose commit failed
+ * because it has to restart. The backoff between attempts doubles after each attempt
+ * up to max_backoff.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/**
+ * Determines how much MVCC history the background GC keeps. Every interval, the
+ * history older than ttl (in units of the database time) is garbage collected.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct GcPolicy {
+    pub ttl: u64,
+    pub interval: Duration,
+}
+
+/**
+ * An operation of a batch txn. Values are encoded and decoded with the DB's codec.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOperation<'a, T> {
+    Put { key: &'a str, value: T },
+    Delete { key: &'a str },
+    Get { key: &'a str },
+}
+
+/**
+ * The result of a BatchOperation. Get returns the value of the key, or None if
+ * the key doesn't exist.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchOperationResult<T> {
+    Put,
+    Delete,
+    Get(Option<T>),
+}
+
+/**
+ * Options of a read-write transaction.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TxnOptions {
+    pub priority: TxnPriority,
+}
+
+/**
+ * Catches a panic thrown while polling the future.
+ */
+struct CatchUnwind<Fut> {
+    future: Pin<Box<Fut>>,
+}
+
+impl<Fut: Future> Future for CatchUnwind<Fut> {
+    type Output = std::thread::Result<Fut::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.future.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic_payload) => Poll::Ready(Err(panic_payload)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbError {
+    // No txn was started with the txn ID
+    UnknownTxn(Uuid),
     TxnAborted,
+    TxnCommitted,
+    // The txn's read timestamp couldn't be advanced to its write timestamp on commit.
+    // The txn isn't aborted by the commit. It should be aborted and retried
+    ReadRefreshFailure,
+    // The value couldn't be serialized or the stored value couldn't be deserialized
+    // into the requested type
+    SerializationError(String),
+    StorageError(StorageError),
+    // The value of the key didn't match the expected value of a conditional put.
+    // actual is None if the key doesn't exist
+    ConditionFailed { actual: Option<Value> },
+    // The value of the key isn't an integer or the increment overflowed
+    InvalidIncrement,
+    // The savepoint was created by another txn
+    InvalidSavepoint,
+    // A read-only txn attempted to write
+    ReadOnlyTxn,
+    // A read-only txn can't read above the current time of the database
+    InvalidReadTimestamp,
+    // The request's wait policy didn't allow it to wait for the lock on the key
+    // held or reserved by holder_txn
+    LockConflict { key: Key, holder_txn: Uuid },
+    // The request read beneath the GC threshold. The txn should be retried
+    BelowGcThreshold { threshold: HLCTimestamp },
+    // The GC threshold can't be above the current time of the database
+    InvalidGcThreshold,
+    // The executor returned a response that doesn't match the request
+    UnexpectedResponse,
+}
+
+pub type DbResult<T> = Result<T, DbError>;
+
+impl From<ExecuteError> for DbError {
+    fn from(err: ExecuteError) -> Self {
+        match err {
+            ExecuteError::ReadRefreshFailure => DbError::ReadRefreshFailure,
+            ExecuteError::TxnCommitted => DbError::TxnCommitted,
+            ExecuteError::TxnAborted => DbError::TxnAborted,
+            ExecuteError::ConditionFailed { actual } => DbError::ConditionFailed { actual },
+            ExecuteError::InvalidIncrement => DbError::InvalidIncrement,
+            ExecuteError::ReadOnlyTxn => DbError::ReadOnlyTxn,
+            ExecuteError::LockConflict { key, holder_txn } => {
+                DbError::LockConflict { key, holder_txn }
+            }
+            ExecuteError::BelowGcThreshold { threshold } => DbError::BelowGcThreshold { threshold },
+            ExecuteError::StorageError(err) => DbError::StorageError(err),
+        }
+    }
+}
+
+impl From<StorageError> for DbError {
+    fn from(err: StorageError) -> Self {
+        DbError::StorageError(err)
+    }
+}
+
+impl From<CodecError> for DbError {
+    fn from(err: CodecError) -> Self {
+        DbError::SerializationError(err.to_string())
+    }
 }
 
 impl Timestamp {
@@ -79,14 +249,28 @@ impl Timestamp {
 
 impl DB {
     pub fn new_cleaned(path: &str, initial_time: Timestamp) -> Self {
+        DB::new_cleaned_with_codec(path, initial_time, JsonCodec)
+    }
+
+    pub fn new(path: &str, initial_time: Timestamp) -> Self {
+        DB::new_with_codec(path, initial_time, JsonCodec)
+    }
+}
+
+impl<C: ValueCodec> DB<C> {
+    pub fn new_cleaned_with_codec(path: &str, initial_time: Timestamp, codec: C) -> Self {
         DB {
-            db: Arc::new(InternalDB::new_cleaned(path, initial_time)),
+            db: Arc::new(InternalDB::new_cleaned(path, initial_time, codec)),
         }
     }
 
-    pub fn new(path: &str, initial_time: Timestamp) -> Self {
+    /**
+     * Opens the database with the codec of its values. The database must be opened
+     * with the codec it was written with.
+     */
+    pub fn new_with_codec(path: &str, initial_time: Timestamp, codec: C) -> Self {
         DB {
-            db: Arc::new(InternalDB::new(path, initial_time)),
+            db: Arc::new(InternalDB::new(path, initial_time, codec)),
         }
     }
 
@@ -94,99 +278,468 @@ impl DB {
         self.db.set_time(timestamp);
     }
 
-    // TODO: This should return an error - deadlock detection
-    pub async fn write<T: Serialize>(
+    pub async fn write<T>(&self, key: &str, value: T, txn_id: Uuid) -> DbResult<ResponseUnion>
+    where
+        C: ValueEncoder<T>,
+    {
+        self.db.write(key, value, txn_id).await
+    }
+
+    pub async fn read<T>(&self, key: &str, txn_id: Uuid) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.read(key, txn_id).await
+    }
+
+    /**
+     * Like write, but the key and the value are written as is, without the codec.
+     * Keys and values can be any bytes.
+     */
+    pub async fn put_raw(&self, key: Key, value: Value, txn_id: Uuid) -> DbResult<ResponseUnion> {
+        self.db.put_raw(key, value, txn_id).await
+    }
+
+    /**
+     * Like read, but the value is returned as is, without the codec.
+     */
+    pub async fn get_raw(&self, key: Key, txn_id: Uuid) -> DbResult<Option<Value>> {
+        self.db.get_raw(key, txn_id).await
+    }
+
+    /**
+     * Reads the latest committed value of the key and locks it exclusively without
+     * writing an intent. Other txns can't lock or write the key until the txn finalizes,
+     * but non-locking reads don't wait for the lock.
+     */
+    pub async fn read_for_update<T>(&self, key: &str, txn_id: Uuid) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.read_for_update(key, txn_id).await
+    }
+
+    /**
+     * Like read_for_update, but the lock can be shared with other txns reading the key
+     * with read_for_share. Writers wait until every shared lock is released.
+     */
+    pub async fn read_for_share<T>(&self, key: &str, txn_id: Uuid) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.read_for_share(key, txn_id).await
+    }
+
+    /**
+     * read_for_update that handles a conflicting lock with the wait policy. With
+     * WaitPolicy::SkipLocked, a key locked by another txn is read as None without locking it.
+     */
+    pub async fn read_for_update_with_wait_policy<T>(
         &self,
         key: &str,
-        value: T,
+        wait_policy: WaitPolicy,
         txn_id: Uuid,
-    ) -> Result<ResponseUnion, ExecuteError> {
-        self.db.write(key, value, txn_id).await
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db
+            .read_for_update_with_wait_policy(key, wait_policy, txn_id)
+            .await
     }
 
-    pub async fn read<T: DeserializeOwned>(&self, key: &str, txn_id: Uuid) -> Option<T> {
-        self.db.read(key, txn_id).await
+    /**
+     * read_for_share that handles a conflicting lock with the wait policy.
+     */
+    pub async fn read_for_share_with_wait_policy<T>(
+        &self,
+        key: &str,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db
+            .read_for_share_with_wait_policy(key, wait_policy, txn_id)
+            .await
     }
 
-    pub async fn read_without_txn<T: DeserializeOwned>(
+    /**
+     * Writes the value only if the current value of the key equals the expected value.
+     * If expected is None, the key must not exist. Otherwise, a ConditionFailed error
+     * is returned with the actual serialized value.
+     */
+    pub async fn cput<T>(
         &self,
         key: &str,
-        timestamp: Timestamp,
-    ) -> T {
+        value: T,
+        expected: Option<T>,
+        txn_id: Uuid,
+    ) -> DbResult<ResponseUnion>
+    where
+        C: ValueEncoder<T>,
+    {
+        self.db.cput(key, value, expected, txn_id).await
+    }
+
+    /**
+     * Adds the increment to the integer stored at the key (0 if it doesn't exist)
+     * and writes the result in a single request. Returns the new value.
+     */
+    pub async fn increment(&self, key: &str, increment: i64, txn_id: Uuid) -> DbResult<i64> {
+        self.db.increment(key, increment, txn_id).await
+    }
+
+    /**
+     * Deletes the key by writing a tombstone. Reads at timestamps before the
+     * txn commits still see the previous value.
+     */
+    pub async fn delete(&self, key: &str, txn_id: Uuid) -> DbResult<ResponseUnion> {
+        self.db.delete(key, txn_id).await
+    }
+
+    /**
+     * Deletes every key in [start_key, end_key) by writing tombstones.
+     */
+    pub async fn delete_range(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        txn_id: Uuid,
+    ) -> DbResult<ResponseUnion> {
+        self.db.delete_range(start_key, end_key, txn_id).await
+    }
+
+    /**
+     * Creates a savepoint for the txn. Writes made after the savepoint can be
+     * undone with rollback_to.
+     */
+    pub fn savepoint(&self, txn_id: Uuid) -> DbResult<Savepoint> {
+        self.db.savepoint(txn_id)
+    }
+
+    /**
+     * Undoes the txn's writes made after the savepoint while keeping the txn alive.
+     * The keys that were written still hold their locks until the txn finalizes.
+     */
+    pub fn rollback_to(&self, txn_id: Uuid, savepoint: Savepoint) -> DbResult<()> {
+        self.db.rollback_to(txn_id, savepoint)
+    }
+
+    /**
+     * Evaluates the requests in order as a single request. The latches and locks for
+     * all of the requests are acquired once. Returns the response of each request.
+     */
+    pub async fn batch(
+        &self,
+        requests: Vec<RequestUnion>,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<ResponseUnion>> {
+        self.db.batch(requests, txn_id).await
+    }
+
+    /**
+     * Begins a transaction, evaluates the operations in a single batch and commits the
+     * transaction. If the operations only write (puts and deletes), the writes are
+     * committed directly without intents or a txn record (one-phase commit).
+     * Returns the result of each operation.
+     */
+    pub async fn run_batch_txn<T>(
+        &self,
+        operations: Vec<BatchOperation<'_, T>>,
+    ) -> DbResult<Vec<BatchOperationResult<T>>>
+    where
+        C: ValueEncoder<T> + ValueDecoder<T>,
+    {
+        self.db.run_batch_txn(operations).await
+    }
+
+    /**
+     * Writes the value of the key in its own transaction, committed in one phase.
+     */
+    pub async fn upsert<T>(&self, key: &str, value: T) -> DbResult<()>
+    where
+        C: ValueEncoder<T>,
+    {
+        self.db.upsert(key, value).await
+    }
+
+    /**
+     * Scans the keys in [start_key, end_key) and returns at most limit key-value pairs
+     * ordered by key. Returns a serialization error if a key in the range isn't valid
+     * UTF-8, e.g. one written with put_raw. Use scan_raw to read such keys.
+     */
+    pub async fn scan<T>(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(String, T)>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.scan(start_key, end_key, limit, txn_id).await
+    }
+
+    /**
+     * Like scan, but the keys and the values are returned as is, without the codec.
+     * The keys are ordered bytewise, from start_key (inclusive) to end_key (exclusive).
+     */
+    pub async fn scan_raw(
+        &self,
+        start_key: Key,
+        end_key: Key,
+        limit: usize,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(Key, Value)>> {
+        self.db.scan_raw(start_key, end_key, limit, txn_id).await
+    }
+
+    /**
+     * scan that handles conflicting locks with the wait policy. With WaitPolicy::SkipLocked,
+     * the keys locked by other txns are omitted from the results.
+     */
+    pub async fn scan_with_wait_policy<T>(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(String, T)>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db
+            .scan_with_wait_policy(start_key, end_key, limit, wait_policy, txn_id)
+            .await
+    }
+
+    /**
+     * Reads the most recent value of the key at the timestamp without starting a transaction.
+     * The read waits for uncommitted writes beneath the timestamp to finalize.
+     */
+    pub async fn read_without_txn<T>(&self, key: &str, timestamp: Timestamp) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
         self.db.read_without_txn(key, timestamp).await
     }
 
     /**
      * Creates a transaction. All reads and writes with the TxnContext will be using
-     * the created txn.
+     * the created txn. Retries with the default RetryPolicy.
      *
-     * An uncaught panic thrown from the transactional code will rollback the transaction
-     * to the start of the transaction scope.
+     * If the transaction function returns Ok, the transaction will commit and
+     * the value is returned. If the commit fails because the txn has to restart,
+     * the function is called again in a new transaction.
      *
-     * If the transaction function finishes executing, the transaction will commit
+     * If the transaction function returns Err, the transaction is aborted and the
+     * error is returned. An uncaught panic thrown from the transactional code will abort
+     * the transaction before the panic is resumed.
      */
-    pub async fn run_txn<'a, Fut>(&self, f: impl FnOnce(Arc<TxnContext>) -> Fut + std::marker::Copy)
+    pub async fn run_txn<T, E, F, Fut>(&self, f: F) -> Result<T, E>
     where
-        Fut: Future<Output = ()>,
+        F: Fn(Arc<TxnContext<C>>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<DbError>,
     {
+        self.run_txn_with_retry_policy(RetryPolicy::default(), f)
+            .await
+    }
+
+    pub async fn run_txn_with_retry_policy<T, E, F, Fut>(
+        &self,
+        retry_policy: RetryPolicy,
+        f: F,
+    ) -> Result<T, E>
+    where
+        F: Fn(Arc<TxnContext<C>>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<DbError>,
+    {
+        self.run_txn_with_options(TxnOptions::default(), retry_policy, f)
+            .await
+    }
+
+    /**
+     * Like run_txn_with_retry_policy, but the transaction is created with the options.
+     * Each retry raises the priority of the transaction within its class so that it
+     * isn't starved in deadlocks with transactions of the same class.
+     */
+    pub async fn run_txn_with_options<T, E, F, Fut>(
+        &self,
+        options: TxnOptions,
+        retry_policy: RetryPolicy,
+        f: F,
+    ) -> Result<T, E>
+    where
+        F: Fn(Arc<TxnContext<C>>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: From<DbError>,
+    {
+        let mut attempt = 1;
+        let mut backoff = retry_policy.initial_backoff;
+        let mut options = options;
         loop {
-            let txn_id = self.db.begin_txn().await;
+            let txn_id = self.db.begin_txn_with_options(options).await?;
             let context = Arc::new(TxnContext {
                 txn_id,
                 db: self.db.clone(),
             });
-            let cloned = context.clone();
-
-            // TODO: What if this panics? We should abort if so
-            f(cloned).await;
-            let res = self.db.commit_txn(txn_id).await;
-            match res {
-                CommitTxnResult::Success(_) => break,
-                CommitTxnResult::Fail(_) => {
-                    // TODO: Should we abort before continuing?
-                    continue;
+
+            let result = match (CatchUnwind {
+                future: Box::pin(f(context)),
+            })
+            .await
+            {
+                Ok(result) => result,
+                Err(panic_payload) => {
+                    let _ = self.db.abort_txn(txn_id).await;
+                    panic::resume_unwind(panic_payload);
                 }
+            };
+            let value = match result {
+                Ok(value) => value,
+                Err(err) => {
+                    // the caller's error is returned even if the txn was already aborted
+                    let _ = self.db.abort_txn(txn_id).await;
+                    return Err(err);
+                }
+            };
+
+            match self.db.commit_txn(txn_id).await {
+                Ok(_) => return Ok(value),
+                Err(err @ (DbError::ReadRefreshFailure | DbError::TxnAborted)) => {
+                    // clean up the intents that were laid by the txn
+                    let _ = self.db.abort_txn(txn_id).await;
+                    if attempt >= retry_policy.max_attempts {
+                        return Err(E::from(err));
+                    }
+                }
+                Err(err) => return Err(E::from(err)),
             }
+            tokio::time::sleep(backoff).await;
+            backoff = std::cmp::min(backoff * 2, retry_policy.max_backoff);
+            options.priority = options.priority.raise();
+            attempt += 1;
         }
     }
 
-    pub async fn begin_txn(&self) -> Uuid {
+    pub async fn begin_txn(&self) -> DbResult<Uuid> {
         self.db.begin_txn().await
     }
 
-    pub async fn abort_txn(&self, txn_id: Uuid) {
+    /**
+     * Starts a transaction with the options. When the transaction runs into a lock held
+     * by a transaction with a lower priority class, the holder is aborted instead of
+     * waited on.
+     */
+    pub async fn begin_txn_with_options(&self, options: TxnOptions) -> DbResult<Uuid> {
+        self.db.begin_txn_with_options(options).await
+    }
+
+    /**
+     * Starts a read-only transaction pinned to the timestamp. Reads and scans are
+     * served from the MVCC history at the timestamp without contending with
+     * read-write transactions. Writes are rejected with ReadOnlyTxn.
+     */
+    pub async fn begin_read_only_txn_at(&self, timestamp: Timestamp) -> DbResult<Uuid> {
+        self.db.begin_read_only_txn_at(timestamp).await
+    }
+
+    pub async fn abort_txn(&self, txn_id: Uuid) -> DbResult<()> {
         self.db.abort_txn(txn_id).await
     }
 
     // TODO: We should return the final timestamps if possible - easier for testing
     // Returns whether the commit was successful or if a retry was necessary
-    pub async fn commit_txn(&self, txn_id: Uuid) -> CommitTxnResult {
+    pub async fn commit_txn(&self, txn_id: Uuid) -> DbResult<CommitTxnSuccess> {
         self.db.commit_txn(txn_id).await
     }
+
+    /**
+     * Garbage collects the MVCC history beneath the threshold. Reads beneath the
+     * threshold fail with BelowGcThreshold afterwards.
+     */
+    pub fn run_gc(&self, threshold: Timestamp) -> DbResult<GcStats> {
+        self.db.run_gc(threshold)
+    }
+
+    /**
+     * Advances the GC threshold without garbage collecting. Reads beneath the threshold
+     * fail with BelowGcThreshold right away, and the versions shadowed beneath the
+     * threshold are dropped when RocksDB compacts them.
+     */
+    pub fn advance_gc_threshold(&self, threshold: Timestamp) -> DbResult<()> {
+        self.db.advance_gc_threshold(threshold)
+    }
+
+    /**
+     * Starts a background task that garbage collects the history older than the
+     * policy's ttl. The task stops once the database is dropped.
+     */
+    pub fn start_gc(&self, policy: GcPolicy) {
+        self.start_gc_with(policy, |db, threshold| db.run_gc(threshold));
+    }
+
+    /**
+     * Like start_gc, but each run garbage collects with run_gc.
+     */
+    pub(crate) fn start_gc_with<F>(&self, policy: GcPolicy, run_gc: F)
+    where
+        F: Fn(&InternalDB<C>, Timestamp) -> DbResult<GcStats> + Send + 'static,
+    {
+        let db = Arc::downgrade(&self.db);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(policy.interval).await;
+                let Some(db) = Weak::upgrade(&db) else {
+                    break;
+                };
+                let now = db.now();
+                if now.value > policy.ttl {
+                    // the next run garbage collects what this run couldn't
+                    if let Err(err) = run_gc(&db, Timestamp::new(now.value - policy.ttl)) {
+                        error!("failed to garbage collect: {:?}", err);
+                    }
+                }
+            }
+        });
+    }
 }
 
-impl InternalDB {
+impl<C: ValueCodec> InternalDB<C> {
     // TODO: Should we have a new_cleaned and keep a new?
     // path example: "./tmp/data";
-    pub fn new_cleaned(path: &str, initial_time: Timestamp) -> Self {
+    pub fn new_cleaned(path: &str, initial_time: Timestamp, codec: C) -> Self {
         let txns = Arc::new(RwLock::new(HashMap::new()));
         if initial_time.value == 0 {
             panic!("DB time cannot start from 0 as it is reserved for intents")
         }
         let (sender, receiver) = mpsc::channel::<TaskQueueRequest>(1);
         let sender = Arc::new(sender);
-        let executor = Arc::new(Executor::new_cleaned(path, txns.clone(), sender.clone()));
+        let codec = Arc::new(codec);
+        let executor = Arc::new(Executor::new_cleaned(
+            path,
+            txns.clone(),
+            sender.clone(),
+            codec.clone(),
+        ));
         let txns = Arc::new(RwLock::new(HashMap::new()));
         let thread_pool = TaskQueue::new(receiver, executor.clone(), txns.clone(), sender.clone());
         InternalDB {
             executor,
+            codec,
             txns,
             clock: RwLock::new(Clock::manual(initial_time.value)),
             thread_pool,
         }
     }
 
-    pub fn new(path: &str, initial_time: Timestamp) -> Self {
+    pub fn new(path: &str, initial_time: Timestamp, codec: C) -> Self {
         let txns = Arc::new(RwLock::new(HashMap::new()));
         if initial_time.value == 0 {
             panic!("DB time cannot start from 0 as it is reserved for intents")
@@ -194,13 +747,26 @@ impl InternalDB {
         let (sender, receiver) = mpsc::channel::<TaskQueueRequest>(1);
         let sender = Arc::new(sender);
 
-        let executor = Arc::new(Executor::new(path, txns.clone(), sender.clone()));
+        let codec = Arc::new(codec);
+        let executor = Arc::new(Executor::new(
+            path,
+            txns.clone(),
+            sender.clone(),
+            codec.clone(),
+        ));
         let txns = Arc::new(RwLock::new(HashMap::new()));
         let thread_pool = TaskQueue::new(receiver, executor.clone(), txns.clone(), sender.clone());
+        let mut clock = Clock::manual(initial_time.value);
+        // the txns from before the restart are finalized and the clock moves past every
+        // timestamp in the database so that new txns don't write beneath existing versions
+        if let Some(max_timestamp) = executor.store.recover().unwrap() {
+            clock.receive_timestamp(max_timestamp);
+        }
         InternalDB {
             executor,
+            codec,
             txns,
-            clock: RwLock::new(Clock::manual(initial_time.value)),
+            clock: RwLock::new(clock),
             thread_pool,
         }
     }
@@ -223,35 +789,344 @@ impl InternalDB {
         }
     }
 
-    // TODO: Return potential error
-    pub async fn write<T: Serialize>(
+    pub async fn write<T>(&self, key: &str, value: T, txn_id: Uuid) -> DbResult<ResponseUnion>
+    where
+        C: ValueEncoder<T>,
+    {
+        self.put_raw(str_to_key(key), self.encode(&value)?, txn_id)
+            .await
+    }
+
+    pub async fn put_raw(&self, key: Key, value: Value, txn_id: Uuid) -> DbResult<ResponseUnion> {
+        let request_union = RequestUnion::Put(PutRequest { key, value });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let write_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        Ok(self
+            .executor
+            .execute_request_with_concurrency_retries(write_request)
+            .await?)
+    }
+
+    pub async fn cput<T>(
         &self,
         key: &str,
         value: T,
+        expected: Option<T>,
+        txn_id: Uuid,
+    ) -> DbResult<ResponseUnion>
+    where
+        C: ValueEncoder<T>,
+    {
+        let request_union = RequestUnion::ConditionalPut(ConditionalPutRequest {
+            key: str_to_key(key),
+            value: self.encode(&value)?,
+            expected_value: expected
+                .map(|expected| self.encode(&expected))
+                .transpose()?,
+        });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let cput_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        Ok(self
+            .executor
+            .execute_request_with_concurrency_retries(cput_request)
+            .await?)
+    }
+
+    pub async fn increment(&self, key: &str, increment: i64, txn_id: Uuid) -> DbResult<i64> {
+        let request_union = RequestUnion::Increment(IncrementRequest {
+            key: str_to_key(key),
+            increment,
+        });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let increment_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        let response = self
+            .executor
+            .execute_request_with_concurrency_retries(increment_request)
+            .await?;
+        match response {
+            ResponseUnion::Increment(increment_response) => Ok(increment_response.new_value),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn delete(&self, key: &str, txn_id: Uuid) -> DbResult<ResponseUnion> {
+        let request_union = RequestUnion::Delete(DeleteRequest {
+            key: str_to_key(key),
+        });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let delete_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        Ok(self
+            .executor
+            .execute_request_with_concurrency_retries(delete_request)
+            .await?)
+    }
+
+    pub async fn delete_range(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        txn_id: Uuid,
+    ) -> DbResult<ResponseUnion> {
+        let request_union = RequestUnion::DeleteRange(DeleteRangeRequest {
+            start_key: str_to_key(start_key),
+            end_key: str_to_key(end_key),
+        });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let delete_range_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        Ok(self
+            .executor
+            .execute_request_with_concurrency_retries(delete_range_request)
+            .await?)
+    }
+
+    pub fn savepoint(&self, txn_id: Uuid) -> DbResult<Savepoint> {
+        let txn = self.get_txn(txn_id)?;
+        let sequence = txn.read().unwrap().sequence;
+        Ok(Savepoint { txn_id, sequence })
+    }
+
+    pub fn rollback_to(&self, txn_id: Uuid, savepoint: Savepoint) -> DbResult<()> {
+        if savepoint.txn_id != txn_id {
+            return Err(DbError::InvalidSavepoint);
+        }
+        let txn = self.get_txn(txn_id)?;
+        txn.write()
+            .unwrap()
+            .rollback_to_sequence(savepoint.sequence);
+        Ok(())
+    }
+
+    pub async fn batch(
+        &self,
+        requests: Vec<RequestUnion>,
         txn_id: Uuid,
-    ) -> Result<ResponseUnion, ExecuteError> {
+    ) -> DbResult<Vec<ResponseUnion>> {
+        let request_union = RequestUnion::Batch(BatchRequest::new(requests));
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let batch_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        let response = self
+            .executor
+            .execute_request_with_concurrency_retries(batch_request)
+            .await?;
+        match response {
+            ResponseUnion::Batch(batch_response) => Ok(batch_response.responses),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn run_batch_txn<T>(
+        &self,
+        operations: Vec<BatchOperation<'_, T>>,
+    ) -> DbResult<Vec<BatchOperationResult<T>>>
+    where
+        C: ValueEncoder<T> + ValueDecoder<T>,
+    {
+        let requests = operations
+            .iter()
+            .map(|operation| {
+                Ok(match operation {
+                    BatchOperation::Put { key, value } => RequestUnion::Put(PutRequest {
+                        key: str_to_key(key),
+                        value: self.encode(value)?,
+                    }),
+                    BatchOperation::Delete { key } => RequestUnion::Delete(DeleteRequest {
+                        key: str_to_key(key),
+                    }),
+                    BatchOperation::Get { key } => RequestUnion::Get(GetRequest {
+                        key: str_to_key(key),
+                    }),
+                })
+            })
+            .collect::<DbResult<Vec<_>>>()?;
+        self.run_batch_txn_requests(requests)
+            .await?
+            .into_iter()
+            .map(|response| match response {
+                ResponseUnion::Put(_) => Ok(BatchOperationResult::Put),
+                ResponseUnion::Delete(_) => Ok(BatchOperationResult::Delete),
+                ResponseUnion::Get(get_result) => Ok(BatchOperationResult::Get(
+                    get_result
+                        .value
+                        .map(|(_, value)| self.decode::<T>(&value))
+                        .transpose()?,
+                )),
+                _ => Err(DbError::UnexpectedResponse),
+            })
+            .collect()
+    }
+
+    /**
+     * The requests must not begin, commit or abort the txn since the batch does.
+     */
+    async fn run_batch_txn_requests(
+        &self,
+        requests: Vec<RequestUnion>,
+    ) -> DbResult<Vec<ResponseUnion>> {
+        let (txn_id, txn) = self.create_txn_internal(TxnOptions::default());
+        let mut batch = Vec::with_capacity(requests.len() + 2);
+        batch.push(RequestUnion::BeginTxn(BeginTxnRequest { txn_id }));
+        batch.extend(requests);
+        batch.push(RequestUnion::CommitTxn(CommitTxnRequest {}));
+        let request = Request {
+            metadata: RequestMetadata::new(txn),
+            request_union: RequestUnion::Batch(BatchRequest::new(batch)),
+        };
+        let response = self
+            .executor
+            .execute_request_with_concurrency_retries(request)
+            .await;
+        let result = match response {
+            Ok(ResponseUnion::Batch(mut batch_response)) => {
+                // omit the responses of beginning and committing the txn
+                batch_response.responses.pop();
+                batch_response.responses.remove(0);
+                Ok(batch_response.responses)
+            }
+            Ok(_) => Err(DbError::UnexpectedResponse),
+            Err(err) => Err(DbError::from(err)),
+        };
+        // Only a batch that wasn't committed in one phase writes a txn record and intents.
+        // Those are cleaned up if the txn didn't commit.
+        let is_pending = self
+            .executor
+            .store
+            .get_transaction_record(txn_id)
+            .is_some_and(|record| record.status == TransactionStatus::PENDING);
+        if result.is_err() && is_pending {
+            if let Err(err) = self.abort_txn(txn_id).await {
+                // The txn is kept so that a pusher can abort it with its intents once
+                // the record expires
+                error!("failed to abort batch txn {}: {:?}", txn_id, err);
+                return result;
+            }
+        }
+        self.txns.write().unwrap().remove(&txn_id);
+        result
+    }
+
+    pub async fn upsert<T>(&self, key: &str, value: T) -> DbResult<()>
+    where
+        C: ValueEncoder<T>,
+    {
         let request_union = RequestUnion::Put(PutRequest {
             key: str_to_key(key),
-            value: serde_json::to_string(&value).unwrap().into_bytes(),
+            value: self.encode(&value)?,
         });
-        let txn = self.get_txn(txn_id);
-        let request_metadata = RequestMetadata { txn };
-        let write_request = Request {
+        self.run_batch_txn_requests(Vec::from([request_union]))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn read<T>(&self, key: &str, txn_id: Uuid) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.get_raw(str_to_key(key), txn_id)
+            .await?
+            .map(|value| self.decode::<T>(&value))
+            .transpose()
+    }
+
+    pub async fn get_raw(&self, key: Key, txn_id: Uuid) -> DbResult<Option<Value>> {
+        let request_union = RequestUnion::Get(GetRequest { key });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata::new(txn);
+        let read_request = Request {
             metadata: request_metadata,
             request_union,
         };
-        self.executor
-            .execute_request_with_concurrency_retries(write_request)
+        let response = self
+            .executor
+            .execute_request_with_concurrency_retries(read_request)
+            .await?;
+        match response {
+            ResponseUnion::Get(get_result) => Ok(get_result.value.map(|(_, value)| value)),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn read_for_update<T>(&self, key: &str, txn_id: Uuid) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.read_for_update_with_wait_policy(key, WaitPolicy::default(), txn_id)
             .await
     }
 
-    // TODO: Result
-    pub async fn read<T: DeserializeOwned>(&self, key: &str, txn_id: Uuid) -> Option<T> {
-        let request_union = RequestUnion::Get(GetRequest {
+    pub async fn read_for_share<T>(&self, key: &str, txn_id: Uuid) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.read_for_share_with_wait_policy(key, WaitPolicy::default(), txn_id)
+            .await
+    }
+
+    pub async fn read_for_update_with_wait_policy<T>(
+        &self,
+        key: &str,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.locking_read(key, LockStrength::Exclusive, wait_policy, txn_id)
+            .await
+    }
+
+    pub async fn read_for_share_with_wait_policy<T>(
+        &self,
+        key: &str,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.locking_read(key, LockStrength::Shared, wait_policy, txn_id)
+            .await
+    }
+
+    async fn locking_read<T>(
+        &self,
+        key: &str,
+        strength: LockStrength,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        let request_union = RequestUnion::GetForUpdate(GetForUpdateRequest {
             key: str_to_key(key),
+            strength,
         });
-        let txn = self.get_txn(txn_id);
-        let request_metadata = RequestMetadata { txn };
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata { txn, wait_policy };
         let read_request = Request {
             metadata: request_metadata,
             request_union,
@@ -259,37 +1134,134 @@ impl InternalDB {
         let response = self
             .executor
             .execute_request_with_concurrency_retries(read_request)
-            .await;
-
+            .await?;
         match response {
-            Ok(res) => match res {
-                ResponseUnion::Get(get_result) => get_result
-                    .value
-                    .and_then(|(_, value)| Some(serde_json::from_slice::<T>(&value).unwrap())),
-                _ => unreachable!(),
-            },
-            Err(err) => match err {
-                ExecuteError::ReadRefreshFailure => unreachable!(),
-                ExecuteError::TxnCommitted => todo!(),
-                ExecuteError::TxnAborted => todo!(),
-            },
+            ResponseUnion::GetForUpdate(get_result) => get_result
+                .value
+                .map(|(_, value)| self.decode::<T>(&value))
+                .transpose(),
+            _ => unreachable!(),
         }
     }
 
-    pub async fn read_without_txn<T: DeserializeOwned>(
+    pub async fn scan<T>(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(String, T)>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.scan_with_wait_policy(start_key, end_key, limit, WaitPolicy::default(), txn_id)
+            .await
+    }
+
+    pub async fn scan_with_wait_policy<T>(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(String, T)>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.scan_raw_with_wait_policy(
+            str_to_key(start_key),
+            str_to_key(end_key),
+            limit,
+            wait_policy,
+            txn_id,
+        )
+        .await?
+        .into_iter()
+        .map(|(key, value)| {
+            let key = String::from_utf8(key)
+                .map_err(|err| DbError::SerializationError(err.to_string()))?;
+            Ok((key, self.decode::<T>(&value)?))
+        })
+        .collect()
+    }
+
+    pub async fn scan_raw(
+        &self,
+        start_key: Key,
+        end_key: Key,
+        limit: usize,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(Key, Value)>> {
+        self.scan_raw_with_wait_policy(start_key, end_key, limit, WaitPolicy::default(), txn_id)
+            .await
+    }
+
+    async fn scan_raw_with_wait_policy(
         &self,
-        _key: &str,
-        _timestamp: Timestamp,
-    ) -> T {
-        todo!()
+        start_key: Key,
+        end_key: Key,
+        limit: usize,
+        wait_policy: WaitPolicy,
+        txn_id: Uuid,
+    ) -> DbResult<Vec<(Key, Value)>> {
+        let request_union = RequestUnion::Scan(ScanRequest {
+            start_key,
+            end_key,
+            max_result_count: limit,
+        });
+        let txn = self.get_txn(txn_id)?;
+        let request_metadata = RequestMetadata { txn, wait_policy };
+        let scan_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        let response = self
+            .executor
+            .execute_request_with_concurrency_retries(scan_request)
+            .await?;
+        match response {
+            ResponseUnion::Scan(scan_result) => Ok(scan_result
+                .results
+                .into_iter()
+                .map(|(mvcc_key, value)| (mvcc_key.key, value))
+                .collect()),
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn read_without_txn<T>(&self, key: &str, timestamp: Timestamp) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        let request_union = RequestUnion::Get(GetRequest {
+            key: str_to_key(key),
+        });
+        let request_metadata = RequestMetadata::new_non_txn(HLCTimestamp::new(timestamp.value, 0));
+        let read_request = Request {
+            metadata: request_metadata,
+            request_union,
+        };
+        let response = self
+            .executor
+            .execute_request_with_concurrency_retries(read_request)
+            .await?;
+        match response {
+            ResponseUnion::Get(get_result) => get_result
+                .value
+                .map(|(_, value)| self.decode::<T>(&value))
+                .transpose(),
+            _ => unreachable!(),
+        }
     }
 
-    // pub async fn run_txn<Fut>(&self, f: impl FnOnce(db: &Self) -> Fut () where
-    // Fut: Future<Output = bool>) {}
+    pub async fn begin_txn(&self) -> DbResult<Uuid> {
+        self.begin_txn_with_options(TxnOptions::default()).await
+    }
 
-    pub async fn begin_txn(&self) -> Uuid {
-        let (txn_id, txn) = self.create_txn_internal();
-        let request_metadata = RequestMetadata { txn };
+    pub async fn begin_txn_with_options(&self, options: TxnOptions) -> DbResult<Uuid> {
+        let (txn_id, txn) = self.create_txn_internal(options);
+        let request_metadata = RequestMetadata::new(txn);
         let txn_request = RequestUnion::BeginTxn(BeginTxnRequest { txn_id });
         let request = Request {
             metadata: request_metadata,
@@ -297,32 +1269,61 @@ impl InternalDB {
         };
         self.executor
             .execute_request_with_concurrency_retries(request)
-            .await
-            .unwrap();
+            .await?;
+        Ok(txn_id)
+    }
 
-        txn_id
+    /**
+     * Read-only txns don't have a txn record since they never lay intents. Their reads
+     * don't update the timestamp oracle, so the timestamp can't be above the current
+     * time. Otherwise, a writer could still commit beneath it and change what the txn
+     * reads.
+     */
+    pub async fn begin_read_only_txn_at(&self, timestamp: Timestamp) -> DbResult<Uuid> {
+        let timestamp = HLCTimestamp::new(timestamp.value, 0);
+        if timestamp > self.now_hlc() {
+            return Err(DbError::InvalidReadTimestamp);
+        }
+        let txn_id = Uuid::new_v4();
+        let txn = Txn::new_read_only_link(txn_id, timestamp);
+        self.txns.write().unwrap().insert(txn_id, txn);
+        Ok(txn_id)
     }
 
-    pub async fn abort_txn(&self, txn_id: Uuid) {
+    pub async fn abort_txn(&self, txn_id: Uuid) -> DbResult<()> {
         let txn_request = RequestUnion::AbortTxn(AbortTxnRequest {});
-        let txn = self.get_txn(txn_id);
-        let request_metadata = RequestMetadata { txn };
+        let txn = self.get_txn(txn_id)?;
+        if txn.read().unwrap().read_only {
+            self.txns.write().unwrap().remove(&txn_id);
+            return Ok(());
+        }
+        let request_metadata = RequestMetadata::new(txn);
 
         let request = Request {
             metadata: request_metadata,
             request_union: txn_request,
         };
-        let _response = self
-            .executor
+        self.executor
             .execute_request_with_concurrency_retries(request)
-            .await;
+            .await?;
+        Ok(())
     }
 
     // TODO: We should return the final timestamps if possible - easier for testing
     // Returns whether the commit was successful or if a retry was necessary
-    pub async fn commit_txn(&self, txn_id: Uuid) -> CommitTxnResult {
-        let txn = self.get_txn(txn_id);
-        let request_metadata = RequestMetadata { txn };
+    pub async fn commit_txn(&self, txn_id: Uuid) -> DbResult<CommitTxnSuccess> {
+        let txn = self.get_txn(txn_id)?;
+        {
+            // read-only txns never need a read refresh since their timestamp never moves
+            let txn = txn.read().unwrap();
+            if txn.read_only {
+                self.txns.write().unwrap().remove(&txn_id);
+                return Ok(CommitTxnSuccess {
+                    commit_timestamp: txn.read_timestamp,
+                });
+            }
+        }
+        let request_metadata = RequestMetadata::new(txn);
         let txn_request = RequestUnion::CommitTxn(CommitTxnRequest {});
         let request = Request {
             metadata: request_metadata,
@@ -331,67 +1332,209 @@ impl InternalDB {
         let response = self
             .executor
             .execute_request_with_concurrency_retries(request)
-            .await;
+            .await?;
         match response {
-            Ok(res) => match res {
-                ResponseUnion::CommitTxn(commit_res) => {
-                    CommitTxnResult::Success(CommitTxnSuccess {
-                        commit_timestamp: commit_res.commit_timestamp,
-                    })
-                }
-                _ => unreachable!(),
-            },
-            Err(err) => match err {
-                ExecuteError::ReadRefreshFailure => {
-                    CommitTxnResult::Fail(CommitTxnFailureReason::ReadRefreshFail)
-                }
-                ExecuteError::TxnCommitted => todo!(),
-                ExecuteError::TxnAborted => {
-                    CommitTxnResult::Fail(CommitTxnFailureReason::TxnAborted)
-                }
-            },
+            ResponseUnion::CommitTxn(commit_res) => Ok(CommitTxnSuccess {
+                commit_timestamp: commit_res.commit_timestamp,
+            }),
+            _ => unreachable!(),
         }
     }
 
-    fn create_txn_internal(&self) -> (Uuid, TxnLink) {
+    pub fn run_gc(&self, threshold: Timestamp) -> DbResult<GcStats> {
+        let threshold = self.to_gc_threshold(threshold)?;
+        Ok(self.executor.store.mvcc_gc(threshold)?)
+    }
+
+    pub fn advance_gc_threshold(&self, threshold: Timestamp) -> DbResult<()> {
+        let threshold = self.to_gc_threshold(threshold)?;
+        self.executor
+            .store
+            .storage
+            .advance_gc_threshold(threshold)?;
+        Ok(())
+    }
+
+    fn to_gc_threshold(&self, threshold: Timestamp) -> DbResult<HLCTimestamp> {
+        let threshold = HLCTimestamp::new(threshold.value, 0);
+        if threshold > self.now_hlc() {
+            return Err(DbError::InvalidGcThreshold);
+        }
+        Ok(threshold)
+    }
+
+    fn create_txn_internal(&self, options: TxnOptions) -> (Uuid, TxnLink) {
         let txn_id = Uuid::new_v4();
-        let txn = Txn::new_link(txn_id, self.now_hlc());
+        let mut txn = Txn::new(txn_id, self.now_hlc());
+        txn.priority = options.priority;
+        let txn = Arc::new(RwLock::new(txn));
         let mut txns = self.txns.write().unwrap();
         txns.insert(txn_id, txn.clone());
         (txn_id, txn)
     }
 
-    fn get_txn(&self, txn_id: Uuid) -> TxnLink {
+    fn encode<T: ?Sized>(&self, value: &T) -> DbResult<Value>
+    where
+        C: ValueEncoder<T>,
+    {
+        Ok(self.codec.encode(value)?)
+    }
+
+    fn decode<T>(&self, value: &[u8]) -> DbResult<T>
+    where
+        C: ValueDecoder<T>,
+    {
+        Ok(self.codec.decode(value)?)
+    }
+
+    fn get_txn(&self, txn_id: Uuid) -> DbResult<TxnLink> {
         let txns = self.txns.read().unwrap();
         let txn = txns.get(&txn_id);
         match txn {
-            Some(txn_link) => txn_link.clone(),
-            None => {
-                panic!("No txn found for {}", txn_id)
-            }
+            Some(txn_link) => Ok(txn_link.clone()),
+            None => Err(DbError::UnknownTxn(txn_id)),
         }
     }
 }
 
-impl Drop for InternalDB {
+impl<C> Drop for InternalDB<C> {
     fn drop(&mut self) {}
 }
 
-pub struct TxnContext {
+pub struct TxnContext<C = JsonCodec> {
     txn_id: Uuid,
-    db: Arc<InternalDB>,
+    db: Arc<InternalDB<C>>,
 }
 
-impl TxnContext {
-    pub async fn write<T: Serialize>(
-        &self,
-        key: &str,
-        value: T,
-    ) -> Result<ResponseUnion, ExecuteError> {
+impl<C: ValueCodec> TxnContext<C> {
+    pub async fn write<T>(&self, key: &str, value: T) -> DbResult<ResponseUnion>
+    where
+        C: ValueEncoder<T>,
+    {
         self.db.write(key, value, self.txn_id).await
     }
 
-    pub async fn read<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+    pub async fn read<T>(&self, key: &str) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
         self.db.read(key, self.txn_id).await
     }
+
+    pub async fn put_raw(&self, key: Key, value: Value) -> DbResult<ResponseUnion> {
+        self.db.put_raw(key, value, self.txn_id).await
+    }
+
+    pub async fn get_raw(&self, key: Key) -> DbResult<Option<Value>> {
+        self.db.get_raw(key, self.txn_id).await
+    }
+
+    pub async fn read_for_update<T>(&self, key: &str) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.read_for_update(key, self.txn_id).await
+    }
+
+    pub async fn read_for_share<T>(&self, key: &str) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.read_for_share(key, self.txn_id).await
+    }
+
+    pub async fn read_for_update_with_wait_policy<T>(
+        &self,
+        key: &str,
+        wait_policy: WaitPolicy,
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db
+            .read_for_update_with_wait_policy(key, wait_policy, self.txn_id)
+            .await
+    }
+
+    pub async fn read_for_share_with_wait_policy<T>(
+        &self,
+        key: &str,
+        wait_policy: WaitPolicy,
+    ) -> DbResult<Option<T>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db
+            .read_for_share_with_wait_policy(key, wait_policy, self.txn_id)
+            .await
+    }
+
+    pub async fn cput<T>(&self, key: &str, value: T, expected: Option<T>) -> DbResult<ResponseUnion>
+    where
+        C: ValueEncoder<T>,
+    {
+        self.db.cput(key, value, expected, self.txn_id).await
+    }
+
+    pub async fn increment(&self, key: &str, increment: i64) -> DbResult<i64> {
+        self.db.increment(key, increment, self.txn_id).await
+    }
+
+    pub async fn batch(&self, requests: Vec<RequestUnion>) -> DbResult<Vec<ResponseUnion>> {
+        self.db.batch(requests, self.txn_id).await
+    }
+
+    pub fn savepoint(&self) -> DbResult<Savepoint> {
+        self.db.savepoint(self.txn_id)
+    }
+
+    pub fn rollback_to(&self, savepoint: Savepoint) -> DbResult<()> {
+        self.db.rollback_to(self.txn_id, savepoint)
+    }
+
+    pub async fn delete(&self, key: &str) -> DbResult<ResponseUnion> {
+        self.db.delete(key, self.txn_id).await
+    }
+
+    pub async fn delete_range(&self, start_key: &str, end_key: &str) -> DbResult<ResponseUnion> {
+        self.db.delete_range(start_key, end_key, self.txn_id).await
+    }
+
+    pub async fn scan<T>(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+    ) -> DbResult<Vec<(String, T)>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db.scan(start_key, end_key, limit, self.txn_id).await
+    }
+
+    pub async fn scan_raw(
+        &self,
+        start_key: Key,
+        end_key: Key,
+        limit: usize,
+    ) -> DbResult<Vec<(Key, Value)>> {
+        self.db
+            .scan_raw(start_key, end_key, limit, self.txn_id)
+            .await
+    }
+
+    pub async fn scan_with_wait_policy<T>(
+        &self,
+        start_key: &str,
+        end_key: &str,
+        limit: usize,
+        wait_policy: WaitPolicy,
+    ) -> DbResult<Vec<(String, T)>>
+    where
+        C: ValueDecoder<T>,
+    {
+        self.db
+            .scan_with_wait_policy(start_key, end_key, limit, wait_policy, self.txn_id)
+            .await
+    }
 }
@@ -20,15 +20,15 @@ mod test {
             let key_a_value = 14;
             let key_b_value = 20;
 
-            let txn1 = db.begin_txn().await;
+            let txn1 = db.begin_txn().await.unwrap();
             db.write(key_a, key_a_value, txn1).await.unwrap();
             db.write(key_b, key_b_value, txn1).await.unwrap();
-            db.commit_txn(txn1).await;
+            db.commit_txn(txn1).await.unwrap();
 
-            let read_txn = db.begin_txn().await;
-            let db_key_a_value = db.read::<i32>(key_a, read_txn).await.unwrap();
+            let read_txn = db.begin_txn().await.unwrap();
+            let db_key_a_value = db.read::<i32>(key_a, read_txn).await.unwrap().unwrap();
             assert_eq!(key_a_value, db_key_a_value);
-            let db_key_b_value = db.read::<i32>(key_b, read_txn).await.unwrap();
+            let db_key_b_value = db.read::<i32>(key_b, read_txn).await.unwrap().unwrap();
             assert_eq!(key_b_value, db_key_b_value);
         }
     }
@@ -39,8 +39,8 @@ mod test {
         // earlier transaction
 
         /**
-         * A read running into an uncommitted intent with a lower timestamp will
-         * wait for the earlier transaction to finalize.
+         * A read running into an uncommitted intent with a lower timestamp pushes the
+         * earlier transaction above its read timestamp instead of waiting for it.
          *
          * A read running into an uncommitted intent with a higher timestamp ignores the
          * intent and does not need to wait.
@@ -55,26 +55,23 @@ mod test {
                 };
 
                 #[tokio::test]
-                async fn read_waits_for_uncommitted_write() {
+                async fn read_pushes_uncommitted_write() {
                     let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
-                    let write_txn = db.begin_txn().await;
+                    let write_txn = db.begin_txn().await.unwrap();
                     let key = "foo";
-                    db.write(key, 12, write_txn).await;
+                    db.write(key, 12, write_txn).await.unwrap();
                     db.set_time(Timestamp::new(12));
-                    let read_txn = db.begin_txn().await;
+                    let read_txn = db.begin_txn().await.unwrap();
 
-                    let db_1 = db.clone();
-                    let task_1 = tokio::spawn(async move {
-                        let read_res = db_1.read::<i32>(key, read_txn).await;
-                        db_1.commit_txn(read_txn).await;
-                        assert_eq!(read_res, Some(12));
-                    });
+                    let read_res = db.read::<i32>(key, read_txn).await.unwrap();
+                    assert_eq!(read_res, None);
+                    let read_commit = db.commit_txn(read_txn).await.unwrap();
 
-                    let db_2 = db.clone();
-                    let task_2 = tokio::spawn(async move {
-                        db_2.commit_txn(write_txn).await;
-                    });
-                    tokio::try_join!(task_1, task_2).unwrap();
+                    // the writer commits above the reader's timestamp
+                    let write_commit = db.commit_txn(write_txn).await.unwrap();
+                    assert!(write_commit.commit_timestamp > read_commit.commit_timestamp);
+                    let read_txn = db.begin_txn().await.unwrap();
+                    assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(12)));
                 }
             }
 
@@ -91,13 +88,13 @@ mod test {
                 #[tokio::test]
                 async fn ignores_intent_with_higher_timestamp() {
                     let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
-                    let read_txn = db.begin_txn().await;
+                    let read_txn = db.begin_txn().await.unwrap();
                     let key = "foo";
 
                     db.set_time(Timestamp::new(12));
-                    let write_txn = db.begin_txn().await;
+                    let write_txn = db.begin_txn().await.unwrap();
                     db.write(key, 12, write_txn).await;
-                    let read_res = db.read::<i32>(key, read_txn).await;
+                    let read_res = db.read::<i32>(key, read_txn).await.unwrap();
                     assert!(read_res.is_none());
                 }
             }
@@ -111,7 +108,7 @@ mod test {
             mod run_into_uncommitted_intent {
                 use std::sync::Arc;
 
-                use crate::db::db::{CommitTxnResult, Timestamp, DB};
+                use crate::db::db::{Timestamp, DB};
 
                 use crate::{
                     helpers::test_helpers::create_temp_dir,
@@ -121,8 +118,8 @@ mod test {
                 #[tokio::test]
                 async fn write_waits_for_uncommitted_write() {
                     let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
-                    let txn_1 = db.begin_txn().await;
-                    let txn_2 = db.begin_txn().await;
+                    let txn_1 = db.begin_txn().await.unwrap();
+                    let txn_2 = db.begin_txn().await.unwrap();
                     let key = "baz";
                     db.write(key, 12, txn_1).await;
                     db.set_time(Timestamp::new(15));
@@ -133,11 +130,11 @@ mod test {
                         db_1.write(key, 100, txn_2).await;
                         let commit_res = db_1.commit_txn(txn_2).await;
                         match commit_res {
-                            CommitTxnResult::Success(res) => {
+                            Ok(res) => {
                                 assert_eq!(res.commit_timestamp, HLCTimestamp::new(10, 2));
                                 res.commit_timestamp
                             }
-                            CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                            Err(_) => panic!("failed to commit"),
                         };
                     });
 
@@ -146,11 +143,11 @@ mod test {
                     let task_2 = tokio::spawn(async move {
                         let commit_res = db_2.commit_txn(txn_1).await;
                         match commit_res {
-                            CommitTxnResult::Success(res) => {
+                            Ok(res) => {
                                 assert_eq!(res.commit_timestamp, HLCTimestamp::new(10, 1));
                                 res.commit_timestamp
                             }
-                            CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                            Err(_) => panic!("failed to commit"),
                         };
                         println!("Finished committing txn_1");
                     });
@@ -164,7 +161,7 @@ mod test {
                 use std::sync::Arc;
 
                 use crate::{
-                    db::db::{CommitTxnResult, Timestamp, DB},
+                    db::db::{Timestamp, DB},
                     helpers::test_helpers::create_temp_dir,
                 };
 
@@ -174,21 +171,21 @@ mod test {
                     let key = "foo";
 
                     // begin txn1
-                    let write_txn_1 = db.begin_txn().await;
+                    let write_txn_1 = db.begin_txn().await.unwrap();
 
                     // time = 12
                     db.set_time(Timestamp::new(12));
 
                     // begin txn2. txn2 writes and commits
-                    let write_txn_2 = db.begin_txn().await;
+                    let write_txn_2 = db.begin_txn().await.unwrap();
                     db.write(key, 12, write_txn_2).await;
                     let txn_2_commit_res = db.commit_txn(write_txn_2).await;
                     let txn_2_commit_timestamp = match txn_2_commit_res {
-                        CommitTxnResult::Success(res) => {
+                        Ok(res) => {
                             assert_eq!(res.commit_timestamp.wall_time, 12);
                             res.commit_timestamp
                         }
-                        CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                        Err(_) => panic!("failed to commit"),
                     };
 
                     // txn1 writes
@@ -198,13 +195,13 @@ mod test {
                     let commit_res = db.commit_txn(write_txn_1).await;
 
                     match commit_res {
-                        CommitTxnResult::Success(res) => {
+                        Ok(res) => {
                             assert_eq!(
                                 res.commit_timestamp,
                                 txn_2_commit_timestamp.next_logical_timestamp()
                             );
                         }
-                        CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                        Err(_) => panic!("failed to commit"),
                     }
                 }
             }
@@ -219,7 +216,7 @@ mod test {
             use std::sync::Arc;
 
             use crate::{
-                db::db::{CommitTxnResult, Timestamp, DB},
+                db::db::{Timestamp, DB},
                 helpers::test_helpers::create_temp_dir,
             };
 
@@ -228,29 +225,29 @@ mod test {
                 let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
                 let key = "foo";
 
-                let write_txn = db.begin_txn().await;
+                let write_txn = db.begin_txn().await.unwrap();
 
                 db.set_time(Timestamp::new(20));
 
-                let read_txn = db.begin_txn().await;
-                db.read::<i32>(key, read_txn).await;
+                let read_txn = db.begin_txn().await.unwrap();
+                db.read::<i32>(key, read_txn).await.unwrap();
 
                 db.write(key, 100, write_txn).await;
 
                 let write_txn_commit_res = db.commit_txn(write_txn).await;
                 match write_txn_commit_res {
-                    CommitTxnResult::Success(res) => {
+                    Ok(res) => {
                         assert_eq!(res.commit_timestamp.wall_time, 20);
                     }
-                    CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                    Err(_) => panic!("failed to commit"),
                 };
 
                 let read_txn_commit_res = db.commit_txn(read_txn).await;
                 match read_txn_commit_res {
-                    CommitTxnResult::Success(res) => {
+                    Ok(res) => {
                         assert_eq!(res.commit_timestamp.wall_time, 20);
                     }
-                    CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                    Err(_) => panic!("failed to commit"),
                 };
             }
         }
@@ -263,7 +260,7 @@ mod test {
             use std::sync::Arc;
 
             use crate::{
-                db::db::{CommitTxnResult, Timestamp, DB},
+                db::db::{Timestamp, DB},
                 helpers::test_helpers::create_temp_dir,
             };
 
@@ -273,15 +270,15 @@ mod test {
                 let read_key = "foo";
                 let write_key = "bar";
 
-                let txn_1 = db.begin_txn().await;
-                db.read::<i32>(read_key, txn_1).await;
+                let txn_1 = db.begin_txn().await.unwrap();
+                db.read::<i32>(read_key, txn_1).await.unwrap();
 
                 db.set_time(Timestamp::new(15));
 
                 // txn_2 writes and commits
-                let txn_2 = db.begin_txn().await;
+                let txn_2 = db.begin_txn().await.unwrap();
                 db.write(write_key, "foo", txn_2).await;
-                db.commit_txn(txn_2).await;
+                db.commit_txn(txn_2).await.unwrap();
 
                 // txn_1 writes - timestamp gets advanced to 15 due to
                 // write-write conflict
@@ -289,12 +286,32 @@ mod test {
 
                 let res = db.commit_txn(txn_1).await;
                 match res {
-                    CommitTxnResult::Success(res) => {
+                    Ok(res) => {
                         assert_eq!(res.commit_timestamp.wall_time, 15);
                     }
-                    CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                    Err(_) => panic!("failed to commit"),
                 };
             }
+
+            #[tokio::test]
+            async fn read_refresh_skips_own_intents() {
+                let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+                let key = "foo";
+
+                let txn_1 = db.begin_txn().await.unwrap();
+                db.read::<i32>(key, txn_1).await.unwrap();
+                db.write(key, 12, txn_1).await.unwrap();
+
+                // txn_2 reads above txn_1's intent and pushes txn_1
+                db.set_time(Timestamp::new(15));
+                let txn_2 = db.begin_txn().await.unwrap();
+                assert_eq!(db.read::<i32>(key, txn_2).await, Ok(None));
+                db.commit_txn(txn_2).await.unwrap();
+
+                // txn_1's own intent doesn't fail the refresh to the pushed timestamp
+                let res = db.commit_txn(txn_1).await.unwrap();
+                assert!(res.commit_timestamp.wall_time >= 15);
+            }
         }
 
         // Advancing a transaction read timestamp from ta to tb is possible
@@ -305,7 +322,7 @@ mod test {
             use std::sync::Arc;
 
             use crate::{
-                db::db::{CommitTxnResult, Timestamp, DB},
+                db::db::{DbError, Timestamp, DB},
                 helpers::test_helpers::create_temp_dir,
             };
 
@@ -316,34 +333,30 @@ mod test {
                 let read_key = "foo";
                 let write_key = "bar";
 
-                let txn_1 = db.begin_txn().await;
-                db.read::<i32>(read_key, txn_1).await;
+                let txn_1 = db.begin_txn().await.unwrap();
+                db.read::<i32>(read_key, txn_1).await.unwrap();
 
                 db.set_time(Timestamp::new(15));
 
                 // txn_2 writes and commits
-                let txn_2 = db.begin_txn().await;
+                let txn_2 = db.begin_txn().await.unwrap();
                 db.write(write_key, "foo", txn_2).await;
                 db.write(read_key, 12, txn_2).await;
                 let txn_2_res = db.commit_txn(txn_2).await;
                 match txn_2_res {
-                    CommitTxnResult::Success(res) => {
+                    Ok(res) => {
                         assert_eq!(res.commit_timestamp.wall_time, 15);
                     }
-                    CommitTxnResult::Fail(_) => panic!("failed to commit"),
+                    Err(_) => panic!("failed to commit"),
                 };
 
                 // txn_1 writes - timestamp gets advanced to 15 due to
                 // write-write conflict
                 db.write(write_key, "bar", txn_1).await;
 
+                // read_key was updated in (10, 15], so the read can't be refreshed
                 let res = db.commit_txn(txn_1).await;
-                match res {
-                    CommitTxnResult::Success(res) => {
-                        assert_eq!(res.commit_timestamp.wall_time, 15);
-                    }
-                    CommitTxnResult::Fail(_) => panic!("failed to commit"),
-                };
+                assert!(matches!(res, Err(DbError::ReadRefreshFailure)));
             }
         }
     }
@@ -360,19 +373,19 @@ mod test {
             let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
             let key = "foo";
 
-            let txn_to_abort = db.begin_txn().await;
+            let txn_to_abort = db.begin_txn().await.unwrap();
             db.write(key, 12, txn_to_abort).await;
-            db.abort_txn(txn_to_abort).await;
+            db.abort_txn(txn_to_abort).await.unwrap();
 
-            let read_txn = db.begin_txn().await;
-            let res = db.read::<i32>(key, read_txn).await;
-            db.abort_txn(read_txn).await;
+            let read_txn = db.begin_txn().await.unwrap();
+            let res = db.read::<i32>(key, read_txn).await.unwrap();
+            db.abort_txn(read_txn).await.unwrap();
             assert!(res.is_none());
 
-            let write_txn = db.begin_txn().await;
+            let write_txn = db.begin_txn().await.unwrap();
             db.write(key, 100, write_txn).await;
             println!("Foooo");
-            db.commit_txn(write_txn).await;
+            db.commit_txn(write_txn).await.unwrap();
             println!("ENDDD");
         }
     }
@@ -382,7 +395,7 @@ mod test {
         use std::sync::Arc;
 
         use crate::{
-            db::db::{CommitTxnResult, Timestamp, DB},
+            db::db::{Timestamp, DB},
             helpers::test_helpers::create_temp_dir,
             storage::str_to_key,
         };
@@ -390,8 +403,8 @@ mod test {
         #[tokio::test]
         async fn conflicting_writes() {
             let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
-            let txn1 = db.begin_txn().await;
-            let txn2 = db.begin_txn().await;
+            let txn1 = db.begin_txn().await.unwrap();
+            let txn2 = db.begin_txn().await.unwrap();
             println!("Txn1 is: {}", txn1);
             println!("Txn2 is: {}", txn2);
 
@@ -422,11 +435,11 @@ mod test {
                         println!("\ntxn1 committing!");
                         let commit_res = db_1.commit_txn(txn1).await;
                         match commit_res {
-                            CommitTxnResult::Success(_) => {
+                            Ok(_) => {
                                 println!("\ntxn1 succeeded in committing");
                                 return true;
                             }
-                            CommitTxnResult::Fail(_) => {
+                            Err(_) => {
                                 println!("\ntxn1 failed in committing");
                                 return false;
                             }
@@ -448,11 +461,11 @@ mod test {
                         println!("\ntxn2 committing!");
                         let commit_res = db_2.commit_txn(txn2).await;
                         match commit_res {
-                            CommitTxnResult::Success(_) => {
+                            Ok(_) => {
                                 println!("txn2 succeeded in committing");
                                 return true;
                             }
-                            CommitTxnResult::Fail(_) => {
+                            Err(_) => {
                                 println!("txn2 failed in committing");
                                 return false;
                             }
@@ -467,24 +480,24 @@ mod test {
             let (did_txn1_commit, did_txn2_commit) = tokio::try_join!(task_1, task_2).unwrap();
             // make sure not both of them committed
             assert!(!(did_txn1_commit && did_txn2_commit));
-            let read_txn = db.begin_txn().await;
+            let read_txn = db.begin_txn().await.unwrap();
             if did_txn1_commit {
                 println!("Reading since txn1 committed");
                 println!("Reading key a");
-                let key_a_value = db.read::<i32>(key_a, read_txn).await.unwrap();
+                let key_a_value = db.read::<i32>(key_a, read_txn).await.unwrap().unwrap();
                 assert_eq!(key_a_value, key_a_txn1_value);
 
                 println!("Reading key b");
-                let key_b_value = db.read::<i32>(key_b, read_txn).await.unwrap();
+                let key_b_value = db.read::<i32>(key_b, read_txn).await.unwrap().unwrap();
                 assert_eq!(key_b_value, key_b_txn1_value);
             } else if did_txn2_commit {
                 println!("Reading since txn2 committed");
                 println!("Reading key a");
-                let key_a_value = db.read::<i32>(key_a, read_txn).await.unwrap();
+                let key_a_value = db.read::<i32>(key_a, read_txn).await.unwrap().unwrap();
                 assert_eq!(key_a_value, key_a_txn2_value);
 
                 println!("Reading key b");
-                let key_b_value = db.read::<i32>(key_b, read_txn).await.unwrap();
+                let key_b_value = db.read::<i32>(key_b, read_txn).await.unwrap().unwrap();
                 assert_eq!(key_b_value, key_b_txn2_value);
             }
         }
@@ -495,7 +508,7 @@ mod test {
         use std::sync::Arc;
 
         use crate::{
-            db::db::{Timestamp, DB},
+            db::db::{DbError, Timestamp, TxnOptions, TxnPriority, DB},
             helpers::test_helpers::create_temp_dir,
         };
 
@@ -505,10 +518,12 @@ mod test {
             db.run_txn(|txn_context| async move {
                 let key = "foo";
                 txn_context.write(key, 12).await.unwrap();
-                let read = txn_context.read::<i32>(key).await;
+                let read = txn_context.read::<i32>(key).await.unwrap();
                 assert_eq!(read, Some(12));
+                Ok::<(), DbError>(())
             })
-            .await;
+            .await
+            .unwrap();
         }
 
         #[tokio::test]
@@ -518,10 +533,12 @@ mod test {
                 let key = "foo";
                 txn_context.write(key, 12).await;
                 txn_context.write(key, 100).await;
-                let read = txn_context.read::<i32>(key).await;
+                let read = txn_context.read::<i32>(key).await.unwrap();
                 assert_eq!(read, Some(100));
+                Ok::<(), DbError>(())
             })
-            .await;
+            .await
+            .unwrap();
         }
 
         #[tokio::test]
@@ -533,24 +550,1683 @@ mod test {
             let task_1 = tokio::spawn(async move {
                 db_1.run_txn(|txn_context| async move {
                     txn_context.write(key, 88).await.unwrap();
-                    let read = txn_context.read::<i32>(key).await;
+                    let read = txn_context.read::<i32>(key).await.unwrap();
                     println!("txn1 finished");
                     assert_eq!(read, Some(88));
+                    Ok::<(), DbError>(())
                 })
                 .await
+                .unwrap()
             });
 
             let db_2 = db.clone();
             let task_2 = tokio::spawn(async move {
                 db_2.run_txn(|txn_context| async move {
                     txn_context.write(key, 12).await.unwrap();
-                    let read = txn_context.read::<i32>(key).await;
+                    let read = txn_context.read::<i32>(key).await.unwrap();
                     println!("txn2 finished");
                     assert_eq!(read, Some(12));
+                    Ok::<(), DbError>(())
                 })
-                .await;
+                .await
+                .unwrap();
             });
             tokio::try_join!(task_1, task_2).unwrap();
         }
+
+        #[tokio::test]
+        async fn returns_value() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let value = db
+                .run_txn(|txn_context| async move {
+                    txn_context.write("foo", 12).await?;
+                    txn_context.increment("foo", 3).await
+                })
+                .await;
+            assert_eq!(value, Ok(15));
+        }
+
+        #[tokio::test]
+        async fn error_aborts_txn() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let res = db
+                .run_txn(|txn_context| async move {
+                    txn_context.write("foo", 12).await?;
+                    txn_context.read::<String>("foo").await
+                })
+                .await;
+            assert!(matches!(res, Err(DbError::SerializationError(_))));
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", read_txn).await, Ok(None));
+        }
+
+        #[tokio::test]
+        async fn panic_aborts_txn() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let db_1 = db.clone();
+            let task = tokio::spawn(async move {
+                db_1.run_txn(|txn_context| async move {
+                    txn_context.write("foo", 12).await?;
+                    panic!("transaction panicked");
+                    #[allow(unreachable_code)]
+                    Ok::<(), DbError>(())
+                })
+                .await
+            });
+            assert!(task.await.unwrap_err().is_panic());
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", read_txn).await, Ok(None));
+        }
+
+        #[tokio::test]
+        async fn error_is_returned_if_txn_was_aborted() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let db_1 = db.clone();
+            let res = db
+                .run_txn(|txn_context| {
+                    let db_1 = db_1.clone();
+                    async move {
+                        txn_context.write("foo", 12).await?;
+                        // a pusher of a higher priority class aborts the txn
+                        let high_txn = db_1
+                            .begin_txn_with_options(TxnOptions {
+                                priority: TxnPriority::HIGH,
+                            })
+                            .await?;
+                        db_1.write("foo", 13, high_txn).await?;
+                        db_1.commit_txn(high_txn).await?;
+                        Err::<(), DbError>(DbError::InvalidSavepoint)
+                    }
+                })
+                .await;
+            assert_eq!(res, Err(DbError::InvalidSavepoint));
+        }
+    }
+
+    #[cfg(test)]
+    mod scan {
+        use std::sync::Arc;
+
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn scan_committed_keys() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let write_txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, write_txn).await.unwrap();
+            db.write("banana", 2, write_txn).await.unwrap();
+            db.write("cherry", 3, write_txn).await.unwrap();
+            db.write("durian", 4, write_txn).await.unwrap();
+            db.commit_txn(write_txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            let res = db
+                .scan::<i32>("apple", "durian", 10, read_txn)
+                .await
+                .unwrap();
+            assert_eq!(
+                res,
+                Vec::from([
+                    ("apple".to_owned(), 1),
+                    ("banana".to_owned(), 2),
+                    ("cherry".to_owned(), 3)
+                ])
+            );
+
+            let res = db.scan::<i32>("b", "z", 2, read_txn).await.unwrap();
+            assert_eq!(
+                res,
+                Vec::from([("banana".to_owned(), 2), ("cherry".to_owned(), 3)])
+            );
+        }
+
+        #[tokio::test]
+        async fn scan_own_uncommitted_writes() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let write_txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, write_txn).await.unwrap();
+            db.commit_txn(write_txn).await.unwrap();
+
+            db.run_txn(|txn_context| async move {
+                txn_context.write("banana", 2).await.unwrap();
+                let res = txn_context.scan::<i32>("a", "c", 10).await.unwrap();
+                assert_eq!(
+                    res,
+                    Vec::from([("apple".to_owned(), 1), ("banana".to_owned(), 2)])
+                );
+                Ok::<(), DbError>(())
+            })
+            .await
+            .unwrap();
+        }
+
+        #[tokio::test]
+        async fn scan_pushes_uncommitted_write_in_range() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let write_txn = db.begin_txn().await.unwrap();
+            let read_txn = db.begin_txn().await.unwrap();
+            db.write("banana", 2, write_txn).await.unwrap();
+
+            let res = db
+                .scan::<i32>("apple", "cherry", 10, read_txn)
+                .await
+                .unwrap();
+            assert_eq!(res, Vec::new());
+            db.commit_txn(write_txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            let res = db
+                .scan::<i32>("apple", "cherry", 10, read_txn)
+                .await
+                .unwrap();
+            assert_eq!(res, Vec::from([("banana".to_owned(), 2)]));
+        }
+    }
+
+    #[cfg(test)]
+    mod read_without_txn {
+        use crate::{
+            db::db::{Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn reads_snapshot_at_timestamp() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.write(key, 12, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            db.set_time(Timestamp::new(20));
+            let txn = db.begin_txn().await.unwrap();
+            db.write(key, 15, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(5))
+                    .await
+                    .unwrap(),
+                None
+            );
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(15))
+                    .await
+                    .unwrap(),
+                Some(12)
+            );
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(25))
+                    .await
+                    .unwrap(),
+                Some(15)
+            );
+        }
+
+        #[tokio::test]
+        async fn read_bumps_later_writer() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.read_without_txn::<i32>(key, Timestamp::new(20))
+                .await
+                .unwrap();
+            db.write(key, 12, txn).await.unwrap();
+            match db.commit_txn(txn).await {
+                Ok(res) => {
+                    assert!(res.commit_timestamp.wall_time >= 20);
+                }
+                Err(_) => panic!("failed to commit"),
+            }
+        }
+
+        #[tokio::test]
+        async fn read_pushes_uncommitted_write() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let write_txn = db.begin_txn().await.unwrap();
+            db.write(key, 12, write_txn).await.unwrap();
+
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(15)).await,
+                Ok(None)
+            );
+
+            // the write is committed above the read timestamp
+            db.commit_txn(write_txn).await.unwrap();
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(15)).await,
+                Ok(None)
+            );
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(16)).await,
+                Ok(Some(12))
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod delete {
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn delete_committed_key() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.write(key, 12, txn).await.unwrap();
+            db.write("bar", 15, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            db.set_time(Timestamp::new(20));
+            let delete_txn = db.begin_txn().await.unwrap();
+            db.delete(key, delete_txn).await.unwrap();
+            db.commit_txn(delete_txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await.unwrap(), None);
+            assert_eq!(
+                db.scan::<i32>("a", "z", 10, read_txn).await.unwrap(),
+                Vec::from([("bar".to_owned(), 15)])
+            );
+
+            // older snapshots still see the deleted value
+            assert_eq!(
+                db.read_without_txn::<i32>(key, Timestamp::new(15))
+                    .await
+                    .unwrap(),
+                Some(12)
+            );
+        }
+
+        #[tokio::test]
+        async fn delete_own_write() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            db.run_txn(|txn_context| async move {
+                let key = "foo";
+                txn_context.write(key, 12).await.unwrap();
+                txn_context.delete(key).await.unwrap();
+                let read = txn_context.read::<i32>(key).await.unwrap();
+                assert_eq!(read, None);
+                Ok::<(), DbError>(())
+            })
+            .await
+            .unwrap();
+        }
+    }
+
+    #[cfg(test)]
+    mod delete_range {
+        use std::sync::Arc;
+
+        use crate::{
+            db::db::{Timestamp, DB},
+            execute::request::ResponseUnion,
+            helpers::test_helpers::create_temp_dir,
+            storage::str_to_key,
+        };
+
+        #[tokio::test]
+        async fn delete_keys_in_range() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, txn).await.unwrap();
+            db.write("banana", 2, txn).await.unwrap();
+            db.write("cherry", 3, txn).await.unwrap();
+            db.write("durian", 4, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            db.set_time(Timestamp::new(20));
+            let delete_txn = db.begin_txn().await.unwrap();
+            db.delete_range("banana", "durian", delete_txn)
+                .await
+                .unwrap();
+            db.commit_txn(delete_txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(
+                db.scan::<i32>("a", "z", 10, read_txn).await.unwrap(),
+                Vec::from([("apple".to_owned(), 1), ("durian".to_owned(), 4)])
+            );
+            assert_eq!(
+                db.read_without_txn::<i32>("cherry", Timestamp::new(15))
+                    .await
+                    .unwrap(),
+                Some(3)
+            );
+        }
+
+        #[tokio::test]
+        async fn abort_delete_range() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, txn).await.unwrap();
+            db.write("banana", 2, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let delete_txn = db.begin_txn().await.unwrap();
+            db.delete_range("a", "z", delete_txn).await.unwrap();
+            db.abort_txn(delete_txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(
+                db.scan::<i32>("a", "z", 10, read_txn).await.unwrap(),
+                Vec::from([("apple".to_owned(), 1), ("banana".to_owned(), 2)])
+            );
+        }
+
+        #[tokio::test]
+        async fn delete_range_waits_for_uncommitted_write() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let write_txn = db.begin_txn().await.unwrap();
+            let delete_txn = db.begin_txn().await.unwrap();
+            db.write("banana", 2, write_txn).await.unwrap();
+
+            let db_2 = db.clone();
+            let delete_task = tokio::spawn(async move {
+                db_2.delete_range("a", "z", delete_txn).await.unwrap();
+                db_2.commit_txn(delete_txn).await.unwrap();
+            });
+            let db_3 = db.clone();
+            let commit_task =
+                tokio::spawn(async move { db_3.commit_txn(write_txn).await.unwrap() });
+            tokio::try_join!(delete_task, commit_task).unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("banana", read_txn).await.unwrap(), None);
+        }
+
+        #[tokio::test]
+        async fn delete_range_over_key_committed_above_txn_timestamp() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let delete_txn = db.begin_txn().await.unwrap();
+
+            db.set_time(Timestamp::new(20));
+            let write_txn = db.begin_txn().await.unwrap();
+            db.write("banana", 2, write_txn).await.unwrap();
+            db.commit_txn(write_txn).await.unwrap();
+
+            // the key is deleted even though it was committed above the deleter's timestamp
+            let response = db.delete_range("a", "z", delete_txn).await.unwrap();
+            match response {
+                ResponseUnion::DeleteRange(response) => {
+                    assert_eq!(response.keys, Vec::from([str_to_key("banana")]))
+                }
+                _ => panic!("unexpected response"),
+            }
+            let commit = db.commit_txn(delete_txn).await.unwrap();
+            assert!(commit.commit_timestamp.wall_time >= 20);
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("banana", read_txn).await.unwrap(), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod cput {
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+            storage::serialized_to_value,
+        };
+
+        #[tokio::test]
+        async fn cput_absent_key() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.cput(key, 12, None, txn).await.unwrap();
+            assert_eq!(db.read::<i32>(key, txn).await.unwrap(), Some(12));
+
+            // the txn's own intent is compared
+            let res = db.cput(key, 15, None, txn).await;
+            match res {
+                Err(DbError::ConditionFailed { actual }) => {
+                    assert_eq!(actual, Some(serialized_to_value(12)));
+                }
+                _ => panic!("expected the condition to fail"),
+            }
+            db.cput(key, 15, Some(12), txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await.unwrap(), Some(15));
+        }
+
+        #[tokio::test]
+        async fn cput_committed_value() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.write(key, 12, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let txn = db.begin_txn().await.unwrap();
+            let res = db.cput(key, 20, Some(13), txn).await;
+            match res {
+                Err(DbError::ConditionFailed { actual }) => {
+                    assert_eq!(actual, Some(serialized_to_value(12)));
+                }
+                _ => panic!("expected the condition to fail"),
+            }
+            db.cput(key, 20, Some(12), txn).await.unwrap();
+            assert_eq!(db.read::<i32>(key, txn).await.unwrap(), Some(20));
+        }
+
+        #[tokio::test]
+        async fn cput_compares_value_committed_after_txn_started() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.set_time(Timestamp::new(20));
+            db.upsert(key, 5).await.unwrap();
+
+            let res = db.cput(key, 12, None, txn).await;
+            assert_eq!(
+                res.err(),
+                Some(DbError::ConditionFailed {
+                    actual: Some(serialized_to_value(5))
+                })
+            );
+            db.cput(key, 12, Some(5), txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await.unwrap(), Some(12));
+        }
+    }
+
+    #[cfg(test)]
+    mod increment {
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn increment_missing_and_existing_key() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "counter";
+            let txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.increment(key, 5, txn).await.unwrap(), 5);
+            assert_eq!(db.increment(key, -2, txn).await.unwrap(), 3);
+            db.commit_txn(txn).await.unwrap();
+
+            let txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.increment(key, 10, txn).await.unwrap(), 13);
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i64>(key, read_txn).await.unwrap(), Some(13));
+        }
+
+        #[tokio::test]
+        async fn increment_non_integer_value() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.write(key, "bar", txn).await.unwrap();
+            let res = db.increment(key, 1, txn).await;
+            assert!(matches!(res, Err(DbError::InvalidIncrement)));
+        }
+
+        #[tokio::test]
+        async fn increment_value_committed_after_txn_started() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "counter";
+            let txn = db.begin_txn().await.unwrap();
+            db.set_time(Timestamp::new(20));
+            db.upsert(key, 5).await.unwrap();
+
+            assert_eq!(db.increment(key, 1, txn).await, Ok(6));
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i64>(key, read_txn).await, Ok(Some(6)));
+        }
+    }
+
+    #[cfg(test)]
+    mod batch {
+        use crate::{
+            db::db::{Timestamp, DB},
+            execute::request::{
+                GetRequest, IncrementRequest, PutRequest, RequestUnion, ResponseUnion,
+            },
+            helpers::test_helpers::create_temp_dir,
+            storage::{serialized_to_value, str_to_key},
+        };
+
+        #[tokio::test]
+        async fn batch_writes_and_reads() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            let mut requests = Vec::new();
+            for i in 0..10 {
+                requests.push(RequestUnion::Put(PutRequest {
+                    key: str_to_key(&format!("key{}", i)),
+                    value: serialized_to_value(i),
+                }));
+            }
+            requests.push(RequestUnion::Increment(IncrementRequest {
+                key: str_to_key("key3"),
+                increment: 5,
+            }));
+            requests.push(RequestUnion::Get(GetRequest {
+                key: str_to_key("key3"),
+            }));
+            let responses = db.batch(requests, txn).await.unwrap();
+            assert_eq!(responses.len(), 12);
+            match &responses[10] {
+                ResponseUnion::Increment(response) => assert_eq!(response.new_value, 8),
+                _ => panic!("expected an increment response"),
+            }
+            match &responses[11] {
+                ResponseUnion::Get(response) => {
+                    assert_eq!(
+                        response.value.as_ref().map(|(_, value)| value.clone()),
+                        Some(serialized_to_value(8))
+                    );
+                }
+                _ => panic!("expected a get response"),
+            }
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            let results = db.scan::<i32>("key0", "key9", 20, read_txn).await.unwrap();
+            assert_eq!(results.len(), 9);
+            assert_eq!(results[3], ("key3".to_owned(), 8));
+        }
+
+        #[tokio::test]
+        async fn read_only_batch() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("foo", 12, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            let responses = db
+                .batch(
+                    Vec::from([
+                        RequestUnion::Get(GetRequest {
+                            key: str_to_key("foo"),
+                        }),
+                        RequestUnion::Get(GetRequest {
+                            key: str_to_key("bar"),
+                        }),
+                    ]),
+                    read_txn,
+                )
+                .await
+                .unwrap();
+            match (&responses[0], &responses[1]) {
+                (ResponseUnion::Get(foo), ResponseUnion::Get(bar)) => {
+                    assert!(foo.value.is_some());
+                    assert!(bar.value.is_none());
+                }
+                _ => panic!("expected get responses"),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod db_error {
+        use uuid::Uuid;
+
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn unknown_txn() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn_id = Uuid::new_v4();
+            assert_eq!(
+                db.write("foo", 12, txn_id).await.err(),
+                Some(DbError::UnknownTxn(txn_id))
+            );
+            assert_eq!(
+                db.read::<i32>("foo", txn_id).await,
+                Err(DbError::UnknownTxn(txn_id))
+            );
+            assert_eq!(
+                db.commit_txn(txn_id).await.err(),
+                Some(DbError::UnknownTxn(txn_id))
+            );
+        }
+
+        #[tokio::test]
+        async fn deserialization_error() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("foo", "bar", txn).await.unwrap();
+            let res = db.read::<i32>("foo", txn).await;
+            assert!(matches!(res, Err(DbError::SerializationError(_))));
+        }
+
+        #[tokio::test]
+        async fn commit_aborted_txn() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("foo", 12, txn).await.unwrap();
+            db.abort_txn(txn).await.unwrap();
+            assert_eq!(db.commit_txn(txn).await.err(), Some(DbError::TxnAborted));
+        }
+    }
+
+    #[cfg(test)]
+    mod savepoint {
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn rollback_to_savepoint() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, txn).await.unwrap();
+            let savepoint = db.savepoint(txn).unwrap();
+            db.write("apple", 2, txn).await.unwrap();
+            db.write("banana", 3, txn).await.unwrap();
+            db.rollback_to(txn, savepoint).unwrap();
+
+            assert_eq!(db.read::<i32>("apple", txn).await, Ok(Some(1)));
+            assert_eq!(db.read::<i32>("banana", txn).await, Ok(None));
+
+            db.write("cherry", 4, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            let res = db.scan::<i32>("a", "z", 10, read_txn).await.unwrap();
+            assert_eq!(
+                res,
+                Vec::from([("apple".to_owned(), 1), ("cherry".to_owned(), 4)])
+            );
+        }
+
+        #[tokio::test]
+        async fn rollback_restores_committed_value() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("foo", 1, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            db.run_txn(|txn_context| async move {
+                let savepoint = txn_context.savepoint()?;
+                txn_context.write("foo", 2).await?;
+                txn_context.delete("foo").await?;
+                txn_context.rollback_to(savepoint)?;
+                assert_eq!(txn_context.read::<i32>("foo").await?, Some(1));
+                Ok::<(), DbError>(())
+            })
+            .await
+            .unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", read_txn).await, Ok(Some(1)));
+        }
+
+        #[tokio::test]
+        async fn savepoint_of_another_txn() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn_1 = db.begin_txn().await.unwrap();
+            let txn_2 = db.begin_txn().await.unwrap();
+            let savepoint = db.savepoint(txn_1).unwrap();
+            assert_eq!(
+                db.rollback_to(txn_2, savepoint),
+                Err(DbError::InvalidSavepoint)
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod read_only_txn {
+        use std::sync::Arc;
+
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn read_history_at_timestamp() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, txn).await.unwrap();
+            db.write("banana", 2, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            db.set_time(Timestamp::new(20));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("apple", 10, txn).await.unwrap();
+            db.delete("banana", txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            // an uncommitted write above the timestamp doesn't block the read
+            db.set_time(Timestamp::new(30));
+            let pending_txn = db.begin_txn().await.unwrap();
+            db.write("apple", 100, pending_txn).await.unwrap();
+
+            let read_txn = db.begin_read_only_txn_at(Timestamp::new(15)).await.unwrap();
+            assert_eq!(db.read::<i32>("apple", read_txn).await, Ok(Some(1)));
+            assert_eq!(
+                db.scan::<i32>("a", "z", 10, read_txn).await,
+                Ok(Vec::from([
+                    ("apple".to_owned(), 1),
+                    ("banana".to_owned(), 2)
+                ]))
+            );
+            assert_eq!(
+                db.write("apple", 5, read_txn).await.err(),
+                Some(DbError::ReadOnlyTxn)
+            );
+            let commit_res = db.commit_txn(read_txn).await.unwrap();
+            assert_eq!(commit_res.commit_timestamp.wall_time, 15);
+
+            let read_txn = db.begin_read_only_txn_at(Timestamp::new(25)).await.unwrap();
+            assert_eq!(db.read::<i32>("apple", read_txn).await, Ok(Some(10)));
+            assert_eq!(db.read::<i32>("banana", read_txn).await, Ok(None));
+        }
+
+        #[tokio::test]
+        async fn timestamp_cannot_be_in_the_future() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            assert_eq!(
+                db.begin_read_only_txn_at(Timestamp::new(20)).await,
+                Err(DbError::InvalidReadTimestamp)
+            );
+        }
+
+        #[tokio::test]
+        async fn finalized_txn_is_forgotten() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let committed_txn = db.begin_read_only_txn_at(Timestamp::new(5)).await.unwrap();
+            db.commit_txn(committed_txn).await.unwrap();
+            assert_eq!(
+                db.read::<i32>("apple", committed_txn).await,
+                Err(DbError::UnknownTxn(committed_txn))
+            );
+
+            let aborted_txn = db.begin_read_only_txn_at(Timestamp::new(5)).await.unwrap();
+            db.abort_txn(aborted_txn).await.unwrap();
+            assert_eq!(
+                db.read::<i32>("apple", aborted_txn).await,
+                Err(DbError::UnknownTxn(aborted_txn))
+            );
+        }
+
+        #[tokio::test]
+        async fn waits_for_intent_beneath_timestamp() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let write_txn = db.begin_txn().await.unwrap();
+            db.write("apple", 1, write_txn).await.unwrap();
+            db.set_time(Timestamp::new(20));
+
+            let db_1 = db.clone();
+            let read_task = tokio::spawn(async move {
+                let read_txn = db_1
+                    .begin_read_only_txn_at(Timestamp::new(15))
+                    .await
+                    .unwrap();
+                db_1.read::<i32>("apple", read_txn).await
+            });
+            let db_2 = db.clone();
+            let commit_task =
+                tokio::spawn(async move { db_2.commit_txn(write_txn).await.unwrap() });
+            let (read_res, _) = tokio::try_join!(read_task, commit_task).unwrap();
+            assert_eq!(read_res, Ok(Some(1)));
+        }
+    }
+
+    #[cfg(test)]
+    mod read_for_update {
+        use std::sync::Arc;
+
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn locking_read_waits_for_lock_holder() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let key = "foo";
+            let txn = db.begin_txn().await.unwrap();
+            db.write(key, 1, txn).await.unwrap();
+            db.commit_txn(txn).await.unwrap();
+
+            let txn_1 = db.begin_txn().await.unwrap();
+            let txn_2 = db.begin_txn().await.unwrap();
+            assert_eq!(db.read_for_update::<i32>(key, txn_1).await, Ok(Some(1)));
+
+            let db_2 = db.clone();
+            let task = tokio::spawn(async move {
+                // reads the value committed by txn_1 once its lock is released
+                let value = db_2.read_for_update::<i32>(key, txn_2).await.unwrap();
+                db_2.write(key, value.unwrap() + 1, txn_2).await.unwrap();
+                db_2.commit_txn(txn_2).await.unwrap();
+            });
+
+            // non-locking reads don't wait for the lock
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(1)));
+            db.write(key, 2, txn_1).await.unwrap();
+            db.commit_txn(txn_1).await.unwrap();
+            task.await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(3)));
+        }
+
+        #[tokio::test]
+        async fn shared_locks_block_writer() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let key = "foo";
+            let txn_1 = db.begin_txn().await.unwrap();
+            let txn_2 = db.begin_txn().await.unwrap();
+            assert_eq!(db.read_for_share::<i32>(key, txn_1).await, Ok(None));
+            assert_eq!(db.read_for_share::<i32>(key, txn_2).await, Ok(None));
+
+            let db_3 = db.clone();
+            let write_task = tokio::spawn(async move {
+                let txn_3 = db_3.begin_txn().await.unwrap();
+                db_3.write(key, 3, txn_3).await.unwrap();
+                db_3.commit_txn(txn_3).await.unwrap();
+            });
+            db.commit_txn(txn_1).await.unwrap();
+            db.abort_txn(txn_2).await.unwrap();
+            write_task.await.unwrap();
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(3)));
+        }
+
+        #[tokio::test]
+        async fn concurrent_read_modify_writes() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let key = "foo";
+            let tasks = (0..2)
+                .map(|_| {
+                    let db = db.clone();
+                    tokio::spawn(async move {
+                        db.run_txn(|txn_context| async move {
+                            let value = txn_context.read_for_update::<i32>(key).await?;
+                            txn_context.write(key, value.unwrap_or(0) + 1).await?;
+                            Ok::<(), DbError>(())
+                        })
+                        .await
+                    })
+                })
+                .collect::<Vec<_>>();
+            for task in tasks {
+                assert_eq!(task.await.unwrap(), Ok(()));
+            }
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(2)));
+        }
+    }
+
+    #[cfg(test)]
+    mod wait_policy {
+        use std::time::Duration;
+
+        use crate::db::db::{DbError, Timestamp, WaitPolicy, DB};
+        use crate::helpers::test_helpers::create_temp_dir;
+
+        #[tokio::test]
+        async fn nowait_returns_lock_conflict() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn_1 = db.begin_txn().await.unwrap();
+            let txn_2 = db.begin_txn().await.unwrap();
+            assert_eq!(db.read_for_update::<i32>(key, txn_1).await, Ok(None));
+            assert_eq!(
+                db.read_for_update_with_wait_policy::<i32>(key, WaitPolicy::Error, txn_2)
+                    .await,
+                Err(DbError::LockConflict {
+                    key: key.as_bytes().to_vec(),
+                    holder_txn: txn_1
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn lock_timeout_returns_lock_conflict() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let txn_1 = db.begin_txn().await.unwrap();
+            let txn_2 = db.begin_txn().await.unwrap();
+            db.write(key, 1, txn_1).await.unwrap();
+            let wait_policy = WaitPolicy::Block {
+                timeout: Some(Duration::from_millis(50)),
+            };
+            assert_eq!(
+                db.read_for_update_with_wait_policy::<i32>(key, wait_policy, txn_2)
+                    .await,
+                Err(DbError::LockConflict {
+                    key: key.as_bytes().to_vec(),
+                    holder_txn: txn_1
+                })
+            );
+
+            // the request stops waiting without aborting the lock holder
+            db.commit_txn(txn_1).await.unwrap();
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(1)));
+        }
+
+        #[tokio::test]
+        async fn skip_locked_scan_omits_locked_keys() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            for (i, key) in ["job_a", "job_b", "job_c", "job_d"].iter().enumerate() {
+                db.write(key, i as i32, txn).await.unwrap();
+            }
+            db.commit_txn(txn).await.unwrap();
+
+            let worker_1 = db.begin_txn().await.unwrap();
+            assert_eq!(
+                db.read_for_update::<i32>("job_a", worker_1).await,
+                Ok(Some(0))
+            );
+            db.write("job_c", 20, worker_1).await.unwrap();
+
+            let worker_2 = db.begin_txn().await.unwrap();
+            let jobs = db
+                .scan_with_wait_policy::<i32>("job_a", "job_z", 1, WaitPolicy::SkipLocked, worker_2)
+                .await
+                .unwrap();
+            assert_eq!(jobs, Vec::from([("job_b".to_owned(), 1)]));
+            assert_eq!(
+                db.read_for_update_with_wait_policy::<i32>(
+                    "job_c",
+                    WaitPolicy::SkipLocked,
+                    worker_2
+                )
+                .await,
+                Ok(None)
+            );
+            assert_eq!(
+                db.read_for_update_with_wait_policy::<i32>(
+                    "job_d",
+                    WaitPolicy::SkipLocked,
+                    worker_2
+                )
+                .await,
+                Ok(Some(3))
+            );
+            db.commit_txn(worker_1).await.unwrap();
+            db.commit_txn(worker_2).await.unwrap();
+        }
+    }
+
+    #[cfg(test)]
+    mod txn_priority {
+        use std::{sync::Arc, time::Duration};
+
+        use crate::db::db::{DbError, Timestamp, TxnContext, TxnOptions, TxnPriority, DB};
+        use crate::helpers::test_helpers::create_temp_dir;
+
+        #[tokio::test]
+        async fn high_priority_pusher_aborts_low_priority_holder() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let low_txn = db
+                .begin_txn_with_options(TxnOptions {
+                    priority: TxnPriority::LOW,
+                })
+                .await
+                .unwrap();
+            db.write(key, 1, low_txn).await.unwrap();
+
+            let high_txn = db
+                .begin_txn_with_options(TxnOptions {
+                    priority: TxnPriority::HIGH,
+                })
+                .await
+                .unwrap();
+            db.write(key, 2, high_txn).await.unwrap();
+            db.commit_txn(high_txn).await.unwrap();
+            assert_eq!(
+                db.commit_txn(low_txn).await.map(|_| ()),
+                Err(DbError::TxnAborted)
+            );
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>(key, read_txn).await, Ok(Some(2)));
+        }
+
+        #[tokio::test]
+        async fn high_priority_pusher_doesnt_wait_before_aborting_holder() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = "foo";
+            let low_txn = db.begin_txn().await.unwrap();
+            db.write(key, 1, low_txn).await.unwrap();
+
+            let high_txn = db
+                .begin_txn_with_options(TxnOptions {
+                    priority: TxnPriority::HIGH,
+                })
+                .await
+                .unwrap();
+            // the holder is aborted before the pusher's wait for the lock times out
+            tokio::time::timeout(Duration::from_millis(500), db.write(key, 2, high_txn))
+                .await
+                .unwrap()
+                .unwrap();
+            db.commit_txn(high_txn).await.unwrap();
+            assert_eq!(
+                db.commit_txn(low_txn).await.map(|_| ()),
+                Err(DbError::TxnAborted)
+            );
+        }
+
+        #[test]
+        fn retries_stay_in_their_class() {
+            let mut priority = TxnPriority::NORMAL;
+            for _ in 0..2_000 {
+                priority = priority.raise();
+            }
+            assert!(priority > TxnPriority::NORMAL);
+            assert!(!priority.is_higher_class_than(TxnPriority::NORMAL));
+            assert!(TxnPriority::HIGH.is_higher_class_than(priority));
+        }
+
+        #[tokio::test]
+        async fn retried_txn_waits_for_holder_of_same_class() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let key = "foo";
+            let holder_txn = db.begin_txn().await.unwrap();
+            db.write(key, 1, holder_txn).await.unwrap();
+
+            let retried_txn = db
+                .begin_txn_with_options(TxnOptions {
+                    priority: TxnPriority::NORMAL.raise(),
+                })
+                .await
+                .unwrap();
+            let db_1 = db.clone();
+            let write_task = tokio::spawn(async move {
+                db_1.write(key, 2, retried_txn).await.unwrap();
+                db_1.commit_txn(retried_txn).await.map(|_| ())
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(db.commit_txn(holder_txn).await.map(|_| ()), Ok(()));
+            assert_eq!(write_task.await.unwrap(), Ok(()));
+        }
+
+        #[tokio::test]
+        async fn contending_retried_txns_both_commit() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let increment_both = |db: Arc<DB>, first: &'static str, second: &'static str| async move {
+                db.run_txn(|txn_context: Arc<TxnContext>| async move {
+                    txn_context.increment(first, 1).await?;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    txn_context.increment(second, 1).await?;
+                    Ok::<(), DbError>(())
+                })
+                .await
+            };
+            let task_1 = tokio::spawn(increment_both(db.clone(), "apple", "banana"));
+            let task_2 = tokio::spawn(increment_both(db.clone(), "banana", "apple"));
+            let (res_1, res_2) = tokio::time::timeout(Duration::from_secs(10), async {
+                tokio::try_join!(task_1, task_2).unwrap()
+            })
+            .await
+            .unwrap();
+            assert_eq!((res_1, res_2), (Ok(()), Ok(())));
+
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i64>("apple", read_txn).await, Ok(Some(2)));
+            assert_eq!(db.read::<i64>("banana", read_txn).await, Ok(Some(2)));
+        }
+    }
+
+    #[cfg(test)]
+    mod txn_liveness {
+        use std::{sync::Arc, time::Duration};
+
+        use tokio::time::Instant;
+
+        use crate::{
+            db::db::{DbError, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+            storage::txn::{TXN_CLIENT_IDLE_TIMEOUT, TXN_LIVENESS_THRESHOLD},
+        };
+
+        #[tokio::test]
+        async fn pusher_aborts_abandoned_txn() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let abandoned_txn = db.begin_txn().await.unwrap();
+            db.write("foo", 1, abandoned_txn).await.unwrap();
+
+            // the client of the abandoned txn doesn't send any more requests
+            let start = Instant::now();
+            let txn = db.begin_txn().await.unwrap();
+            tokio::time::timeout(
+                TXN_CLIENT_IDLE_TIMEOUT + TXN_LIVENESS_THRESHOLD + Duration::from_secs(5),
+                db.write("foo", 2, txn),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            assert!(start.elapsed() > TXN_LIVENESS_THRESHOLD);
+            db.commit_txn(txn).await.unwrap();
+
+            assert_eq!(
+                db.commit_txn(abandoned_txn).await.map(|_| ()),
+                Err(DbError::TxnAborted)
+            );
+            let read_txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", read_txn).await, Ok(Some(2)));
+        }
+    }
+
+    #[cfg(test)]
+    mod recovery {
+        use tokio::runtime::Runtime;
+
+        use crate::db::db::{Timestamp, DB};
+        use crate::helpers::test_helpers::create_temp_dir;
+
+        #[test]
+        fn reopened_db_aborts_txns_pending_before_restart() {
+            let path = create_temp_dir();
+            {
+                // dropping the runtime stops the db's background tasks which closes the db
+                let runtime = Runtime::new().unwrap();
+                runtime.block_on(async {
+                    let db = DB::new_cleaned(&path, Timestamp::new(10));
+                    let committed_txn = db.begin_txn().await.unwrap();
+                    db.write("foo", 1, committed_txn).await.unwrap();
+                    db.commit_txn(committed_txn).await.unwrap();
+
+                    let pending_txn = db.begin_txn().await.unwrap();
+                    db.write("foo", 2, pending_txn).await.unwrap();
+                    db.write("bar", 3, pending_txn).await.unwrap();
+                });
+            }
+
+            let runtime = Runtime::new().unwrap();
+            runtime.block_on(async {
+                let db = DB::new(&path, Timestamp::new(1));
+                let txn = db.begin_txn().await.unwrap();
+                assert_eq!(db.read::<i32>("foo", txn).await, Ok(Some(1)));
+                assert_eq!(db.read::<i32>("bar", txn).await, Ok(None));
+                db.write("bar", 4, txn).await.unwrap();
+                db.commit_txn(txn).await.unwrap();
+            });
+        }
+
+        #[tokio::test]
+        async fn dropped_db_with_open_txn_can_be_reopened() {
+            let path = create_temp_dir();
+            {
+                let db = DB::new_cleaned(&path, Timestamp::new(10));
+                let txn = db.begin_txn().await.unwrap();
+                db.write("foo", 1, txn).await.unwrap();
+            }
+
+            let db = DB::new(&path, Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", txn).await, Ok(None));
+        }
+    }
+
+    #[cfg(test)]
+    mod intent_resolution {
+        use crate::db::db::{Timestamp, DB};
+        use crate::helpers::test_helpers::create_temp_dir;
+
+        // the intents of a committed txn are resolved in the background. Requests that
+        // run into them before the resolution resolve them with the txn record
+        #[tokio::test]
+        async fn requests_right_after_commit_see_committed_writes() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let keys = ["apple", "banana", "cherry"];
+            let txn_1 = db.begin_txn().await.unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                db.write(key, i as i32, txn_1).await.unwrap();
+            }
+            // the rolled back write is ignored when the intent is resolved
+            let savepoint = db.savepoint(txn_1).unwrap();
+            db.write("cherry", 30, txn_1).await.unwrap();
+            db.rollback_to(txn_1, savepoint).unwrap();
+            db.commit_txn(txn_1).await.unwrap();
+
+            let txn_2 = db.begin_txn().await.unwrap();
+            db.write("banana", 10, txn_2).await.unwrap();
+            assert_eq!(
+                db.scan::<i32>("apple", "zebra", 10, txn_2).await,
+                Ok(Vec::from([
+                    ("apple".to_owned(), 0),
+                    ("banana".to_owned(), 10),
+                    ("cherry".to_owned(), 2)
+                ]))
+            );
+            db.commit_txn(txn_2).await.unwrap();
+        }
+    }
+
+    #[cfg(test)]
+    mod one_phase_commit {
+        use std::sync::Arc;
+
+        use crate::{
+            db::db::{BatchOperation, BatchOperationResult, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn upsert_and_delete() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            db.upsert("foo", 1).await.unwrap();
+            db.upsert("bar", 2).await.unwrap();
+
+            db.set_time(Timestamp::new(12));
+            let responses = db
+                .run_batch_txn(Vec::from([
+                    BatchOperation::Put {
+                        key: "foo",
+                        value: 3,
+                    },
+                    BatchOperation::Delete { key: "bar" },
+                ]))
+                .await
+                .unwrap();
+            assert_eq!(
+                responses,
+                Vec::from([BatchOperationResult::Put, BatchOperationResult::Delete])
+            );
+
+            let txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", txn).await, Ok(Some(3)));
+            assert_eq!(db.read::<i32>("bar", txn).await, Ok(None));
+        }
+
+        #[tokio::test]
+        async fn upsert_waits_for_uncommitted_write() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            let txn_1 = db.begin_txn().await.unwrap();
+            db.write("foo", 1, txn_1).await.unwrap();
+
+            db.set_time(Timestamp::new(12));
+            let db_2 = db.clone();
+            let upsert_task = tokio::spawn(async move { db_2.upsert("foo", 2).await });
+            db.commit_txn(txn_1).await.unwrap();
+            upsert_task.await.unwrap().unwrap();
+
+            let txn_2 = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", txn_2).await, Ok(Some(2)));
+        }
+
+        // a batch that reads isn't committed in one phase
+        #[tokio::test]
+        async fn batch_with_reads_commits_in_two_phases() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let responses = db
+                .run_batch_txn(Vec::from([
+                    BatchOperation::Put {
+                        key: "foo",
+                        value: 1,
+                    },
+                    BatchOperation::Get { key: "foo" },
+                ]))
+                .await
+                .unwrap();
+            assert_eq!(responses[1], BatchOperationResult::Get(Some(1)));
+
+            let txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<i32>("foo", txn).await, Ok(Some(1)));
+        }
+    }
+
+    #[cfg(test)]
+    mod gc {
+        use std::{
+            sync::{
+                atomic::{AtomicU64, Ordering},
+                Arc,
+            },
+            time::Duration,
+        };
+
+        use crate::{
+            db::db::{DbError, GcPolicy, Timestamp, DB},
+            helpers::test_helpers::create_temp_dir,
+            hlc::timestamp::Timestamp as HLCTimestamp,
+            StorageError,
+        };
+
+        #[tokio::test]
+        async fn reads_beneath_threshold_are_rejected() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            db.upsert("foo", 1).await.unwrap();
+            let old_txn = db.begin_txn().await.unwrap();
+            db.set_time(Timestamp::new(20));
+            db.upsert("foo", 2).await.unwrap();
+            db.set_time(Timestamp::new(30));
+
+            db.run_gc(Timestamp::new(25)).unwrap();
+            let below_threshold = Err(DbError::BelowGcThreshold {
+                threshold: HLCTimestamp::new(25, 0),
+            });
+            assert_eq!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(15)).await,
+                below_threshold
+            );
+            assert_eq!(db.read::<i32>("foo", old_txn).await, below_threshold);
+            let read_only_txn = db.begin_read_only_txn_at(Timestamp::new(15)).await.unwrap();
+            assert_eq!(db.read::<i32>("foo", read_only_txn).await, below_threshold);
+            // the txn can still be aborted
+            db.abort_txn(old_txn).await.unwrap();
+
+            assert_eq!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(25)).await,
+                Ok(Some(2))
+            );
+        }
+
+        #[tokio::test]
+        async fn advancing_threshold_rejects_reads_beneath_it() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            db.upsert("foo", 1).await.unwrap();
+            db.set_time(Timestamp::new(20));
+            db.upsert("foo", 2).await.unwrap();
+            db.set_time(Timestamp::new(30));
+
+            db.advance_gc_threshold(Timestamp::new(25)).unwrap();
+            assert_eq!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(15)).await,
+                Err(DbError::BelowGcThreshold {
+                    threshold: HLCTimestamp::new(25, 0)
+                })
+            );
+            assert_eq!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(25)).await,
+                Ok(Some(2))
+            );
+        }
+
+        #[tokio::test]
+        async fn threshold_cannot_be_in_the_future() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            assert_eq!(
+                db.run_gc(Timestamp::new(20)),
+                Err(DbError::InvalidGcThreshold)
+            );
+        }
+
+        #[tokio::test]
+        async fn gc_runs_concurrently_with_writes() {
+            let db = Arc::new(DB::new_cleaned(&create_temp_dir(), Timestamp::new(10)));
+            db.upsert("foo", 0).await.unwrap();
+            let time = Arc::new(AtomicU64::new(10));
+
+            let db_1 = db.clone();
+            let time_1 = time.clone();
+            let writer = tokio::spawn(async move {
+                for i in 1..=50 {
+                    time_1.store(10 + i, Ordering::SeqCst);
+                    db_1.set_time(Timestamp::new(10 + i));
+                    db_1.upsert("foo", i).await.unwrap();
+                }
+            });
+            while !writer.is_finished() {
+                // the threshold trails the writer's time so that its txns aren't beneath it
+                let threshold = time.load(Ordering::SeqCst) - 1;
+                db.run_gc(Timestamp::new(threshold)).unwrap();
+                tokio::task::yield_now().await;
+            }
+            writer.await.unwrap();
+
+            db.set_time(Timestamp::new(70));
+            db.run_gc(Timestamp::new(65)).unwrap();
+            assert_eq!(
+                db.read_without_txn::<u64>("foo", Timestamp::new(65)).await,
+                Ok(Some(50))
+            );
+        }
+
+        #[tokio::test]
+        async fn background_gc_keeps_history_within_ttl() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            db.upsert("foo", 1).await.unwrap();
+            db.set_time(Timestamp::new(100));
+            db.start_gc(GcPolicy {
+                ttl: 50,
+                interval: Duration::from_millis(10),
+            });
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            assert!(matches!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(20)).await,
+                Err(DbError::BelowGcThreshold { .. })
+            ));
+            assert_eq!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(60)).await,
+                Ok(Some(1))
+            );
+        }
+
+        #[tokio::test]
+        async fn background_gc_runs_after_a_failed_run() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            db.upsert("foo", 1).await.unwrap();
+            db.set_time(Timestamp::new(100));
+            let runs = Arc::new(AtomicU64::new(0));
+            let runs_1 = runs.clone();
+            db.start_gc_with(
+                GcPolicy {
+                    ttl: 50,
+                    interval: Duration::from_millis(10),
+                },
+                move |db, threshold| {
+                    if runs_1.fetch_add(1, Ordering::SeqCst) == 0 {
+                        return Err(DbError::StorageError(StorageError::new(
+                            "gc_error".to_owned(),
+                            "failed to garbage collect".to_owned(),
+                        )));
+                    }
+                    db.run_gc(threshold)
+                },
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            assert!(runs.load(Ordering::SeqCst) > 1);
+            assert!(matches!(
+                db.read_without_txn::<i32>("foo", Timestamp::new(20)).await,
+                Err(DbError::BelowGcThreshold { .. })
+            ));
+        }
+    }
+
+    mod codecs {
+        use tokio::runtime::Runtime;
+
+        use crate::{
+            db::{
+                codec::{BincodeCodec, RawBytesCodec},
+                db::{DbError, Timestamp, DB},
+            },
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[test]
+        fn bincode_db_reads_its_writes_after_reopening() {
+            let path = create_temp_dir();
+            {
+                // dropping the runtime stops the db's background tasks which closes the db
+                let runtime = Runtime::new().unwrap();
+                runtime.block_on(async {
+                    let db = DB::new_cleaned_with_codec(&path, Timestamp::new(10), BincodeCodec);
+                    db.upsert("apple", ("red".to_owned(), 3u32)).await.unwrap();
+                    db.upsert("banana", ("yellow".to_owned(), 12u32))
+                        .await
+                        .unwrap();
+                });
+            }
+
+            let runtime = Runtime::new().unwrap();
+            runtime.block_on(async {
+                let db = DB::new_with_codec(&path, Timestamp::new(20), BincodeCodec);
+                let txn = db.begin_txn().await.unwrap();
+                assert_eq!(
+                    db.scan::<(String, u32)>("apple", "cherry", 10, txn).await,
+                    Ok(Vec::from([
+                        ("apple".to_owned(), ("red".to_owned(), 3)),
+                        ("banana".to_owned(), ("yellow".to_owned(), 12)),
+                    ]))
+                );
+            });
+        }
+
+        #[tokio::test]
+        async fn raw_bytes_db_stores_values_as_is() {
+            let db =
+                DB::new_cleaned_with_codec(&create_temp_dir(), Timestamp::new(10), RawBytesCodec);
+            let value = Vec::from([0u8, 159, 255]);
+            let txn = db.begin_txn().await.unwrap();
+            db.write("blob", value.clone(), txn).await.unwrap();
+            assert_eq!(db.increment("counter", 5, txn).await, Ok(5));
+            assert_eq!(db.increment("counter", 7, txn).await, Ok(12));
+            db.commit_txn(txn).await.unwrap();
+
+            let txn = db.begin_txn().await.unwrap();
+            assert_eq!(db.read::<Vec<u8>>("blob", txn).await, Ok(Some(value)));
+            assert_eq!(
+                db.read::<Vec<u8>>("counter", txn).await,
+                Ok(Some(12i64.to_be_bytes().to_vec()))
+            );
+            assert_eq!(db.read::<i64>("counter", txn).await, Ok(Some(12)));
+            assert!(matches!(
+                db.read::<i64>("blob", txn).await,
+                Err(DbError::SerializationError(_))
+            ));
+        }
+    }
+
+    mod raw_api {
+        use std::sync::Arc;
+
+        use crate::{
+            db::db::{DbError, Timestamp, TxnContext, DB},
+            helpers::test_helpers::create_temp_dir,
+        };
+
+        #[tokio::test]
+        async fn binary_keys_and_values() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let keys = [
+                Vec::from([0u8]),
+                Vec::from([0u8, 0]),
+                Vec::from([0u8, 255]),
+                Vec::from([1u8, 0, 1]),
+                Vec::from([255u8]),
+            ];
+            let values = [
+                Vec::new(),
+                Vec::from([255u8]),
+                Vec::from([255u8, 0]),
+                Vec::from([0u8, 1]),
+                b"not json".to_vec(),
+            ];
+            let txn = db.begin_txn().await.unwrap();
+            for (key, value) in keys.iter().zip(values.iter()) {
+                db.put_raw(key.clone(), value.clone(), txn).await.unwrap();
+            }
+            db.commit_txn(txn).await.unwrap();
+
+            let txn = db.begin_txn().await.unwrap();
+            for (key, value) in keys.iter().zip(values.iter()) {
+                assert_eq!(db.get_raw(key.clone(), txn).await, Ok(Some(value.clone())));
+            }
+            assert_eq!(db.get_raw(Vec::from([0u8, 1]), txn).await, Ok(None));
+            assert_eq!(
+                db.scan_raw(Vec::from([0u8]), Vec::from([255u8]), 10, txn)
+                    .await,
+                Ok(keys[..4]
+                    .iter()
+                    .cloned()
+                    .zip(values[..4].iter().cloned())
+                    .collect())
+            );
+        }
+
+        #[tokio::test]
+        async fn typed_api_reads_raw_writes() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.write("foo", 12, txn).await.unwrap();
+            assert_eq!(
+                db.get_raw(b"foo".to_vec(), txn).await,
+                Ok(Some(b"12".to_vec()))
+            );
+            db.put_raw(b"bar".to_vec(), b"13".to_vec(), txn)
+                .await
+                .unwrap();
+            assert_eq!(db.read::<i32>("bar", txn).await, Ok(Some(13)));
+            db.put_raw(b"baz".to_vec(), Vec::from([255u8]), txn)
+                .await
+                .unwrap();
+            assert!(matches!(
+                db.read::<i32>("baz", txn).await,
+                Err(DbError::SerializationError(_))
+            ));
+        }
+
+        #[tokio::test]
+        async fn typed_scan_rejects_non_utf8_keys() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let txn = db.begin_txn().await.unwrap();
+            db.put_raw(Vec::from([b'a', 255]), b"12".to_vec(), txn)
+                .await
+                .unwrap();
+            assert!(matches!(
+                db.scan::<i32>("a", "b", 10, txn).await,
+                Err(DbError::SerializationError(_))
+            ));
+            assert_eq!(
+                db.scan_raw(b"a".to_vec(), b"b".to_vec(), 10, txn).await,
+                Ok(Vec::from([(Vec::from([b'a', 255]), b"12".to_vec())]))
+            );
+        }
+
+        #[tokio::test]
+        async fn raw_api_in_txn() {
+            let db = DB::new_cleaned(&create_temp_dir(), Timestamp::new(10));
+            let key = Vec::from([7u8, 0, 7]);
+            db.run_txn(|txn_context: Arc<TxnContext>| {
+                let key = key.clone();
+                async move {
+                    txn_context.put_raw(key.clone(), Vec::new()).await?;
+                    assert_eq!(txn_context.get_raw(key).await, Ok(Some(Vec::new())));
+                    Ok::<(), DbError>(())
+                }
+            })
+            .await
+            .unwrap();
+
+            let results = db
+                .run_txn(|txn_context: Arc<TxnContext>| async move {
+                    txn_context
+                        .scan_raw(Vec::from([7u8]), Vec::from([8u8]), 10)
+                        .await
+                })
+                .await;
+            assert_eq!(results, Ok(Vec::from([(key, Vec::new())])));
+        }
     }
 }
@@ -1,3 +1,4 @@
+pub mod codec;
 pub mod db;
 mod db_test;
 pub mod request_queue;
@@ -1,7 +1,6 @@
 use std::{
-    sync::{
-        Arc,
-    },
+    ops::RangeInclusive,
+    sync::{Arc, Weak},
 };
 
 use tokio::{
@@ -11,11 +10,16 @@ use tokio::{
         Mutex,
     },
 };
+use tracing::error;
 use uuid::Uuid;
 
-use crate::execute::{
-    executor::Executor,
-    request::{AbortTxnRequest, Request, RequestMetadata, RequestUnion},
+use crate::{
+    execute::{
+        executor::Executor,
+        request::{AbortTxnRequest, Request, RequestMetadata, RequestUnion, SpanSet},
+    },
+    hlc::timestamp::Timestamp,
+    storage::Key,
 };
 
 use super::db::{TxnLink, TxnMap};
@@ -29,11 +33,13 @@ pub struct TaskQueueRequest {
 #[derive(Debug)]
 pub enum TaskQueueRequestUnion {
     AbortTxn(AbortTxnQueueRequest),
+    ResolveIntents(ResolveIntentsQueueRequest),
 }
 
 #[derive(Debug)]
 pub enum QueueResponseUnion {
     AbortTxn(AbortTxnThreadPoolResponse),
+    ResolveIntents(ResolveIntentsThreadPoolResponse),
 }
 
 #[derive(Debug)]
@@ -44,6 +50,20 @@ pub struct AbortTxnQueueRequest {
 #[derive(Debug)]
 pub struct AbortTxnThreadPoolResponse {}
 
+/**
+ * Resolves the intents that a committed txn laid within its lock spans.
+ */
+#[derive(Debug)]
+pub struct ResolveIntentsQueueRequest {
+    pub txn_id: Uuid,
+    pub commit_timestamp: Timestamp,
+    pub ignored_sequences: Vec<RangeInclusive<u64>>,
+    pub lock_spans: SpanSet<Key>,
+}
+
+#[derive(Debug)]
+pub struct ResolveIntentsThreadPoolResponse {}
+
 // The implementation of ThreadPool is based on the ThreadPool
 // implementation from The Rust Programing Language (Chpter 20: Building a Multithreaded Web Server)
 pub struct TaskQueue {
@@ -60,42 +80,91 @@ impl TaskQueue {
         sender: Arc<Sender<TaskQueueRequest>>,
     ) -> Self {
         let receiver = Arc::new(Mutex::new(receiver));
-        let cloned_executor = executor.clone();
+        // the executor holds a sender, so the task only holds a weak reference to it.
+        // Otherwise, the queue would keep the database open after it's dropped
+        let cloned_executor = Arc::downgrade(&executor);
         let cloned_txns = txns.clone();
         spawn(async move {
             loop {
                 let mut locked = receiver.lock().await;
                 let request = locked.recv().await;
                 let txns_cloned = cloned_txns.clone();
-                let executor_cloned = cloned_executor.clone();
+                let Some(executor_cloned) = Weak::upgrade(&cloned_executor) else {
+                    break;
+                };
+                let Some(request) = request else {
+                    break;
+                };
                 spawn(async move {
-                    match request {
-                        Some(request) => match request.request {
-                            TaskQueueRequestUnion::AbortTxn(abort_request) => {
-                                let txn_id = abort_request.txn_id;
-                                println!("Received an abort request for txn: {}", txn_id);
+                    match request.request {
+                        TaskQueueRequestUnion::AbortTxn(abort_request) => {
+                            let txn_id = abort_request.txn_id;
+                            println!("Received an abort request for txn: {}", txn_id);
 
-                                let txn_request = RequestUnion::AbortTxn(AbortTxnRequest {});
-                                let txn = TaskQueue::get_txn(txns_cloned.clone(), txn_id);
-                                let request_metadata = RequestMetadata { txn };
-                                let done_sender = request.done_sender.clone();
-                                let request = Request {
-                                    metadata: request_metadata,
-                                    request_union: txn_request,
-                                };
-                                let _response = executor_cloned
-                                    .execute_request_with_concurrency_retries(request)
-                                    .await
-                                    .unwrap();
-                                done_sender
-                                    .send(QueueResponseUnion::AbortTxn(
-                                        AbortTxnThreadPoolResponse {},
-                                    ))
-                                    .await
-                                    .unwrap();
+                            let done_sender = request.done_sender.clone();
+                            match TaskQueue::get_txn(txns_cloned.clone(), txn_id) {
+                                Some(txn) => {
+                                    let request = Request {
+                                        metadata: RequestMetadata::new(txn),
+                                        request_union: RequestUnion::AbortTxn(AbortTxnRequest {}),
+                                    };
+                                    let res = executor_cloned
+                                        .execute_request_with_concurrency_retries(request)
+                                        .await;
+                                    if let Err(err) = res {
+                                        error!("failed to abort txn {}: {:?}", txn_id, err);
+                                    }
+                                }
+                                // The txn finished without a handle being left, e.g. a batch
+                                // txn, so its locks were released. Only its record is aborted
+                                // if it's still pending.
+                                None => {
+                                    let res = executor_cloned
+                                        .store
+                                        .abort_pending_transaction_record(txn_id);
+                                    if let Err(err) = res {
+                                        error!("failed to abort txn {}: {:?}", txn_id, err);
+                                    }
+                                    executor_cloned
+                                        .concr_manager
+                                        .lock_table
+                                        .txn_wait_queue
+                                        .finalize_txn(txn_id)
+                                        .await;
+                                }
                             }
-                        },
-                        None => {}
+                            done_sender
+                                .send(QueueResponseUnion::AbortTxn(AbortTxnThreadPoolResponse {}))
+                                .await
+                                .unwrap();
+                        }
+                        TaskQueueRequestUnion::ResolveIntents(resolve_request) => {
+                            let txn_id = resolve_request.txn_id;
+                            let res = executor_cloned
+                                .concr_manager
+                                .resolve_intents(
+                                    txn_id,
+                                    resolve_request.commit_timestamp,
+                                    &resolve_request.ignored_sequences,
+                                    resolve_request.lock_spans,
+                                )
+                                .await;
+                            // the intents that are left are resolved by the requests that
+                            // run into them
+                            if let Err(err) = res {
+                                error!(
+                                    "failed to resolve the intents of txn {}: {:?}",
+                                    txn_id, err
+                                );
+                            }
+                            // the committer doesn't necessarily wait for the resolution
+                            let _ = request
+                                .done_sender
+                                .send(QueueResponseUnion::ResolveIntents(
+                                    ResolveIntentsThreadPoolResponse {},
+                                ))
+                                .await;
+                        }
                     }
                 });
             }
@@ -108,14 +177,8 @@ impl TaskQueue {
         }
     }
 
-    fn get_txn(txns: TxnMap, txn_id: Uuid) -> TxnLink {
+    fn get_txn(txns: TxnMap, txn_id: Uuid) -> Option<TxnLink> {
         let txns = txns.read().unwrap();
-        let txn = txns.get(&txn_id);
-        match txn {
-            Some(txn_link) => txn_link.clone(),
-            None => {
-                panic!("No txn found for {}", txn_id)
-            }
-        }
+        txns.get(&txn_id).cloned()
     }
 }
@@ -1,24 +1,37 @@
-use std::sync::{Arc, RwLock};
-
-use tokio::sync::mpsc::Sender;
+use std::{
+    sync::{Arc, RwLock, Weak},
+    time::Duration,
+};
 
+use tokio::{
+    sync::mpsc::{channel, Sender},
+    time::Instant,
+};
+use tracing::error;
+use uuid::Uuid;
 
 use crate::{
     concurrency::concurrency_manager::{ConcurrencyManager, Guard, SequenceReqError},
     db::{
+        codec::ValueCodec,
         db::{TxnLink, TxnMap},
-        request_queue::TaskQueueRequest,
+        request_queue::{ResolveIntentsQueueRequest, TaskQueueRequest, TaskQueueRequestUnion},
     },
     hlc::timestamp::Timestamp,
+    lock_table::lock_table::WaitPolicy,
     storage::{
-        mvcc::{KVStore, MVCCGetParams},
+        mvcc::{KVStore, MVCCScanParams},
         mvcc_key::create_intent_key,
-        txn::{TransactionStatus, TxnIntent},
+        txn::{TransactionStatus, TxnIntent, TXN_HEARTBEAT_INTERVAL},
+        Key, Value,
     },
     timestamp_oracle::oracle::TimestampOracle,
+    StorageError, StorageResult,
 };
 
-use super::request::{Command, Request, ResponseError, ResponseResult, ResponseUnion};
+use super::request::{
+    Command, Request, RequestUnion, ResponseError, ResponseResult, ResponseUnion,
+};
 
 pub type ExecuteResult = Result<ResponseUnion, ExecuteError>;
 
@@ -28,12 +41,35 @@ pub enum ExecuteError {
     TxnCommitted,
     // Failed to execute because its transaction is already aborted
     TxnAborted,
+    // The value of the key didn't match the expected value of a conditional put.
+    // actual is None if the key doesn't exist
+    ConditionFailed { actual: Option<Value> },
+    // The value of the key isn't an integer or the increment overflowed
+    InvalidIncrement,
+    // A read-only txn attempted to write
+    ReadOnlyTxn,
+    // The request couldn't wait for the lock held or reserved by holder_txn
+    LockConflict { key: Key, holder_txn: Uuid },
+    // The request reads beneath the GC threshold, where the history it would read
+    // may have been garbage collected
+    BelowGcThreshold { threshold: Timestamp },
+    // The storage failed to read or write
+    StorageError(StorageError),
+}
+
+impl From<StorageError> for ExecuteError {
+    fn from(err: StorageError) -> Self {
+        ExecuteError::StorageError(err)
+    }
 }
 
 pub struct Executor {
     pub concr_manager: ConcurrencyManager,
     pub store: Arc<KVStore>,
     pub timestamp_oracle: RwLock<TimestampOracle>,
+    // the codec of the DB's values, which increment decodes and encodes
+    pub value_codec: Arc<dyn ValueCodec>,
+    request_sender: Arc<Sender<TaskQueueRequest>>,
 }
 
 impl Executor {
@@ -42,36 +78,107 @@ impl Executor {
         path: &str,
         txns: TxnMap,
         request_sender: Arc<Sender<TaskQueueRequest>>,
+        value_codec: Arc<dyn ValueCodec>,
     ) -> Self {
         let store = Arc::new(KVStore::new_cleaned(path));
         Executor {
-            concr_manager: ConcurrencyManager::new(txns, request_sender, Arc::clone(&store)),
+            concr_manager: ConcurrencyManager::new(
+                txns,
+                request_sender.clone(),
+                Arc::clone(&store),
+            ),
             store: store,
             timestamp_oracle: RwLock::new(TimestampOracle::new()),
+            value_codec,
+            request_sender,
         }
     }
 
-    pub fn new(path: &str, txns: TxnMap, request_sender: Arc<Sender<TaskQueueRequest>>) -> Self {
+    pub fn new(
+        path: &str,
+        txns: TxnMap,
+        request_sender: Arc<Sender<TaskQueueRequest>>,
+        value_codec: Arc<dyn ValueCodec>,
+    ) -> Self {
         let store = Arc::new(KVStore::new(path));
         Executor {
-            concr_manager: ConcurrencyManager::new(txns, request_sender, store.clone()),
+            concr_manager: ConcurrencyManager::new(txns, request_sender.clone(), store.clone()),
             store,
             timestamp_oracle: RwLock::new(TimestampOracle::new()),
+            value_codec,
+            request_sender,
         }
     }
 
+    /**
+     * Heartbeats the txn record until the txn finalizes so that pushers don't consider
+     * the txn abandoned and abort it. Must be started once the record is written. The
+     * task stops once the database is dropped, so that a txn left open doesn't keep the
+     * database open. It also stops once the client is idle, so that the record of a txn
+     * the client went away from expires.
+     */
+    pub fn start_heartbeat(&self, txn_id: Uuid, txn: TxnLink) {
+        let store = Arc::downgrade(&self.store);
+        let txn = Arc::downgrade(&txn);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(TXN_HEARTBEAT_INTERVAL).await;
+                let Some(store) = Weak::upgrade(&store) else {
+                    break;
+                };
+                let is_idle =
+                    Weak::upgrade(&txn).is_none_or(|txn| txn.read().unwrap().is_client_idle());
+                if is_idle {
+                    break;
+                }
+                match store.heartbeat_transaction_record(txn_id) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    // the next heartbeat is retried before the record expires
+                    Err(err) => error!("failed to heartbeat txn {}: {:?}", txn_id, err),
+                }
+            }
+        });
+    }
+
+    /**
+     * The request counts as activity of the txn's client while it's executed.
+     */
     pub async fn execute_request_with_concurrency_retries(
         &self,
         request: Request,
     ) -> ExecuteResult {
+        let _client_request = ClientRequestGuard::new(request.metadata.txn.clone());
+        self.execute_request(request).await
+    }
+
+    async fn execute_request(&self, request: Request) -> ExecuteResult {
+        Executor::check_gc_threshold(&request, self.store.get_gc_threshold())?;
+        if request.metadata.txn.read().unwrap().read_only {
+            return self.execute_historical_request(&request).await;
+        }
+        let lock_deadline = Executor::get_lock_deadline(&request);
         loop {
             let request_union = &request.request_union;
             let guard = self.concr_manager.sequence_req(&request).await;
 
             if let Err(err) = &guard {
                 match err {
-                    &SequenceReqError::TxnAborted => return ExecuteResult::Err(ExecuteError::TxnAborted),
-                    &SequenceReqError::TxnCommitted => return ExecuteResult::Err(ExecuteError::TxnCommitted),
+                    &SequenceReqError::TxnAborted => {
+                        return ExecuteResult::Err(ExecuteError::TxnAborted)
+                    }
+                    &SequenceReqError::TxnCommitted => {
+                        return ExecuteResult::Err(ExecuteError::TxnCommitted)
+                    }
+                    SequenceReqError::LockConflict { key, holder_txn } => {
+                        return ExecuteResult::Err(ExecuteError::LockConflict {
+                            key: key.clone(),
+                            holder_txn: *holder_txn,
+                        })
+                    }
+                    SequenceReqError::StorageError(err) => {
+                        return ExecuteResult::Err(ExecuteError::StorageError(err.clone()))
+                    }
                 }
             }
 
@@ -81,29 +188,183 @@ impl Executor {
             } else {
                 self.execute_write_request(&request, &guard).await
             };
+            // the intent is resolved or pushed before the latches are released
+            let is_handled = match &result {
+                Err(ResponseError::WriteIntentError(err)) => {
+                    match self.resolve_committed_intent(&err.intent) {
+                        Ok(false) => self.push_intent(&request, &err.intent),
+                        res => res,
+                    }
+                }
+                _ => Ok(false),
+            };
             self.concr_manager.finish_req(guard).await;
+            let is_handled = is_handled?;
             match result {
                 Ok(result) => {
                     // release latches and dequeue request from lockTable
 
                     return Ok(result);
                 }
-                Err(err) => match err {
-                    ResponseError::WriteIntentError(err) => {
-                        self.handle_write_intent_error(err.intent.clone());
-                    }
-                    ResponseError::ReadRefreshError => {
-                        return Err(ExecuteError::ReadRefreshFailure);
-                    }
-                    ResponseError::TxnAbortedError => {
-                        return Err(ExecuteError::TxnAborted);
-                    }
-                },
+                Err(ResponseError::WriteIntentError(_)) if is_handled => {}
+                Err(ResponseError::WriteIntentError(err)) => {
+                    Executor::check_can_wait_for_intent(&request, lock_deadline, &err.intent)?;
+                    self.handle_write_intent_error(err.intent.clone())?;
+                }
+                Err(err) => return Err(Executor::to_execute_error(err)),
             };
         }
     }
 
-    pub fn handle_write_intent_error(&self, txn_intent: TxnIntent) {
+    /**
+     * Resolves the intent with the txn record if the intent's txn committed but the
+     * intent wasn't resolved yet. Must be called while the latches are held.
+     * Returns whether the intent was resolved.
+     */
+    fn resolve_committed_intent(&self, intent: &TxnIntent) -> StorageResult<bool> {
+        let txn_id = intent.txn_meta.txn_id;
+        match self.store.get_transaction_record(txn_id) {
+            Some(record) if record.status == TransactionStatus::COMMITTED => {
+                self.store.mvcc_resolve_intent(
+                    intent.key.clone(),
+                    record.metadata.write_timestamp,
+                    txn_id,
+                    &record.ignored_sequences,
+                )?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /**
+     * A read that runs into an intent beneath its read timestamp pushes the intent's txn
+     * above the read timestamp so that it can read beneath the intent on the retry.
+     * Returns whether the push succeeded.
+     */
+    fn push_intent(&self, request: &Request, intent: &TxnIntent) -> StorageResult<bool> {
+        if !request.request_union.is_read_only() {
+            return Ok(false);
+        }
+        let push_timestamp = request
+            .metadata
+            .txn
+            .read()
+            .unwrap()
+            .read_timestamp
+            .next_logical_timestamp();
+        self.concr_manager.lock_table.push_holder_timestamp(
+            &intent.key,
+            intent.txn_meta.txn_id,
+            push_timestamp,
+        )
+    }
+
+    /**
+     * Rejects requests that read beneath the GC threshold. Committing also reads since
+     * the read refresh scans the history above the txn's read timestamp. Aborting
+     * doesn't read, so a txn can always be aborted.
+     */
+    fn check_gc_threshold(
+        request: &Request,
+        gc_threshold: Option<Timestamp>,
+    ) -> Result<(), ExecuteError> {
+        if let RequestUnion::AbortTxn(_) = request.request_union {
+            return Ok(());
+        }
+        match gc_threshold {
+            Some(threshold) if request.metadata.txn.read().unwrap().read_timestamp < threshold => {
+                Err(ExecuteError::BelowGcThreshold { threshold })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn get_lock_deadline(request: &Request) -> Option<Instant> {
+        match request.metadata.wait_policy {
+            WaitPolicy::Block {
+                timeout: Some(timeout),
+            } => Some(Instant::now() + timeout),
+            _ => None,
+        }
+    }
+
+    /**
+     * A request that runs into an intent of another txn retries until the intent is
+     * resolved. Returns a lock conflict if the wait policy doesn't allow it to wait.
+     */
+    fn check_can_wait_for_intent(
+        request: &Request,
+        lock_deadline: Option<Instant>,
+        intent: &TxnIntent,
+    ) -> Result<(), ExecuteError> {
+        let can_wait = match request.metadata.wait_policy {
+            WaitPolicy::Block { .. } => {
+                lock_deadline.is_none_or(|deadline| Instant::now() < deadline)
+            }
+            WaitPolicy::Error | WaitPolicy::SkipLocked => false,
+        };
+        if can_wait {
+            Ok(())
+        } else {
+            Err(ExecuteError::LockConflict {
+                key: intent.key.clone(),
+                holder_txn: intent.txn_meta.txn_id,
+            })
+        }
+    }
+
+    /**
+     * Evaluates a request of a read-only txn pinned to a past timestamp. The MVCC history
+     * beneath the timestamp doesn't change other than intents being resolved, so the
+     * request isn't sequenced. It doesn't acquire latches, doesn't wait in the lockTable
+     * and doesn't update the timestamp oracle.
+     *
+     * An intent beneath the timestamp could still be committed beneath the timestamp,
+     * so the request backs off until the intent is resolved.
+     */
+    async fn execute_historical_request(&self, request: &Request) -> ExecuteResult {
+        if !request.request_union.is_read_only() {
+            return Err(ExecuteError::ReadOnlyTxn);
+        }
+        let lock_deadline = Executor::get_lock_deadline(request);
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            let result = request.request_union.execute(&request.metadata, self).await;
+            match result {
+                Ok(result) => return Ok(result),
+                Err(ResponseError::WriteIntentError(err)) => {
+                    Executor::check_can_wait_for_intent(request, lock_deadline, &err.intent)?;
+                    self.handle_write_intent_error(err.intent)?;
+                    tokio::time::sleep(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, Duration::from_millis(100));
+                }
+                Err(err) => return Err(Executor::to_execute_error(err)),
+            }
+        }
+    }
+
+    /**
+     * Converts the errors that fail the request. Write intent errors are retried instead.
+     */
+    fn to_execute_error(err: ResponseError) -> ExecuteError {
+        match err {
+            ResponseError::WriteIntentError(_) => unreachable!(),
+            ResponseError::ReadRefreshError => ExecuteError::ReadRefreshFailure,
+            ResponseError::TxnAbortedError => ExecuteError::TxnAborted,
+            ResponseError::ConditionFailedError(err) => {
+                ExecuteError::ConditionFailed { actual: err.actual }
+            }
+            ResponseError::InvalidIncrementError => ExecuteError::InvalidIncrement,
+            ResponseError::LockConflictError(err) => ExecuteError::LockConflict {
+                key: err.key,
+                holder_txn: err.holder_txn,
+            },
+            ResponseError::StorageError(err) => ExecuteError::StorageError(err),
+        }
+    }
+
+    pub fn handle_write_intent_error(&self, txn_intent: TxnIntent) -> StorageResult<()> {
         // check the txn record for the status of transaction.
         let record = self
             .store
@@ -111,9 +372,10 @@ impl Executor {
         if let Some(txn_record) = record {
             if let TransactionStatus::ABORTED = txn_record.status {
                 // If the txn has aborted, remove the txn record
-                self.store.mvcc_delete(create_intent_key(&txn_intent.key));
+                self.store.mvcc_delete(create_intent_key(&txn_intent.key))?;
             }
         }
+        Ok(())
     }
 
     pub async fn execute_write_request(&self, request: &Request, _guard: &Guard) -> ResponseResult {
@@ -165,28 +427,57 @@ impl Executor {
         result
     }
 
-    pub fn resolve_intents_for_txn(&self, txn: TxnLink) {
-        let txn = txn.read().unwrap();
-        let write_timestamp = txn.write_timestamp;
-        let keys = txn.lock_spans.read().unwrap();
+    /**
+     * Hands the resolution of the committed txn's intents to the task queue without
+     * waiting for it.
+     */
+    pub async fn resolve_intents_async(&self, txn: TxnLink) {
+        let request = {
+            let txn = txn.read().unwrap();
+            let lock_spans = txn.lock_spans.read().unwrap().clone();
+            ResolveIntentsQueueRequest {
+                txn_id: txn.txn_id,
+                commit_timestamp: txn.write_timestamp,
+                ignored_sequences: txn.ignored_sequences.clone(),
+                lock_spans,
+            }
+        };
+        let (done_sender, _) = channel(1);
+        self.request_sender
+            .send(TaskQueueRequest {
+                done_sender: Arc::new(done_sender),
+                request: TaskQueueRequestUnion::ResolveIntents(request),
+            })
+            .await
+            .unwrap();
+    }
 
-        for key in keys.iter() {
-            self.store
-                .mvcc_resolve_intent(key.clone(), write_timestamp, txn.txn_id);
-        }
+    /**
+     * Returns the keys of the intents laid by the txn within its lock spans.
+     */
+    pub fn get_txn_intent_keys(&self, txn: TxnLink) -> Vec<Key> {
+        let txn = txn.read().unwrap();
+        let txn_id = txn.txn_id;
+        let lock_spans = txn.lock_spans.read().unwrap();
+        lock_spans
+            .iter()
+            .flat_map(|span| self.store.mvcc_scan_intents(&span.start_key, &span.end_key))
+            .filter(|intent| intent.txn_meta.txn_id == txn_id)
+            .map(|intent| intent.key)
+            .collect()
     }
 
     /**
      * Updates the txn's readTimestamp to its writeTimestamp and return true.
-     * If it's not possible, return false.
+     * If it's not possible, return false. Fails if resolving an intent fails.
      *
      * Advancing a transaction’s read timestamp from ta to tb is possible if we
      * can prove that none of the record that the transaction has read at ta
      * has been updated to the interval (ta,tb].
      *
-     * To prove it, we use MVCCGet and set tb as the higher bound.
+     * To prove it, we scan each span in the read set and set tb as the higher bound.
      */
-    pub fn refresh_read_timestamp(&self, txn_link: TxnLink) -> bool {
+    pub fn refresh_read_timestamp(&self, txn_link: TxnLink) -> StorageResult<bool> {
         let txn = txn_link.read().unwrap();
         let txn_id = txn.txn_id;
         // TODO: Remove clone
@@ -194,31 +485,81 @@ impl Executor {
         let to_timestamp = txn.write_timestamp;
         let from_timestamp = txn.read_timestamp;
         drop(txn);
-        for key in read_set.iter() {
-            let res = self.store.mvcc_get(
-                &key,
-                to_timestamp,
-                MVCCGetParams {
-                    transaction: Some(txn_link.clone()),
-                },
-            );
-            if let Some((mvcc_key, _)) = res.value {
+        // the write timestamp was never bumped so there is nothing to refresh
+        if to_timestamp == from_timestamp {
+            return Ok(true);
+        }
+        for span in read_set.iter() {
+            let res = loop {
+                let res = self.store.mvcc_scan(
+                    span.start_key.clone(),
+                    span.end_key.clone(),
+                    to_timestamp,
+                    MVCCScanParams {
+                        max_result_count: usize::MAX,
+                        transaction: Some(txn_link.clone()),
+                        end_key_exclusive: false,
+                        // deleting a key is also an update to the key
+                        tombstones: true,
+                    },
+                );
+                // the commit holds the latches on the read set, so the intents of
+                // committed txns are resolved and the span is scanned again
+                let mut did_resolve = false;
+                for (intent, _) in res.intents.iter() {
+                    if intent.txn_meta.txn_id != txn_id && self.resolve_committed_intent(intent)? {
+                        did_resolve = true;
+                        break;
+                    }
+                }
+                if !did_resolve {
+                    break res;
+                }
+            };
+            for (mvcc_key, _) in res.results.iter() {
                 let mvcc_timestamp = mvcc_key.timestamp;
-                if mvcc_timestamp < from_timestamp {
-                    return false;
+                // the txn's own intents are returned at the intent timestamp
+                if mvcc_timestamp.is_intent_timestamp() {
+                    continue;
+                }
+                // the scan is at to_timestamp, so the version is in (from_timestamp, to_timestamp]
+                if mvcc_timestamp > from_timestamp {
+                    return Ok(false);
                 }
             }
 
             // Check if an intent which is not owned by this transaction was written
             // at or beneath the refresh timestamp.
-            if let Some((intent, _)) = res.intent {
+            for (intent, _) in res.intents.iter() {
                 let intent_timestamp = intent.txn_meta.write_timestamp;
                 if intent.txn_meta.txn_id != txn_id && intent_timestamp < to_timestamp {
-                    return false;
+                    return Ok(false);
                 }
             }
         }
         txn_link.write().unwrap().write_timestamp = to_timestamp;
-        true
+        Ok(true)
+    }
+}
+
+/**
+ * Marks a request of the client as being executed until the guard is dropped.
+ */
+struct ClientRequestGuard {
+    txn: TxnLink,
+}
+
+impl ClientRequestGuard {
+    fn new(txn: TxnLink) -> Self {
+        txn.write().unwrap().start_client_request();
+        ClientRequestGuard { txn }
+    }
+}
+
+impl Drop for ClientRequestGuard {
+    fn drop(&mut self) {
+        if let Ok(mut txn) = self.txn.write() {
+            txn.finish_client_request();
+        }
     }
 }
@@ -1,22 +1,24 @@
-use std::{
-    collections::{HashSet},
-};
+use std::sync::Mutex;
 
 use async_trait::async_trait;
-use rand::prelude::Distribution;
 use uuid::Uuid;
 
 use crate::{
     db::db::TxnLink,
     hlc::timestamp::Timestamp,
-    latch_manager::latch_interval_btree::{Range},
-    lock_table::lock_table::{AbortUpdateLock, CommitUpdateLock, UpdateLock},
+    latch_manager::latch_interval_btree::Range,
+    lock_table::lock_table::{
+        AbortUpdateLock, AcquireLockError, CommitUpdateLock, LockStrength, UpdateLock, WaitPolicy,
+    },
     storage::{
-        mvcc::{MVCCGetParams},
-        mvcc_key::{create_intent_key, MVCCKey},
-        txn::{TransactionStatus, TxnIntent},
+        create_tombstone, from_stored_value, is_tombstone,
+        mvcc::{MVCCGetParams, MVCCScanParams, MVCCWriteError},
+        mvcc_key::MVCCKey,
+        to_stored_value,
+        txn::{TransactionStatus, Txn, TxnIntent},
         Key, Value,
     },
+    StorageError,
 };
 
 use super::executor::Executor;
@@ -36,18 +38,58 @@ pub enum ResponseError {
     WriteIntentError(WriteIntentErrorData),
     ReadRefreshError,
     TxnAbortedError,
+    ConditionFailedError(ConditionFailedErrorData),
+    // the existing value isn't an integer or the increment overflowed
+    InvalidIncrementError,
+    LockConflictError(LockConflictErrorData),
+    StorageError(StorageError),
+}
+
+impl From<StorageError> for ResponseError {
+    fn from(err: StorageError) -> Self {
+        ResponseError::StorageError(err)
+    }
+}
+
+impl From<MVCCWriteError> for ResponseError {
+    fn from(err: MVCCWriteError) -> Self {
+        match err {
+            MVCCWriteError::WriteIntentError(err) => {
+                ResponseError::WriteIntentError(WriteIntentErrorData { intent: err.intent })
+            }
+            MVCCWriteError::StorageError(err) => ResponseError::StorageError(err),
+        }
+    }
 }
 
 pub struct WriteIntentErrorData {
     pub intent: TxnIntent,
 }
 
+pub struct LockConflictErrorData {
+    pub key: Key,
+    // the txn holding or reserving the lock
+    pub holder_txn: Uuid,
+}
+
+pub struct ConditionFailedErrorData {
+    // the value found for the key. None if the key doesn't exist
+    pub actual: Option<Value>,
+}
+
 pub enum ResponseUnion {
     BeginTransaction(BeginTxnResponse),
     CommitTxn(CommitTxnResponse),
     AbortTxn(AbortTxnResponse),
     Get(GetResponse),
+    GetForUpdate(GetForUpdateResponse),
     Put(PutResponse),
+    Scan(ScanResponse),
+    Delete(DeleteResponse),
+    DeleteRange(DeleteRangeResponse),
+    ConditionalPut(ConditionalPutResponse),
+    Increment(IncrementResponse),
+    Batch(BatchResponse),
 }
 
 pub enum RequestUnion {
@@ -55,18 +97,37 @@ pub enum RequestUnion {
     CommitTxn(CommitTxnRequest),
     AbortTxn(AbortTxnRequest),
     Get(GetRequest),
+    GetForUpdate(GetForUpdateRequest),
     Put(PutRequest),
-    // TODO: ConditionalPut
-    // TODO: Scan
+    Scan(ScanRequest),
+    Delete(DeleteRequest),
+    DeleteRange(DeleteRangeRequest),
+    ConditionalPut(ConditionalPutRequest),
+    Increment(IncrementRequest),
+    Batch(BatchRequest),
 }
 
 // TODO: Does this need a timestamp in there?
 pub type SpanSet<K> = Vec<Range<K>>;
 
+/**
+ * Sorts the spans and merges the spans that overlap with each other so that
+ * the resulting spans are disjoint. Start and end keys are both inclusive.
+ */
 pub fn dedupe_spanset(v: &mut SpanSet<Key>) {
-    // note the Copy constraint
-    let mut uniques = HashSet::<Key>::new();
-    v.retain(|e| uniques.insert((*e.start_key).to_vec()));
+    v.sort_by(|a, b| a.start_key.cmp(&b.start_key));
+    let mut merged: SpanSet<Key> = Vec::with_capacity(v.len());
+    for span in v.drain(..) {
+        match merged.last_mut() {
+            Some(last) if span.start_key <= last.end_key => {
+                if span.end_key > last.end_key {
+                    last.end_key = span.end_key;
+                }
+            }
+            _ => merged.push(span),
+        }
+    }
+    *v = merged;
 }
 
 #[derive(Debug, Clone)]
@@ -87,16 +148,53 @@ pub trait Command {
      */
     fn collect_spans(&self, txn_link: TxnLink) -> SpanSet<Key>;
 
+    /**
+     * The strength of the locks that the request acquires on its spans.
+     * Only used for requests that aren't read-only.
+     */
+    fn lock_strength(&self) -> LockStrength {
+        LockStrength::Intent
+    }
+
     async fn execute(&self, header: &RequestMetadata, writer: &Executor) -> ResponseResult;
 }
 
 // Similar to CRDB's RequestHeader
 pub struct RequestMetadata {
     /**
-     * For now, assume every request is part of a transaction.
-     * In the future, we can support non-transactional reads.
+     * Transactional requests use the txn of the request.
+     *
+     * Non-transactional requests use a placeholder txn created by new_non_txn.
+     * The placeholder only carries the request's timestamp so that the lockTable
+     * and timestamp oracle can treat it like any other reader. It is never
+     * registered with the DB and never has a txn record.
      */
     pub txn: TxnLink,
+
+    /**
+     * How the request handles conflicting locks.
+     */
+    pub wait_policy: WaitPolicy,
+}
+
+impl RequestMetadata {
+    pub fn new(txn: TxnLink) -> Self {
+        RequestMetadata {
+            txn,
+            wait_policy: WaitPolicy::default(),
+        }
+    }
+
+    pub fn new_non_txn(timestamp: Timestamp) -> Self {
+        RequestMetadata::new(Txn::new_link(Uuid::new_v4(), timestamp))
+    }
+
+    /**
+     * Whether the request skips the keys locked by other txns instead of waiting.
+     */
+    pub fn skips_locked_keys(&self) -> bool {
+        self.wait_policy == WaitPolicy::SkipLocked
+    }
 }
 
 pub struct BeginTxnRequest {
@@ -120,7 +218,8 @@ impl Command for BeginTxnRequest {
         let write_timestamp = txn.write_timestamp;
         executor
             .store
-            .create_pending_transaction_record(self.txn_id, write_timestamp);
+            .create_pending_transaction_record(self.txn_id, write_timestamp)?;
+        executor.start_heartbeat(self.txn_id, header.txn.clone());
         Ok(ResponseUnion::BeginTransaction(BeginTxnResponse {}))
     }
 }
@@ -138,21 +237,9 @@ impl Command for AbortTxnRequest {
     fn collect_spans(&self, txn: TxnLink) -> SpanSet<Key> {
         let txn = txn.read().unwrap();
         let read_set = txn.read_set.read().unwrap();
-        let mut lock_spans = (&*txn.lock_spans.read().unwrap())
-            .iter()
-            .map(|k| Range {
-                start_key: k.clone(),
-                end_key: k.clone(),
-            })
-            .collect::<SpanSet<Key>>();
+        let mut lock_spans = txn.lock_spans.read().unwrap().clone();
 
-        let mut read_spans = read_set
-            .iter()
-            .map(|k| Range {
-                start_key: k.clone(),
-                end_key: k.clone(),
-            })
-            .collect::<SpanSet<Key>>();
+        let mut read_spans = read_set.clone();
         lock_spans.append(&mut read_spans);
         dedupe_spanset(&mut lock_spans);
         lock_spans
@@ -169,15 +256,11 @@ impl Command for AbortTxnRequest {
             }
         }
 
-        // finalize the transaction record
+        // finalize the transaction record and remove all uncommitted intents from DB
+        let intent_keys = executor.get_txn_intent_keys(header.txn.clone());
         executor
             .store
-            .update_transaction_record_to_abort(txn_id, write_timestamp);
-
-        // Remove all uncomitted intents from DB
-        for key in self.get_lock_keys(header.txn.clone()).iter() {
-            executor.store.mvcc_delete(create_intent_key(key));
-        }
+            .abort_transaction(txn_id, write_timestamp, &intent_keys)?;
 
         // update the lock table to release all uncommitted intent holders
         executor
@@ -204,10 +287,6 @@ impl AbortTxnRequest {
         let txn = txn.read().unwrap();
         (txn.txn_id, txn.write_timestamp)
     }
-
-    pub fn get_lock_keys(&self, txn: TxnLink) -> Vec<Key> {
-        txn.read().unwrap().lock_spans.read().unwrap().clone()
-    }
 }
 
 pub struct CommitTxnRequest {}
@@ -226,28 +305,16 @@ impl Command for CommitTxnRequest {
     fn collect_spans(&self, txn: TxnLink) -> SpanSet<Key> {
         let txn = txn.read().unwrap();
         let read_set = txn.read_set.read().unwrap();
-        let mut lock_spans = (&*txn.lock_spans.read().unwrap())
-            .iter()
-            .map(|k| Range {
-                start_key: k.clone(),
-                end_key: k.clone(),
-            })
-            .collect::<SpanSet<Key>>();
+        let mut lock_spans = txn.lock_spans.read().unwrap().clone();
 
-        let mut read_spans = read_set
-            .iter()
-            .map(|k| Range {
-                start_key: k.clone(),
-                end_key: k.clone(),
-            })
-            .collect::<SpanSet<Key>>();
+        let mut read_spans = read_set.clone();
         lock_spans.append(&mut read_spans);
         dedupe_spanset(&mut lock_spans);
         lock_spans
     }
 
     async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
-        let (txn_id, write_timestamp) = self.get_txn_id_write_timestamp(header.txn.clone());
+        let (txn_id, _) = self.get_txn_id_write_timestamp(header.txn.clone());
         let record_option = executor.store.get_transaction_record(txn_id);
         if let Some(record) = record_option {
             if let TransactionStatus::ABORTED = record.status {
@@ -263,18 +330,36 @@ impl Command for CommitTxnRequest {
             }
         }
 
-        // read refresh
-        let did_refresh = executor.refresh_read_timestamp(header.txn.clone());
-        if !did_refresh {
-            return Err(ResponseError::ReadRefreshError);
-        }
-
-        executor
-            .store
-            .commit_transaction_record(txn_id, write_timestamp);
+        // Readers could have pushed the txn's timestamp. The txn commits at the pushed
+        // timestamp if its reads can be refreshed to it.
+        let write_timestamp = loop {
+            let did_refresh = executor
+                .refresh_read_timestamp(header.txn.clone())
+                .map_err(ResponseError::StorageError)?;
+            if !did_refresh {
+                return Err(ResponseError::ReadRefreshError);
+            }
+            let (write_timestamp, ignored_sequences) = {
+                let txn = header.txn.read().unwrap();
+                (txn.write_timestamp, txn.ignored_sequences.clone())
+            };
+            match executor.store.commit_transaction_record(
+                txn_id,
+                write_timestamp,
+                &ignored_sequences,
+            )? {
+                None => break write_timestamp,
+                Some(pushed_timestamp) => header
+                    .txn
+                    .write()
+                    .unwrap()
+                    .bump_write_timestamp(pushed_timestamp),
+            }
+        };
 
-        //  Resolve intents so blocked requests won't run into uncommitted intents
-        executor.resolve_intents_for_txn(header.txn.clone());
+        // The txn is committed once its record is. The intents are resolved in the
+        // background so that the commit latency doesn't grow with the number of writes.
+        executor.resolve_intents_async(header.txn.clone()).await;
         executor
             .concr_manager
             .update_txn_locks(
@@ -287,9 +372,8 @@ impl Command for CommitTxnRequest {
             .await;
         //   The order that we resolve intents or update the lockTable doesn't have to be strictly
         //   synchronous because if we updated the lockTable but haven't resolved the intent,
-        //   the subsequent request will run into an uncommitted record and will add it to the lockTable
-        //   again. And it will then timeout and push the transaction to either resolve the intent
-        //   or detect that the uncommitted intent is already cleaned up
+        //   the subsequent request will run into the intent and resolve it with the
+        //   committed txn record before retrying
 
         // release any pushers blocked by the transaction
         executor
@@ -336,6 +420,9 @@ impl Command for GetRequest {
         // TODO: Don't need two reads
         let read_timestamp = header.txn.read().unwrap().read_timestamp;
         let txn_id = header.txn.read().unwrap().txn_id;
+        if header.skips_locked_keys() && is_locked(executor, &self.key, txn_id) {
+            return Ok(ResponseUnion::Get(GetResponse { value: None }));
+        }
         let result = executor.store.mvcc_get(
             &self.key,
             read_timestamp,
@@ -343,12 +430,18 @@ impl Command for GetRequest {
                 transaction: Some(header.txn.clone()),
             },
         );
-        let mut value = result.value;
-        if let Some((ref intent, uncommitted_value)) = result.intent {
-            if txn_id == intent.txn_meta.txn_id {
-                // this means the txn is reading its own uncommitted write
-                value = Some((create_intent_key(&self.key.clone()), uncommitted_value))
-            } else if intent.txn_meta.write_timestamp <= read_timestamp {
+        // the scanner returns the txn's own uncommitted write as the value
+        let value = result.value;
+        if let Some((ref intent, _)) = result.intent {
+            if txn_id != intent.txn_meta.txn_id
+                && header.skips_locked_keys()
+                && !is_committed_intent(executor, intent)
+            {
+                return Ok(ResponseUnion::Get(GetResponse { value: None }));
+            }
+            if txn_id != intent.txn_meta.txn_id
+                && (header.skips_locked_keys() || intent.txn_meta.write_timestamp <= read_timestamp)
+            {
                 return Err(ResponseError::WriteIntentError(WriteIntentErrorData {
                     intent: intent.clone(),
                 }));
@@ -360,10 +453,142 @@ impl Command for GetRequest {
             .read()
             .unwrap()
             .append_read_sets(self.key.clone());
+        let value = value.map(|(mvcc_key, value)| (mvcc_key, from_stored_value(value)));
         return Ok(ResponseUnion::Get(GetResponse { value }));
     }
 }
 
+/**
+ * Whether a request skipping locked keys skips the key. Only locks that block
+ * writers count: exclusive locks, intents and reservations of other txns.
+ */
+fn is_locked(executor: &Executor, key: &Key, txn_id: Uuid) -> bool {
+    executor
+        .concr_manager
+        .lock_table
+        .is_locked_by_other_txn(key, txn_id, LockStrength::Shared)
+}
+
+/**
+ * Whether the intent belongs to a txn that committed but whose intents weren't resolved
+ * yet. Such an intent doesn't lock the key. Requests that skip locked keys return a
+ * write intent error instead so that the executor resolves the intent and retries.
+ */
+fn is_committed_intent(executor: &Executor, intent: &TxnIntent) -> bool {
+    executor
+        .store
+        .get_transaction_record(intent.txn_meta.txn_id)
+        .is_some_and(|record| record.status == TransactionStatus::COMMITTED)
+}
+
+/**
+ * A locking read. It acquires an unreplicated lock on the key without writing an intent,
+ * so other txns can't lock or write the key until the txn finalizes.
+ */
+pub struct GetForUpdateRequest {
+    pub key: Key,
+    pub strength: LockStrength,
+}
+
+pub struct GetForUpdateResponse {
+    pub value: Option<(MVCCKey, Value)>,
+}
+
+#[async_trait]
+impl Command for GetForUpdateRequest {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn collect_spans(&self, _: TxnLink) -> SpanSet<Key> {
+        Vec::from([Range {
+            start_key: self.key.clone(),
+            end_key: self.key.clone(),
+        }])
+    }
+
+    fn lock_strength(&self) -> LockStrength {
+        self.strength
+    }
+
+    /**
+     * Reads the latest committed value instead of the value at the txn's read timestamp.
+     * The key can't change until the lock is released, so the read doesn't need to be
+     * refreshed. If the value is newer than the read timestamp, the txn's write timestamp
+     * is bumped past it.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        let txn_id = header.txn.read().unwrap().txn_id;
+        let skipped_response = ResponseUnion::GetForUpdate(GetForUpdateResponse { value: None });
+        if header.skips_locked_keys()
+            && executor.concr_manager.lock_table.is_locked_by_other_txn(
+                &self.key,
+                txn_id,
+                self.strength,
+            )
+        {
+            return Ok(skipped_response);
+        }
+        let result = executor.store.mvcc_scan(
+            self.key.clone(),
+            self.key.clone(),
+            Timestamp::max_timestamp(),
+            MVCCScanParams {
+                max_result_count: 1,
+                transaction: Some(header.txn.clone()),
+                end_key_exclusive: false,
+                // a newer tombstone also bumps the write timestamp
+                tombstones: true,
+            },
+        );
+        // the lock conflicts with intents of other txns at any timestamp
+        for (intent, _) in result.intents.iter() {
+            if txn_id != intent.txn_meta.txn_id {
+                if header.skips_locked_keys() && !is_committed_intent(executor, intent) {
+                    return Ok(skipped_response);
+                }
+                return Err(ResponseError::WriteIntentError(WriteIntentErrorData {
+                    intent: intent.clone(),
+                }));
+            }
+        }
+
+        let value = result.results.into_iter().next();
+        if let Some((ref mvcc_key, _)) = value {
+            // the txn's own intent was written at its write timestamp already
+            if !mvcc_key.timestamp.is_intent_timestamp() {
+                header
+                    .txn
+                    .write()
+                    .unwrap()
+                    .bump_write_timestamp(mvcc_key.timestamp.next_logical_timestamp());
+            }
+        }
+
+        acquire_lock(header, executor, self.key.clone(), self.strength).await?;
+        // the lock spans are released when the txn finalizes
+        header.txn.read().unwrap().append_lock_span(Range {
+            start_key: self.key.clone(),
+            end_key: self.key.clone(),
+        });
+
+        // The executor doesn't update the timestamp oracle for requests that aren't
+        // read-only. The value is read at the write timestamp, so later writers are
+        // pushed above it.
+        let write_timestamp = header.txn.read().unwrap().write_timestamp;
+        executor.timestamp_oracle.read().unwrap().add(
+            write_timestamp,
+            self.key.clone(),
+            self.key.clone(),
+            Some(txn_id),
+        );
+        let value = value
+            .filter(|(_, value)| !is_tombstone(value))
+            .map(|(mvcc_key, value)| (mvcc_key, from_stored_value(value)));
+        Ok(ResponseUnion::GetForUpdate(GetForUpdateResponse { value }))
+    }
+}
+
 pub struct PutRequest {
     pub key: Key,
     pub value: Value,
@@ -385,38 +610,572 @@ impl Command for PutRequest {
     }
 
     async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
-        let res = executor.store.mvcc_put_raw(
-            self.key.clone(),
-            None, // TODO: Allow put without transaction
-            Some(header.txn.clone()),
-            self.value.clone(),
+        let value = to_stored_value(&self.value);
+        write_intent(header, executor, self.key.clone(), value).await?;
+        Ok(ResponseUnion::Put(PutResponse {}))
+    }
+}
+
+/**
+ * Writes an intent for the key and registers the lock with the lockTable
+ * and the txn's lock spans.
+ */
+async fn write_intent(
+    header: &RequestMetadata,
+    executor: &Executor,
+    key: Key,
+    value: Value,
+) -> Result<(), ResponseError> {
+    let res = executor.store.mvcc_put_raw(
+        key.clone(),
+        None, // TODO: Allow put without transaction
+        Some(header.txn.clone()),
+        value,
+    );
+
+    match res {
+        Ok(_) => {
+            // update the txn's lock spans to account for the intent being written
+            header.txn.read().unwrap().append_lock_span(Range {
+                start_key: key.clone(),
+                end_key: key.clone(),
+            });
+            // add to lockTable
+            acquire_lock(header, executor, key.clone(), LockStrength::Intent).await?;
+            println!(
+                "finished executing write for txn {} with key {:?}",
+                header.txn.read().unwrap().txn_id,
+                key
+            );
+            Ok(())
+        }
+        Err(err) => Err(ResponseError::from(err)),
+    }
+}
+
+/**
+ * Adds the lock the txn acquired on the key to the lockTable.
+ */
+async fn acquire_lock(
+    header: &RequestMetadata,
+    executor: &Executor,
+    key: Key,
+    strength: LockStrength,
+) -> Result<(), ResponseError> {
+    executor
+        .concr_manager
+        .lock_table
+        .acquire_lock(key, header.txn.clone(), strength)
+        .await
+        .map_err(|err| match err {
+            AcquireLockError::LockConflict { key, holder_txn } => {
+                ResponseError::LockConflictError(LockConflictErrorData { key, holder_txn })
+            }
+        })
+}
+
+/**
+ * Reads the most recent value of the key for a request that writes based on it: the
+ * txn's own intent or the newest committed value, even if it's newer than the txn's
+ * write timestamp. The write timestamp is bumped past the newest committed version so
+ * that the intent isn't written beneath it. Returns None if the key doesn't exist or
+ * is deleted.
+ */
+fn read_latest_for_write(
+    header: &RequestMetadata,
+    executor: &Executor,
+    key: &Key,
+) -> Result<Option<Value>, ResponseError> {
+    let txn_id = header.txn.read().unwrap().txn_id;
+    let result = executor.store.mvcc_scan(
+        key.clone(),
+        key.clone(),
+        Timestamp::max_timestamp(),
+        MVCCScanParams {
+            max_result_count: 1,
+            transaction: Some(header.txn.clone()),
+            end_key_exclusive: false,
+            // a newer tombstone also bumps the write timestamp
+            tombstones: true,
+        },
+    );
+    for (intent, _) in result.intents.iter() {
+        if txn_id != intent.txn_meta.txn_id {
+            return Err(ResponseError::WriteIntentError(WriteIntentErrorData {
+                intent: intent.clone(),
+            }));
+        }
+    }
+
+    let value = result.results.into_iter().next();
+    if let Some((ref mvcc_key, _)) = value {
+        // the txn's own intent was written at its write timestamp already
+        if !mvcc_key.timestamp.is_intent_timestamp() {
+            header
+                .txn
+                .write()
+                .unwrap()
+                .bump_write_timestamp(mvcc_key.timestamp.next_logical_timestamp());
+        }
+    }
+    Ok(value
+        .filter(|(_, value)| !is_tombstone(value))
+        .map(|(_, value)| from_stored_value(value)))
+}
+
+pub struct ConditionalPutRequest {
+    pub key: Key,
+    pub value: Value,
+    // the value that the key must have for the put to succeed. None means the key must not exist
+    pub expected_value: Option<Value>,
+}
+
+pub struct ConditionalPutResponse {}
+
+#[async_trait]
+impl Command for ConditionalPutRequest {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn collect_spans(&self, _: TxnLink) -> SpanSet<Key> {
+        Vec::from([Range {
+            start_key: self.key.clone(),
+            end_key: self.key.clone(),
+        }])
+    }
+
+    /**
+     * Compares the most recent value of the key (the txn's own intent or the newest
+     * committed value, even if it's newer than the txn's write timestamp) with the
+     * expected value. The intent is only written if they are equal. Since the key is
+     * latched, the comparison and the write are atomic.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        let actual = read_latest_for_write(header, executor, &self.key)?;
+        if actual != self.expected_value {
+            return Err(ResponseError::ConditionFailedError(
+                ConditionFailedErrorData { actual },
+            ));
+        }
+        let value = to_stored_value(&self.value);
+        write_intent(header, executor, self.key.clone(), value).await?;
+        Ok(ResponseUnion::ConditionalPut(ConditionalPutResponse {}))
+    }
+}
+
+pub struct IncrementRequest {
+    pub key: Key,
+    pub increment: i64,
+}
+
+pub struct IncrementResponse {
+    pub new_value: i64,
+}
+
+#[async_trait]
+impl Command for IncrementRequest {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn collect_spans(&self, _: TxnLink) -> SpanSet<Key> {
+        Vec::from([Range {
+            start_key: self.key.clone(),
+            end_key: self.key.clone(),
+        }])
+    }
+
+    /**
+     * Reads the most recent value of the key (the txn's own intent or the newest
+     * committed value, even if it's newer than the txn's write timestamp) and writes
+     * the value plus the increment as an intent. A missing (or deleted) key is treated
+     * as 0. The read isn't added to the txn's read set since the intent already covers
+     * the key.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        let current_value = match read_latest_for_write(header, executor, &self.key)? {
+            Some(value) => match executor.value_codec.decode(&value) {
+                Ok(current_value) => current_value,
+                Err(_) => return Err(ResponseError::InvalidIncrementError),
+            },
+            None => 0,
+        };
+        let new_value = match current_value.checked_add(self.increment) {
+            Some(new_value) => new_value,
+            None => return Err(ResponseError::InvalidIncrementError),
+        };
+        let encoded_value = match executor.value_codec.encode(&new_value) {
+            Ok(encoded_value) => to_stored_value(&encoded_value),
+            Err(_) => return Err(ResponseError::InvalidIncrementError),
+        };
+        write_intent(header, executor, self.key.clone(), encoded_value).await?;
+        Ok(ResponseUnion::Increment(IncrementResponse { new_value }))
+    }
+}
+
+pub struct DeleteRequest {
+    pub key: Key,
+}
+
+pub struct DeleteResponse {}
+
+#[async_trait]
+impl Command for DeleteRequest {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn collect_spans(&self, _: TxnLink) -> SpanSet<Key> {
+        Vec::from([Range {
+            start_key: self.key.clone(),
+            end_key: self.key.clone(),
+        }])
+    }
+
+    /**
+     * Deleting is writing a tombstone intent. Once the txn commits,
+     * the intent resolves to a tombstone version.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        write_intent(header, executor, self.key.clone(), create_tombstone()).await?;
+        Ok(ResponseUnion::Delete(DeleteResponse {}))
+    }
+}
+
+pub struct DeleteRangeRequest {
+    pub start_key: Key,
+    // the end key is exclusive
+    pub end_key: Key,
+}
+
+pub struct DeleteRangeResponse {
+    // the keys that were deleted
+    pub keys: Vec<Key>,
+}
+
+#[async_trait]
+impl Command for DeleteRangeRequest {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn collect_spans(&self, _: TxnLink) -> SpanSet<Key> {
+        Vec::from([Range {
+            start_key: self.start_key.clone(),
+            end_key: self.end_key.clone(),
+        }])
+    }
+
+    /**
+     * Writes a tombstone intent for every key in the span that exists at the txn's
+     * write timestamp or was committed above it. The txn's write timestamp is bumped
+     * past the newest committed version in the span so that no tombstone is written
+     * beneath it. The whole span is latched, so no other request can write to the
+     * span while the keys are collected. If the span contains an intent of another
+     * txn, nothing is written and a WriteIntentError is returned.
+     *
+     * The span is added to the read set since the keys that were deleted depend on
+     * the keys that were found. If the txn's timestamp moves, the read refresh fails
+     * when a key was written into the span after the delete.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        let txn_id = header.txn.read().unwrap().txn_id;
+        let result = executor.store.mvcc_scan(
+            self.start_key.clone(),
+            self.end_key.clone(),
+            Timestamp::max_timestamp(),
+            MVCCScanParams {
+                max_result_count: usize::MAX,
+                transaction: Some(header.txn.clone()),
+                end_key_exclusive: true,
+                // a newer tombstone also bumps the write timestamp
+                tombstones: true,
+            },
+        );
+
+        for (intent, _) in result.intents.iter() {
+            if txn_id != intent.txn_meta.txn_id {
+                return Err(ResponseError::WriteIntentError(WriteIntentErrorData {
+                    intent: intent.clone(),
+                }));
+            }
+        }
+
+        // the txn's own intents were written at its write timestamp already
+        let newest_timestamp = result
+            .results
+            .iter()
+            .map(|(mvcc_key, _)| mvcc_key.timestamp)
+            .filter(|timestamp| !timestamp.is_intent_timestamp())
+            .max();
+        if let Some(newest_timestamp) = newest_timestamp {
+            header
+                .txn
+                .write()
+                .unwrap()
+                .bump_write_timestamp(newest_timestamp.next_logical_timestamp());
+        }
+
+        header.txn.read().unwrap().append_read_span(Range {
+            start_key: self.start_key.clone(),
+            end_key: self.end_key.clone(),
+        });
+        // the whole span is tracked so that the intents are resolved when the txn finalizes
+        header.txn.read().unwrap().append_lock_span(Range {
+            start_key: self.start_key.clone(),
+            end_key: self.end_key.clone(),
+        });
+        let mut keys = Vec::new();
+        for (mvcc_key, value) in result.results.into_iter() {
+            if is_tombstone(&value) {
+                continue;
+            }
+            executor.store.mvcc_put_tombstone(
+                mvcc_key.key.clone(),
+                None,
+                Some(header.txn.clone()),
+            )?;
+            acquire_lock(header, executor, mvcc_key.key.clone(), LockStrength::Intent).await?;
+            keys.push(mvcc_key.key);
+        }
+        Ok(ResponseUnion::DeleteRange(DeleteRangeResponse { keys }))
+    }
+}
+
+pub struct ScanRequest {
+    pub start_key: Key,
+    // the end key is exclusive
+    pub end_key: Key,
+    pub max_result_count: usize,
+}
+
+pub struct ScanResponse {
+    pub results: Vec<(MVCCKey, Value)>,
+}
+
+#[async_trait]
+impl Command for ScanRequest {
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    /**
+     * Spans are inclusive so the span also covers the exclusive end key.
+     * Latching and locking a slightly larger span is safe.
+     */
+    fn collect_spans(&self, _: TxnLink) -> SpanSet<Key> {
+        Vec::from([Range {
+            start_key: self.start_key.clone(),
+            end_key: self.end_key.clone(),
+        }])
+    }
+
+    /**
+     * If the request skips locked keys, the keys locked by other txns are omitted.
+     * The limit is applied after the locked keys are omitted.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        let (txn_id, read_timestamp, _) = Txn::get_txn_properties(header.txn.clone());
+        let skips_locked_keys = header.skips_locked_keys();
+        let result = executor.store.mvcc_scan(
+            self.start_key.clone(),
+            self.end_key.clone(),
+            read_timestamp,
+            MVCCScanParams {
+                max_result_count: if skips_locked_keys {
+                    usize::MAX
+                } else {
+                    self.max_result_count
+                },
+                transaction: Some(header.txn.clone()),
+                end_key_exclusive: true,
+                tombstones: false,
+            },
         );
 
-        match res {
-            Ok(_) => {
-                // add to lockTable
+        let mut intent_keys = Vec::new();
+        for (intent, _) in result.intents.iter() {
+            if txn_id == intent.txn_meta.txn_id {
+                continue;
+            }
+            if skips_locked_keys && !is_committed_intent(executor, intent) {
+                intent_keys.push(intent.key.clone());
+            } else if skips_locked_keys || intent.txn_meta.write_timestamp <= read_timestamp {
+                return Err(ResponseError::WriteIntentError(WriteIntentErrorData {
+                    intent: intent.clone(),
+                }));
+            }
+        }
+
+        let results = if skips_locked_keys {
+            result
+                .results
+                .into_iter()
+                .filter(|(mvcc_key, _)| {
+                    !intent_keys.contains(&mvcc_key.key)
+                        && !is_locked(executor, &mvcc_key.key, txn_id)
+                })
+                .take(self.max_result_count)
+                .collect()
+        } else {
+            result.results
+        };
+        let results = results
+            .into_iter()
+            .map(|(mvcc_key, value)| (mvcc_key, from_stored_value(value)))
+            .collect();
+
+        header.txn.read().unwrap().append_read_span(Range {
+            start_key: self.start_key.clone(),
+            end_key: self.end_key.clone(),
+        });
+        Ok(ResponseUnion::Scan(ScanResponse { results }))
+    }
+}
+
+/**
+ * A batch of commands that are sequenced together. The latches and locks for the
+ * union of the commands' spans are acquired once and the commands are evaluated
+ * in order. The batch fails on the first command that fails.
+ */
+pub struct BatchRequest {
+    pub requests: Vec<RequestUnion>,
+    /**
+     * Responses of the commands evaluated so far. If a command runs into a write intent,
+     * the whole batch is retried, and the commands that already succeeded must not
+     * be evaluated again (e.g. an increment would be applied twice).
+     */
+    responses: Mutex<Vec<ResponseUnion>>,
+}
+
+impl BatchRequest {
+    pub fn new(requests: Vec<RequestUnion>) -> Self {
+        BatchRequest {
+            requests,
+            responses: Mutex::new(Vec::new()),
+        }
+    }
+
+    /**
+     * Returns the key-value pairs written by the batch if the batch begins the txn,
+     * only writes (puts and deletes) and commits the txn. Returns None for batches
+     * of any other shape.
+     */
+    fn get_one_phase_commit_writes(&self) -> Option<Vec<(Key, Value)>> {
+        let (first, rest) = self.requests.split_first()?;
+        let (last, writes) = rest.split_last()?;
+        if !matches!(first, RequestUnion::BeginTxn(_))
+            || !matches!(last, RequestUnion::CommitTxn(_))
+        {
+            return None;
+        }
+        writes
+            .iter()
+            .map(|request| match request {
+                RequestUnion::Put(put) => Some((put.key.clone(), to_stored_value(&put.value))),
+                RequestUnion::Delete(delete) => Some((delete.key.clone(), create_tombstone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /**
+     * One-phase commit: the txn of the batch can't have read anything and its writes
+     * are latched, so the writes are committed at the txn's write timestamp directly.
+     * No txn record or intents are written. If a key has an intent of another txn,
+     * a write intent error is returned without writing anything.
+     */
+    fn execute_one_phase_commit(
+        &self,
+        header: &RequestMetadata,
+        executor: &Executor,
+        writes: Vec<(Key, Value)>,
+    ) -> ResponseResult {
+        let commit_timestamp = header.txn.read().unwrap().write_timestamp;
+        executor
+            .store
+            .mvcc_put_committed(&writes, commit_timestamp)?;
+        let responses = self
+            .requests
+            .iter()
+            .map(|request| match request {
+                RequestUnion::BeginTxn(_) => ResponseUnion::BeginTransaction(BeginTxnResponse {}),
+                RequestUnion::Put(_) => ResponseUnion::Put(PutResponse {}),
+                RequestUnion::Delete(_) => ResponseUnion::Delete(DeleteResponse {}),
+                RequestUnion::CommitTxn(_) => {
+                    ResponseUnion::CommitTxn(CommitTxnResponse { commit_timestamp })
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        Ok(ResponseUnion::Batch(BatchResponse { responses }))
+    }
+}
+
+pub struct BatchResponse {
+    pub responses: Vec<ResponseUnion>,
+}
+
+#[async_trait]
+impl Command for BatchRequest {
+    fn is_read_only(&self) -> bool {
+        self.requests.iter().all(|request| request.is_read_only())
+    }
+
+    /**
+     * The strongest lock of the commands that aren't read-only.
+     */
+    fn lock_strength(&self) -> LockStrength {
+        self.requests
+            .iter()
+            .filter(|request| !request.is_read_only())
+            .map(|request| request.lock_strength())
+            .max()
+            .unwrap_or(LockStrength::Intent)
+    }
+
+    fn collect_spans(&self, txn_link: TxnLink) -> SpanSet<Key> {
+        let mut spans = self
+            .requests
+            .iter()
+            .flat_map(|request| request.collect_spans(txn_link.clone()))
+            .collect::<SpanSet<Key>>();
+        dedupe_spanset(&mut spans);
+        spans
+    }
+
+    /**
+     * A batch that begins the txn, only writes and commits the txn is committed in
+     * one phase.
+     *
+     * If the batch is a write batch, the executor won't update the timestamp oracle
+     * so the read-only commands update it themselves.
+     */
+    async fn execute(&self, header: &RequestMetadata, executor: &Executor) -> ResponseResult {
+        if let Some(writes) = self.get_one_phase_commit_writes() {
+            // a txn with a record began before the batch and could have other writes
+            let txn_id = header.txn.read().unwrap().txn_id;
+            if executor.store.get_transaction_record(txn_id).is_none() {
+                return self.execute_one_phase_commit(header, executor, writes);
+            }
+        }
+        let is_read_only = self.is_read_only();
+        let evaluated_count = self.responses.lock().unwrap().len();
+        for request in self.requests.iter().skip(evaluated_count) {
+            let response = request.execute(header, executor).await?;
+            if !is_read_only && request.is_read_only() {
+                let spans = request.collect_spans(header.txn.clone());
                 executor
-                    .concr_manager
-                    .lock_table
-                    .acquire_lock(self.key.clone(), header.txn.clone())
-                    .await;
-                // update the txn's lock spans to account for the intent being written
-                header
-                    .txn
+                    .timestamp_oracle
                     .read()
                     .unwrap()
-                    .append_lock_span(self.key.clone());
-                println!(
-                    "finished executing write for txn {} with key {:?}",
-                    header.txn.read().unwrap().txn_id,
-                    self.key.clone()
-                );
-                Ok(ResponseUnion::Put(PutResponse {}))
+                    .update_cache_for_spans(header.txn.clone(), &spans);
             }
-            Err(err) => Err(ResponseError::WriteIntentError(WriteIntentErrorData {
-                intent: err.intent,
-            })),
+            self.responses.lock().unwrap().push(response);
         }
+        let responses = self.responses.lock().unwrap().drain(..).collect();
+        Ok(ResponseUnion::Batch(BatchResponse { responses }))
     }
 }
 
@@ -426,8 +1185,15 @@ impl RequestUnion {
             RequestUnion::BeginTxn(_) => "begin txn",
             RequestUnion::CommitTxn(_) => "commit txn",
             RequestUnion::Get(_) => "get",
+            RequestUnion::GetForUpdate(_) => "get for update",
             RequestUnion::Put(_) => "put",
             RequestUnion::AbortTxn(_) => "abort",
+            RequestUnion::Scan(_) => "scan",
+            RequestUnion::Delete(_) => "delete",
+            RequestUnion::DeleteRange(_) => "delete range",
+            RequestUnion::ConditionalPut(_) => "conditional put",
+            RequestUnion::Increment(_) => "increment",
+            RequestUnion::Batch(_) => "batch",
         }
     }
 }
@@ -439,8 +1205,15 @@ impl Command for RequestUnion {
             RequestUnion::BeginTxn(command) => command.is_read_only(),
             RequestUnion::CommitTxn(command) => command.is_read_only(),
             RequestUnion::Get(command) => command.is_read_only(),
+            RequestUnion::GetForUpdate(command) => command.is_read_only(),
             RequestUnion::Put(command) => command.is_read_only(),
             RequestUnion::AbortTxn(command) => command.is_read_only(),
+            RequestUnion::Scan(command) => command.is_read_only(),
+            RequestUnion::Delete(command) => command.is_read_only(),
+            RequestUnion::DeleteRange(command) => command.is_read_only(),
+            RequestUnion::ConditionalPut(command) => command.is_read_only(),
+            RequestUnion::Increment(command) => command.is_read_only(),
+            RequestUnion::Batch(command) => command.is_read_only(),
         }
     }
 
@@ -449,8 +1222,32 @@ impl Command for RequestUnion {
             RequestUnion::BeginTxn(command) => command.collect_spans(txn_link),
             RequestUnion::CommitTxn(command) => command.collect_spans(txn_link),
             RequestUnion::Get(command) => command.collect_spans(txn_link),
+            RequestUnion::GetForUpdate(command) => command.collect_spans(txn_link),
             RequestUnion::Put(command) => command.collect_spans(txn_link),
             RequestUnion::AbortTxn(command) => command.collect_spans(txn_link),
+            RequestUnion::Scan(command) => command.collect_spans(txn_link),
+            RequestUnion::Delete(command) => command.collect_spans(txn_link),
+            RequestUnion::DeleteRange(command) => command.collect_spans(txn_link),
+            RequestUnion::ConditionalPut(command) => command.collect_spans(txn_link),
+            RequestUnion::Increment(command) => command.collect_spans(txn_link),
+            RequestUnion::Batch(command) => command.collect_spans(txn_link),
+        }
+    }
+
+    fn lock_strength(&self) -> LockStrength {
+        match self {
+            RequestUnion::BeginTxn(command) => command.lock_strength(),
+            RequestUnion::CommitTxn(command) => command.lock_strength(),
+            RequestUnion::Get(command) => command.lock_strength(),
+            RequestUnion::GetForUpdate(command) => command.lock_strength(),
+            RequestUnion::Put(command) => command.lock_strength(),
+            RequestUnion::AbortTxn(command) => command.lock_strength(),
+            RequestUnion::Scan(command) => command.lock_strength(),
+            RequestUnion::Delete(command) => command.lock_strength(),
+            RequestUnion::DeleteRange(command) => command.lock_strength(),
+            RequestUnion::ConditionalPut(command) => command.lock_strength(),
+            RequestUnion::Increment(command) => command.lock_strength(),
+            RequestUnion::Batch(command) => command.lock_strength(),
         }
     }
 
@@ -460,7 +1257,14 @@ impl Command for RequestUnion {
             RequestUnion::CommitTxn(command) => command.execute(header, executor).await,
             RequestUnion::AbortTxn(command) => command.execute(header, executor).await,
             RequestUnion::Get(command) => command.execute(header, executor).await,
+            RequestUnion::GetForUpdate(command) => command.execute(header, executor).await,
             RequestUnion::Put(command) => command.execute(header, executor).await,
+            RequestUnion::Scan(command) => command.execute(header, executor).await,
+            RequestUnion::Delete(command) => command.execute(header, executor).await,
+            RequestUnion::DeleteRange(command) => command.execute(header, executor).await,
+            RequestUnion::ConditionalPut(command) => command.execute(header, executor).await,
+            RequestUnion::Increment(command) => command.execute(header, executor).await,
+            RequestUnion::Batch(command) => command.execute(header, executor).await,
         }
     }
 }
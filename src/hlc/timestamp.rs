@@ -84,6 +84,16 @@ impl Timestamp {
     pub fn intent_timestamp() -> Self {
         get_intent_timestamp()
     }
+
+    /**
+     * The highest timestamp. Reading at it returns the latest version of each key.
+     */
+    pub fn max_timestamp() -> Self {
+        Timestamp {
+            wall_time: u64::MAX,
+            logical_time: u32::MAX,
+        }
+    }
 }
 
 /**
@@ -507,9 +507,12 @@ impl<K: NodeKey> LeafNode<K> {
         if let Some(found_idx) = index {
             println!("Key: {:?}", &range.start_key);
             let waiters = self.waiters.write().unwrap();
-            let mut waiter = waiters[found_idx].write().unwrap();
             let (tx, rx) = mpsc::channel::<()>(1);
-            waiter.senders.push(Mutex::new(tx));
+            // waiters aren't tracked for every key. Without a waiter, the receiver is
+            // closed and the caller falls back to its timeout
+            if let Some(waiter) = waiters.get(found_idx) {
+                waiter.write().unwrap().senders.push(Mutex::new(tx));
+            }
             return LatchKeyGuard::NotAcquired(LatchGuardWait { receiver: rx });
         }
 
@@ -1128,4 +1131,54 @@ impl<K: NodeKey> BTree<K> {
             Node::Leaf(_) => {}
         }
     }
+
+    /**
+     * Returns the first latched range that overlaps with the provided range.
+     * Start and end keys are both inclusive.
+     *
+     * Keys are only ordered by their start key, so every leaf whose start keys are
+     * not greater than the range's end key needs to be visited.
+     */
+    pub fn find_overlap(&self, range: &Range<K>) -> Option<Range<K>> {
+        let root_node = self.root.read().unwrap().clone().unwrap();
+        self.find_overlap_helper(root_node, range)
+    }
+
+    fn find_overlap_helper(&self, node: LatchNode<K>, range: &Range<K>) -> Option<Range<K>> {
+        let read_guard = node.read().unwrap();
+        match &*read_guard {
+            Node::Internal(internal_node) => {
+                let keys = internal_node.keys.read().unwrap();
+                let edges = internal_node.edges.read().unwrap();
+                for (idx, edge) in edges.iter().enumerate() {
+                    let child = edge.read().unwrap().clone();
+                    if let Some(child) = child {
+                        let overlap = self.find_overlap_helper(child, range);
+                        if overlap.is_some() {
+                            return overlap;
+                        }
+                    }
+                    // the next edge only contains start keys >= keys[idx]
+                    if idx < keys.len() && keys[idx] > range.end_key {
+                        break;
+                    }
+                }
+                None
+            }
+            Node::Leaf(leaf_node) => {
+                let start_keys = leaf_node.start_keys.read().unwrap();
+                let end_keys = leaf_node.end_keys.read().unwrap();
+                start_keys
+                    .iter()
+                    .zip(end_keys.iter())
+                    .find(|(start_key, end_key)| {
+                        *start_key <= &range.end_key && &range.start_key <= *end_key
+                    })
+                    .map(|(start_key, end_key)| Range {
+                        start_key: start_key.clone(),
+                        end_key: end_key.clone(),
+                    })
+            }
+        }
+    }
 }
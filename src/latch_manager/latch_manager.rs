@@ -1,12 +1,28 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
 use rand::Rng;
-use tokio::time::{self, Duration};
+use tokio::{
+    sync::mpsc::{self, Sender},
+    time::{self, Duration},
+};
 
 use crate::execute::request::SpanSet;
 
-use super::latch_interval_btree::{BTree, LatchKeyGuard, NodeKey, Range};
+use super::latch_interval_btree::{BTree, LatchGuardWait, LatchKeyGuard, NodeKey, Range};
 
 pub struct LatchManager<K: NodeKey> {
     tree: BTree<K>,
+    /**
+     * The tree only detects conflicts between latches with the same start key.
+     * The mutex makes checking for overlapping ranges and inserting the range
+     * a single atomic step.
+     */
+    span_mutex: Mutex<()>,
+    /**
+     * The requests waiting for a latch to be released, keyed by the start key
+     * of the latch. Only accessed while the span_mutex is held.
+     */
+    waiters: Mutex<BTreeMap<K, Vec<Sender<()>>>>,
 }
 
 pub struct LatchGuard<K: NodeKey> {
@@ -16,10 +32,17 @@ pub struct LatchGuard<K: NodeKey> {
 impl<K: NodeKey> LatchManager<K> {
     pub fn new() -> Self {
         let tree = BTree::new(3);
-        LatchManager { tree }
+        LatchManager {
+            tree,
+            span_mutex: Mutex::new(()),
+            waiters: Mutex::new(BTreeMap::new()),
+        }
     }
 
-    // We currently don't support key-range locks. We only support single point locks
+    /**
+     * Spans within the same request must not overlap with each other
+     * since overlapping spans would conflict with each other.
+     */
     pub async fn acquire(&self, spans: SpanSet<K>) -> LatchGuard<K> {
         // create a timer and repeat until success
         // loop through the spans, add them, wait until it's released
@@ -29,10 +52,7 @@ impl<K: NodeKey> LatchManager<K> {
             'outer: for (idx, span) in spans.iter().enumerate() {
                 let mut did_acquire = false;
                 loop {
-                    let lg = self.tree.insert(Range {
-                        start_key: span.start_key.clone(),
-                        end_key: span.end_key.clone(),
-                    });
+                    let lg = self.insert_span(span);
 
                     let duration: u64 = rand::thread_rng().gen_range(50..150);
                     let sleep = time::sleep(Duration::from_millis(duration));
@@ -64,7 +84,7 @@ impl<K: NodeKey> LatchManager<K> {
                     // TODO: what if we keep running into deadlocks?
                     for acquired_idx in acquired_indices.iter() {
                         let span = spans.get(*acquired_idx).unwrap();
-                        self.tree.delete(span.start_key.clone())
+                        self.delete_span(span);
                     }
                     break 'outer;
                 }
@@ -77,7 +97,40 @@ impl<K: NodeKey> LatchManager<K> {
 
     pub fn release(&self, guard: LatchGuard<K>) -> () {
         for span in guard.spans.iter() {
-            self.tree.delete(span.start_key.clone())
+            self.delete_span(span);
+        }
+    }
+
+    /**
+     * Tries to latch the span. If a latch overlaps with the span, the span is
+     * not acquired and the returned receiver is notified once the overlapping
+     * latch is released.
+     */
+    fn insert_span(&self, span: &Range<K>) -> LatchKeyGuard {
+        let _span_guard = self.span_mutex.lock().unwrap();
+        if let Some(overlap) = self.tree.find_overlap(span) {
+            let (sender, receiver) = mpsc::channel(1);
+            self.waiters
+                .lock()
+                .unwrap()
+                .entry(overlap.start_key)
+                .or_default()
+                .push(sender);
+            return LatchKeyGuard::NotAcquired(LatchGuardWait { receiver });
+        }
+        self.tree.insert(Range {
+            start_key: span.start_key.clone(),
+            end_key: span.end_key.clone(),
+        })
+    }
+
+    fn delete_span(&self, span: &Range<K>) {
+        let _span_guard = self.span_mutex.lock().unwrap();
+        self.tree.delete(span.start_key.clone());
+        let waiters = self.waiters.lock().unwrap().remove(&span.start_key);
+        // waiters that timed out dropped their receivers already
+        for sender in waiters.unwrap_or_default() {
+            let _ = sender.try_send(());
         }
     }
 }
@@ -89,7 +142,10 @@ mod test {
 
         use tokio::time::sleep;
 
-        use crate::latch_manager::{latch_interval_btree::Range, latch_manager::LatchManager};
+        use crate::latch_manager::{
+            latch_interval_btree::{LatchKeyGuard, Range},
+            latch_manager::LatchManager,
+        };
 
         #[tokio::test]
         async fn test_select() {
@@ -128,5 +184,66 @@ mod test {
             ]))
             .await;
         }
+
+        #[tokio::test]
+        async fn overlapping_ranges_conflict() {
+            let lm = Arc::new(LatchManager::<i32>::new());
+            let guard = lm
+                .acquire(Vec::from([Range {
+                    start_key: 10,
+                    end_key: 20,
+                }]))
+                .await;
+            assert!(lm
+                .tree
+                .find_overlap(&Range {
+                    start_key: 15,
+                    end_key: 15,
+                })
+                .is_some());
+            assert!(lm
+                .tree
+                .find_overlap(&Range {
+                    start_key: 21,
+                    end_key: 30,
+                })
+                .is_none());
+
+            let lm2 = Arc::clone(&lm);
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(100)).await;
+                lm2.release(guard)
+            });
+
+            lm.acquire(Vec::from([Range {
+                start_key: 15,
+                end_key: 15,
+            }]))
+            .await;
+        }
+
+        #[tokio::test]
+        async fn release_wakes_waiter_of_overlapping_range() {
+            let lm = LatchManager::<i32>::new();
+            lm.acquire(Vec::from([Range {
+                start_key: 10,
+                end_key: 20,
+            }]))
+            .await;
+
+            let span = Range {
+                start_key: 15,
+                end_key: 15,
+            };
+            let LatchKeyGuard::NotAcquired(mut wait_guard) = lm.insert_span(&span) else {
+                panic!("the overlapping span shouldn't be acquired");
+            };
+            lm.delete_span(&Range {
+                start_key: 10,
+                end_key: 20,
+            });
+            assert_eq!(wait_guard.receiver.try_recv(), Ok(()));
+            assert!(matches!(lm.insert_span(&span), LatchKeyGuard::Acquired));
+        }
     }
 }
@@ -20,12 +20,14 @@ use crate::{
     },
     execute::request::{Command, Request, SpanSet},
     hlc::timestamp::Timestamp,
+    latch_manager::latch_interval_btree::Range,
     storage::{
         mvcc::KVStore,
         txn::{Txn, TxnIntent, TxnMetadata},
         Key,
     },
     txn_wait::txn_wait_queue::{TxnWaitQueue, WaitForPushError},
+    StorageResult,
 };
 
 pub struct LockTableGuard {
@@ -48,10 +50,69 @@ pub struct LockTableGuard {
 
     pub wait_state: RwLock<WaitingState>,
 
+    /**
+     * The strength of the locks that the request acquires. Ignored for read-only requests
+     */
+    pub strength: LockStrength,
+
     /**
      * The keys that this request affects
      */
     pub keys: SpanSet<Key>,
+
+    /**
+     * The key of the lock the request is waiting at and the txn it conflicts with.
+     */
+    pub conflict: RwLock<Option<(Key, Uuid)>>,
+}
+
+/**
+ * How a request handles conflicting locks.
+ */
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum WaitPolicy {
+    /**
+     * Waits for the conflicting locks to be released. If a timeout is set, the request fails
+     * with a lock conflict once it has waited that long. Without a timeout, the request pushes
+     * the lock holders to detect deadlocks.
+     */
+    Block { timeout: Option<Duration> },
+    /**
+     * Fails immediately with a lock conflict.
+     */
+    Error,
+    /**
+     * Skips the keys locked by other txns. Only reads and locking reads can skip keys,
+     * other requests fail like with Error.
+     */
+    SkipLocked,
+}
+
+impl Default for WaitPolicy {
+    fn default() -> Self {
+        WaitPolicy::Block { timeout: None }
+    }
+}
+
+/**
+ * The strength of a lock, ordered from the weakest to the strongest.
+ */
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
+pub enum LockStrength {
+    /**
+     * Acquired by shared locking reads. Multiple txns can hold a shared lock
+     * on the same key. It only conflicts with exclusive locks and intents.
+     */
+    Shared,
+    /**
+     * Acquired by exclusive locking reads. The lock isn't backed by an intent
+     * so non-locking reads don't wait for it.
+     */
+    Exclusive,
+    /**
+     * Acquired by writing an intent.
+     */
+    Intent,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
@@ -82,17 +143,36 @@ pub type LockTableGuardLink = Arc<LockTableGuard>;
 pub enum WaitForGuardError {
     TxnAborted,
     TxnCommitted,
+    // the request waited longer than its lock timeout
+    LockTimeout { key: Key, holder_txn: Uuid },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AcquireLockError {
+    // the lock is held or reserved by holder_txn
+    LockConflict { key: Key, holder_txn: Uuid },
 }
 
 /**
  * Invariants:
  * - Reservation and lock_holder cannot both exist at the same time (both not None)
+ * - The lock_holder and the shared_holders don't belong to different transactions
  */
 pub struct LockState {
     pub reservation: Arc<RwLock<Option<LockTableGuardLink>>>,
 
     pub lock_holder: Arc<RwLock<Option<TxnMetadata>>>,
 
+    /**
+     * The strength the lock_holder holds the lock at. Either Exclusive or Intent.
+     */
+    pub lock_strength: RwLock<LockStrength>,
+
+    /**
+     * The transactions holding a shared lock on the key.
+     */
+    pub shared_holders: Arc<RwLock<Vec<TxnMetadata>>>,
+
     pub queued_writers: Arc<RwLock<Vec<LockTableGuardLink>>>,
 
     pub waiting_readers: Arc<RwLock<Vec<LockTableGuardLink>>>,
@@ -162,7 +242,8 @@ impl LockTable {
 
         let mut holder = state.lock_holder.write().unwrap();
         if holder.is_none() {
-            *holder = Some(intent.txn_meta)
+            *holder = Some(intent.txn_meta);
+            *state.lock_strength.write().unwrap() = LockStrength::Intent;
         } else {
             // TODO: What should we do if there's already a holder?
         }
@@ -184,11 +265,54 @@ impl LockTable {
         }
     }
 
+    /**
+     * Pushes the timestamp of the txn holding the lock on the key above the reader's
+     * read timestamp so that the reader doesn't need to wait for the lock.
+     */
+    pub fn push_holder_timestamp(
+        &self,
+        key: &Key,
+        holder_txn_id: Uuid,
+        timestamp: Timestamp,
+    ) -> StorageResult<bool> {
+        if !self
+            .txn_wait_queue
+            .push_timestamp(holder_txn_id, key, timestamp)?
+        {
+            return Ok(false);
+        }
+        if let Some(lock_state) = self.get_lock_state(key) {
+            lock_state.push_holder_timestamp(holder_txn_id, timestamp);
+        }
+        Ok(true)
+    }
+
     pub fn get_lock_state(&self, key: &Key) -> Option<LockStateLink> {
         let locks = self.locks.read().unwrap();
         locks.get(key).and_then(|v| Some(v.clone()))
     }
 
+    /**
+     * Returns the lock states of the keys within the span sorted by key.
+     * Start and end keys are both inclusive.
+     */
+    pub fn get_lock_states_in_span(&self, span: &Range<Key>) -> Vec<(Key, LockStateLink)> {
+        if span.start_key == span.end_key {
+            return self
+                .get_lock_state(&span.start_key)
+                .map(|state| Vec::from([(span.start_key.clone(), state)]))
+                .unwrap_or_default();
+        }
+        let locks = self.locks.read().unwrap();
+        let mut states = locks
+            .iter()
+            .filter(|(key, _)| span.start_key <= **key && **key <= span.end_key)
+            .map(|(key, state)| (key.clone(), state.clone()))
+            .collect::<Vec<(Key, LockStateLink)>>();
+        states.sort_by(|(a, _), (b, _)| a.cmp(b));
+        states
+    }
+
     /**
      * scans the lockTable for conflicting keys and wait if it finds one.
      * Once it finds a lock that it needs to wait at, it adds itself to the queue
@@ -203,20 +327,30 @@ impl LockTable {
             .request_union
             .collect_spans(request.metadata.txn.clone());
         let is_read_only = request.request_union.is_read_only();
+        let strength = request.request_union.lock_strength();
         let txn = request.metadata.txn.clone();
 
         let txn_id = txn.read().unwrap().txn_id;
 
-        let lock_guard =
-            LockTableGuard::new_lock_table_guard_link(txn.clone(), is_read_only, spans.clone());
+        let lock_guard = LockTableGuard::new_lock_table_guard_link(
+            txn.clone(),
+            is_read_only,
+            strength,
+            spans.clone(),
+        );
         println!(
             "created lock guard {} for request type: {}",
             lock_guard.guard_id,
             request.request_union.get_type_string()
         );
+        // the locked keys are skipped during evaluation instead
+        let skips_locked_keys = request.metadata.wait_policy == WaitPolicy::SkipLocked
+            && (is_read_only || strength != LockStrength::Intent);
+        if skips_locked_keys {
+            return (false, lock_guard);
+        }
         for span in spans.iter() {
-            let lock = self.get_lock_state(&span.start_key);
-            if let Some(write_lock_state) = lock {
+            for (key, write_lock_state) in self.get_lock_states_in_span(span) {
                 let should_wait = write_lock_state
                     .try_active_wait(lock_guard.clone(), is_read_only)
                     .await;
@@ -228,6 +362,9 @@ impl LockTable {
                         txn_id
                     );
                     *lock_guard.as_ref().should_wait.write().unwrap() = true;
+                    *lock_guard.conflict.write().unwrap() = write_lock_state
+                        .get_conflicting_txn_id(txn_id)
+                        .map(|holder_txn| (key, holder_txn));
                     write_lock_state.print();
                     return (true, lock_guard);
                 } else {
@@ -249,12 +386,37 @@ impl LockTable {
      * when the request is at the front of all wait-queues and it's safe to
      * re-acquire latches and rescan the lockTable.
      *
-     * It's also responsible for pushing transaction if it times out
+     * It's also responsible for pushing transaction if it times out. If the request
+     * has a lock timeout, it doesn't push and fails once the timeout elapses instead.
+     * Holders of a lower priority class than the request are aborted right away, without
+     * waiting.
      */
-    pub async fn wait_for(&self, guard: LockTableGuardLink) -> Result<(), WaitForGuardError> {
+    pub async fn wait_for(
+        &self,
+        guard: LockTableGuardLink,
+        lock_timeout: Option<Duration>,
+    ) -> Result<(), WaitForGuardError> {
+        if self.has_lower_class_holder(&guard) {
+            // the request rescans the lockTable once the holders are aborted
+            return self.push_lock_holders(&guard, true).await;
+        }
+
         let lg = guard.as_ref();
         let mut rx = lg.wait_done_receiver.lock().await;
 
+        if let Some(lock_timeout) = lock_timeout {
+            if time::timeout(lock_timeout, rx.recv()).await.is_ok() {
+                return Ok(());
+            }
+            drop(rx);
+            self.dequeue(guard.clone()).await;
+            // without a conflicting lock, the request rescans the lockTable instead
+            return match lg.get_conflict() {
+                Some((key, holder_txn)) => Err(WaitForGuardError::LockTimeout { key, holder_txn }),
+                None => Ok(()),
+            };
+        }
+
         // TODO: Random
         let _duration: u64 = rand::thread_rng().gen_range(500..1000);
         let sleep = time::sleep(Duration::from_millis(1000));
@@ -267,41 +429,82 @@ impl LockTable {
             }
             _ = &mut sleep, if !sleep.is_elapsed() => {
                 println!("operation timed out");
-                let keys = &guard.keys;
-                for key in keys.iter() {
-                    let key = &key.start_key;
-                    let lock_state = self.get_lock_state(&key).unwrap();
-                    let holder_txn_id = lock_state.get_holder_txn_id();
-                    if let Some(pushee_txn_id) = holder_txn_id {
-                        let pusher_txn_id = guard.txn.read().unwrap().txn_id;
-                        let wait_res = self.txn_wait_queue
-                            .wait_for_push(pusher_txn_id, pushee_txn_id)
-                            .await;
-                        match wait_res {
-                            Ok(_) => {
-                                println!("Wait for push succeeded for pusher: {} and pushee: {}", pusher_txn_id, pushee_txn_id);
-                            },
-                            Err(err) => {
-                                // TODO: We need to clean up other lock_state too.
-                                // We should probably have a vec of lock_state collected so far
-                                println!("wait for push failed");
-                                lock_state.request_done(guard.clone()).await;
-
-                                match err {
-                                    WaitForPushError::TxnAborted => {
-                                        return Err(WaitForGuardError::TxnAborted);
-                                    },
-                                    WaitForPushError::TxnCommitted => {
-                                        return Err(WaitForGuardError::TxnCommitted);
-                                    },
-                                }
-                            },
+                return self.push_lock_holders(&guard, false).await;
+            }
+        };
+    }
+
+    /**
+     * Returns the holders of the locks in the guard's spans other than the guard's txn,
+     * along with the lock state they hold.
+     */
+    fn get_pushees(&self, guard: &LockTableGuardLink) -> Vec<(LockStateLink, TxnMetadata)> {
+        let pusher_txn_id = LockTableGuard::get_txn_meta(guard).txn_id;
+        guard
+            .keys
+            .iter()
+            .flat_map(|span| self.get_lock_states_in_span(span))
+            .flat_map(|(_, lock_state)| {
+                lock_state
+                    .get_holders()
+                    .into_iter()
+                    .filter(|holder| holder.txn_id != pusher_txn_id)
+                    .map(|holder| (lock_state.clone(), holder))
+                    .collect::<Vec<(LockStateLink, TxnMetadata)>>()
+            })
+            .collect()
+    }
+
+    fn has_lower_class_holder(&self, guard: &LockTableGuardLink) -> bool {
+        let pusher = LockTableGuard::get_txn_meta(guard);
+        self.get_pushees(guard)
+            .iter()
+            .any(|(_, pushee)| pusher.priority.is_higher_class_than(pushee.priority))
+    }
+
+    /**
+     * Pushes the holders of the locks the guard waits for until they finalize. If
+     * only_lower_class is set, only the holders of a lower priority class than the
+     * guard's txn are pushed, which aborts them right away.
+     */
+    async fn push_lock_holders(
+        &self,
+        guard: &LockTableGuardLink,
+        only_lower_class: bool,
+    ) -> Result<(), WaitForGuardError> {
+        let pusher = LockTableGuard::get_txn_meta(guard);
+        let pusher_txn_id = pusher.txn_id;
+        for (lock_state, pushee) in self.get_pushees(guard) {
+            if only_lower_class && !pusher.priority.is_higher_class_than(pushee.priority) {
+                continue;
+            }
+            let pushee_txn_id = pushee.txn_id;
+            let wait_res = self.txn_wait_queue.wait_for_push(pusher, pushee).await;
+            match wait_res {
+                Ok(_) => {
+                    println!(
+                        "Wait for push succeeded for pusher: {} and pushee: {}",
+                        pusher_txn_id, pushee_txn_id
+                    );
+                }
+                Err(err) => {
+                    // TODO: We need to clean up other lock_state too.
+                    // We should probably have a vec of lock_state collected so far
+                    println!("wait for push failed");
+                    lock_state.request_done(guard.clone()).await;
+
+                    match err {
+                        WaitForPushError::TxnAborted => {
+                            return Err(WaitForGuardError::TxnAborted);
+                        }
+                        WaitForPushError::TxnCommitted => {
+                            return Err(WaitForGuardError::TxnCommitted);
                         }
                     }
                 }
-                return Ok(())
             }
-        };
+        }
+        Ok(())
     }
 
     /**
@@ -311,9 +514,8 @@ impl LockTable {
      */
     pub async fn dequeue(&self, lock_table_guard: LockTableGuardLink) {
         let keys = &lock_table_guard.as_ref().keys;
-        for key in keys.iter() {
-            let state = self.get_lock_state(&key.start_key);
-            if let Some(lock_state) = state {
+        for span in keys.iter() {
+            for (_, lock_state) in self.get_lock_states_in_span(span) {
                 lock_state.request_done(lock_table_guard.clone()).await
             }
         }
@@ -324,48 +526,67 @@ impl LockTable {
      * Latches are held when this method is called.
      *
      * Informs the lockTable that that a new lock was acquired. This is called after a write
-     * intent is written. Either the lockTable doesn't have an entry for the key, otherwise
-     * the txn must have reserved.
+     * intent is written or a locking read is evaluated. Either the lockTable doesn't have an
+     * entry for the key, otherwise the txn must have reserved or the lock must be compatible.
      *
      * This method must be called during evaluation phase before Dequeue is called, which
      * means all the latches are held.
+     *
+     * Returns a lock conflict if the lock is held, reserved or shared by another txn
+     * in a way that's incompatible with the requested strength.
      */
-    pub async fn acquire_lock(&self, key: Key, txn: TxnLink) {
+    pub async fn acquire_lock(
+        &self,
+        key: Key,
+        txn: TxnLink,
+        strength: LockStrength,
+    ) -> Result<(), AcquireLockError> {
         let lock_state = self.get_lock_state(&key);
-        let (txn_id, _, write_timestamp) = Txn::get_txn_properties(txn.clone());
+        let txn_meta = txn.read().unwrap().to_txn_metadata();
+        let txn_id = txn_meta.txn_id;
         match lock_state {
             Some(state) => {
-                if let Some(holder_txn_id) = state.get_holder_txn_id() {
-                    if holder_txn_id != txn_id {
-                        panic!("trying to acquire lock held by another transaction")
-                    }
-                }
-
-                if let Some(reservation_txn_id) = state.get_reservation_txn_id() {
-                    if reservation_txn_id != txn_id {
-                        panic!("trying to acquire lock reserved by another transaction")
-                    }
+                // shared locks are compatible with the shared locks of other txns
+                let conflicting_txn_id = if strength == LockStrength::Shared {
+                    state
+                        .get_holder_txn_id()
+                        .into_iter()
+                        .chain(state.get_reservation_txn_id())
+                        .find(|holder_txn_id| *holder_txn_id != txn_id)
+                } else {
+                    state.get_conflicting_txn_id(txn_id)
+                };
+                if let Some(holder_txn) = conflicting_txn_id {
+                    return Err(AcquireLockError::LockConflict { key, holder_txn });
                 }
 
                 state.update_reservation(None);
-                state.update_holder(Some(TxnMetadata {
-                    txn_id,
-                    write_timestamp,
-                }));
+                state.add_holder(txn_meta, strength);
             }
             None => {
                 // TODO: We should just pass the holder in as a constructor parameter
                 let lock_state = LockState::new(self.txn_map.clone());
-                lock_state.update_holder(Some(TxnMetadata {
-                    txn_id,
-                    write_timestamp,
-                }));
+                lock_state.add_holder(txn_meta, strength);
                 self.locks
                     .write()
                     .unwrap()
                     .insert(key.clone(), Arc::new(lock_state));
             }
         }
+        Ok(())
+    }
+
+    /**
+     * Returns whether another txn holds or reserved a lock on the key that conflicts
+     * with a lock of the strength.
+     */
+    pub fn is_locked_by_other_txn(&self, key: &Key, txn_id: Uuid, strength: LockStrength) -> bool {
+        self.get_lock_state(key).is_some_and(|state| {
+            state.conflicts_with_holders(txn_id, strength)
+                || state
+                    .get_reservation_txn_id()
+                    .is_some_and(|reservation_txn_id| reservation_txn_id != txn_id)
+        })
     }
 
     fn insert_lock_state(&self, key: Key, lock_state: LockStateLink) {
@@ -377,8 +598,8 @@ impl LockTable {
      * This function tells the lockTable that an existing lock was updated/released (e.g. the transaction
      * has committed / aborted).
      *
-     * This function sets the lock_holder as null and calls is_lock_free to free the readers / next writer or start
-     * garbage collection.
+     * This function releases the txn's lock (the lock_holder or a shared lock) and calls is_lock_free to free
+     * the readers / next writer or start garbage collection once no transaction holds the lock.
      *
      * Returns whether the lock can be garbage collected.
      */
@@ -394,9 +615,6 @@ impl LockTable {
         let holder_option = lock_state.get_holder_txn_meta();
         let reservation = lock_state.reservation.read().unwrap().clone();
 
-        if reservation.is_none() && holder_option.is_none() {
-            return true;
-        }
         let txn_id = match &update_lock {
             UpdateLock::Commit(commit) => commit.txn_id,
             UpdateLock::Abort(abort) => {
@@ -404,6 +622,11 @@ impl LockTable {
                 abort.txn_id
             }
         };
+        let released_shared_lock = lock_state.remove_shared_holder(txn_id);
+
+        if reservation.is_none() && holder_option.is_none() && !released_shared_lock {
+            return !lock_state.has_shared_holders();
+        }
 
         if let Some(holder) = holder_option {
             if holder.txn_id != txn_id {
@@ -418,12 +641,20 @@ impl LockTable {
                 "Holder to set to none's txn: {}. key: {:?}",
                 holder.txn_id, key
             );
+
+            // only an intent leaves a committed value behind
+            if let UpdateLock::Commit(ref commit) = update_lock {
+                if lock_state.get_lock_strength() == LockStrength::Intent {
+                    lock_state.update_last_commit_timestamp(commit.commit_timestamp);
+                }
+            }
         }
         // somehow there's holder but not reservation
         lock_state.update_holder(None);
 
-        if let UpdateLock::Commit(ref commit) = update_lock {
-            lock_state.update_last_commit_timestamp(commit.commit_timestamp);
+        // the lock is still held by other shared holders, or a request has reserved it
+        if lock_state.has_shared_holders() || reservation.is_some() {
+            return false;
         }
         println!(
             "Calling lock is free to update locks for txn: {}. Reason: {}",
@@ -456,6 +687,8 @@ impl LockState {
         LockState {
             reservation: Arc::new(RwLock::new(None)),
             lock_holder: Arc::new(RwLock::new(None)),
+            lock_strength: RwLock::new(LockStrength::Intent),
+            shared_holders: Arc::new(RwLock::new(Vec::new())),
             queued_writers: Arc::new(RwLock::new(Vec::new())),
             waiting_readers: Arc::new(RwLock::new(Vec::new())),
             last_committed_timestamp: RwLock::new(None),
@@ -506,6 +739,16 @@ impl LockState {
         }
         self.clear_readers();
 
+        // Shared requests at the front of the queue are compatible with each other and with
+        // the shared holders, so they proceed together without reserving the lock.
+        let shared_writers = self.remove_leading_shared_writers();
+        for writer in shared_writers.iter() {
+            writer.done_waiting_at_lock().await;
+        }
+        if !shared_writers.is_empty() || self.has_shared_holders() {
+            return false;
+        }
+
         let first_writer = self.remove_first_writer();
         match first_writer {
             Some(writer) => {
@@ -536,7 +779,8 @@ impl LockState {
             println!("Last commit timestamp is: {:?}", last_commit_timestamp);
 
             let mut txn = txn.write().unwrap();
-            txn.write_timestamp = last_commit_timestamp.next_logical_timestamp();
+            // never move the write timestamp backwards
+            txn.bump_write_timestamp(last_commit_timestamp.next_logical_timestamp());
         } else {
             println!("No last commit timestamp on lockState")
         }
@@ -559,6 +803,18 @@ impl LockState {
         Some(writers.remove(0))
     }
 
+    /**
+     * Removes the shared requests at the front of queued_writers.
+     */
+    pub fn remove_leading_shared_writers(&self) -> Vec<LockTableGuardLink> {
+        let mut writers = self.queued_writers.write().unwrap();
+        let count = writers
+            .iter()
+            .take_while(|writer| writer.strength == LockStrength::Shared)
+            .count();
+        writers.drain(..count).collect()
+    }
+
     pub fn register_guard(&self, guard: LockTableGuardLink) {
         let lg = guard.as_ref();
         let txn = lg.txn.read().unwrap();
@@ -579,7 +835,8 @@ impl LockState {
      * Note that this method does not actually wait.
      */
     pub async fn try_active_wait<'b>(&self, guard: LockTableGuardLink, is_read_only: bool) -> bool {
-        if is_read_only && self.lock_holder.read().unwrap().is_none() {
+        // non-locking reads only wait for intents
+        if is_read_only && !self.is_held_by_intent() {
             return false;
         }
 
@@ -597,8 +854,11 @@ impl LockState {
             if lg_txn_id == reservation_txn_id.unwrap() {
                 return false;
             }
-        } else if self.lock_holder.read().unwrap().is_none() && !is_reserved {
-            self.claim_reservation(guard.clone()).await;
+        } else if !is_read_only && !self.conflicts_with_holders(lg_txn_id, lg.strength) {
+            // Shared locks are compatible with each other so shared requests don't reserve the lock
+            if lg.strength != LockStrength::Shared && self.lock_holder.read().unwrap().is_none() {
+                self.claim_reservation(guard.clone()).await;
+            }
             return false;
         }
 
@@ -667,11 +927,96 @@ impl LockState {
         holder.and_then(|txn_meta| Some(txn_meta))
     }
 
+    pub fn push_holder_timestamp(&self, txn_id: Uuid, timestamp: Timestamp) {
+        let mut holder = self.lock_holder.write().unwrap();
+        if let Some(ref mut holder) = *holder {
+            if holder.txn_id == txn_id {
+                holder.write_timestamp = holder.write_timestamp.advance_to(timestamp);
+            }
+        }
+    }
+
     pub fn update_holder(&self, new_holder: Option<TxnMetadata>) {
         let mut holder = self.lock_holder.write().unwrap();
         *holder = new_holder;
     }
 
+    /**
+     * Adds the txn as a holder of the lock. A txn that already holds the lock keeps
+     * the stronger of the two strengths.
+     */
+    pub fn add_holder(&self, txn_meta: TxnMetadata, strength: LockStrength) {
+        if strength == LockStrength::Shared {
+            if self.get_holder_txn_id() == Some(txn_meta.txn_id) {
+                return;
+            }
+            let mut shared_holders = self.shared_holders.write().unwrap();
+            if !shared_holders
+                .iter()
+                .any(|holder| holder.txn_id == txn_meta.txn_id)
+            {
+                shared_holders.push(txn_meta);
+            }
+            return;
+        }
+
+        // upgrade the txn's shared lock
+        self.remove_shared_holder(txn_meta.txn_id);
+        let mut holder = self.lock_holder.write().unwrap();
+        let mut lock_strength = self.lock_strength.write().unwrap();
+        *lock_strength = match *holder {
+            Some(ref current) if current.txn_id == txn_meta.txn_id => {
+                std::cmp::max(*lock_strength, strength)
+            }
+            _ => strength,
+        };
+        *holder = Some(txn_meta);
+    }
+
+    /**
+     * Returns whether the txn held a shared lock.
+     */
+    pub fn remove_shared_holder(&self, txn_id: Uuid) -> bool {
+        let mut shared_holders = self.shared_holders.write().unwrap();
+        let holder_count = shared_holders.len();
+        shared_holders.retain(|holder| holder.txn_id != txn_id);
+        holder_count != shared_holders.len()
+    }
+
+    pub fn has_shared_holders(&self) -> bool {
+        !self.shared_holders.read().unwrap().is_empty()
+    }
+
+    pub fn is_shared_by_other_txn(&self, txn_id: Uuid) -> bool {
+        self.shared_holders
+            .read()
+            .unwrap()
+            .iter()
+            .any(|holder| holder.txn_id != txn_id)
+    }
+
+    pub fn get_lock_strength(&self) -> LockStrength {
+        *self.lock_strength.read().unwrap()
+    }
+
+    pub fn is_held_by_intent(&self) -> bool {
+        self.lock_holder.read().unwrap().is_some()
+            && self.get_lock_strength() == LockStrength::Intent
+    }
+
+    /**
+     * Returns whether a request of the txn acquiring a lock with the strength conflicts
+     * with the current holders.
+     */
+    pub fn conflicts_with_holders(&self, txn_id: Uuid, strength: LockStrength) -> bool {
+        let held_by_other_txn = matches!(
+            *self.lock_holder.read().unwrap(),
+            Some(holder) if holder.txn_id != txn_id
+        );
+        held_by_other_txn
+            || (strength != LockStrength::Shared && self.is_shared_by_other_txn(txn_id))
+    }
+
     pub fn remove_holder_if_txn_is_holder(&self, txn_id: Uuid) {
         let mut holder_option = self.lock_holder.write().unwrap();
         if let Some(holder) = &*holder_option {
@@ -750,10 +1095,41 @@ impl LockState {
         let holder = self.lock_holder.read().unwrap();
         holder.and_then(|txn_meta| Some(txn_meta.txn_id))
     }
+
+    /**
+     * Returns a txn other than the given txn that holds or reserved the lock.
+     */
+    pub fn get_conflicting_txn_id(&self, txn_id: Uuid) -> Option<Uuid> {
+        self.get_holder_txn_ids()
+            .into_iter()
+            .chain(self.get_reservation_txn_id())
+            .find(|holder_txn_id| *holder_txn_id != txn_id)
+    }
+
+    /**
+     * Returns the txn metadata of the shared holders and the lock_holder.
+     */
+    pub fn get_holders(&self) -> Vec<TxnMetadata> {
+        let mut holders = self.shared_holders.read().unwrap().clone();
+        holders.extend(self.get_holder_txn_meta());
+        holders
+    }
+
+    pub fn get_holder_txn_ids(&self) -> Vec<Uuid> {
+        self.get_holders()
+            .iter()
+            .map(|holder| holder.txn_id)
+            .collect()
+    }
 }
 
 impl LockTableGuard {
-    pub fn new(txn: TxnLink, is_read_only: bool, keys: SpanSet<Key>) -> Self {
+    pub fn new(
+        txn: TxnLink,
+        is_read_only: bool,
+        strength: LockStrength,
+        keys: SpanSet<Key>,
+    ) -> Self {
         let (tx, rx) = channel::<()>(1);
         LockTableGuard {
             should_wait: RwLock::new(false),
@@ -763,16 +1139,19 @@ impl LockTableGuard {
             guard_id: Uuid::new_v4(),
             is_read_only: RwLock::new(is_read_only),
             wait_state: RwLock::new(WaitingState::DoneWaiting),
+            strength,
             keys,
+            conflict: RwLock::new(None),
         }
     }
 
     pub fn new_lock_table_guard_link(
         txn: TxnLink,
         is_read_only: bool,
+        strength: LockStrength,
         keys: SpanSet<Key>,
     ) -> LockTableGuardLink {
-        Arc::new(LockTableGuard::new(txn, is_read_only, keys))
+        Arc::new(LockTableGuard::new(txn, is_read_only, strength, keys))
     }
 
     /**
@@ -794,10 +1173,21 @@ impl LockTableGuard {
      */
     pub async fn done_waiting_at_lock(&self) {
         let sender = self.wait_done_sender.as_ref();
-        sender.send(()).await.unwrap();
+        // A request spanning multiple keys can be notified by multiple locks.
+        // A single pending notification is enough to wake up the waiter, and
+        // there's no one to wake up if the receiver is gone.
+        let _ = sender.try_send(());
         self.update_wait_state(WaitingState::DoneWaiting);
     }
 
+    /**
+     * Returns the conflicting lock recorded by scan_and_enqueue. None if the request
+     * didn't have to wait or no other txn held or reserved the lock it waited at.
+     */
+    pub fn get_conflict(&self) -> Option<(Key, Uuid)> {
+        self.conflict.read().unwrap().clone()
+    }
+
     pub fn get_txn_meta(guard_link: &LockTableGuardLink) -> TxnMetadata {
         let read_guard = guard_link.as_ref();
         let txn_guard = read_guard.txn.read().unwrap();
@@ -6,12 +6,14 @@ pub mod test {
     use crate::{
         db::db::TxnLink,
         execute::request::{
-            GetRequest, PutRequest, Request, RequestMetadata, RequestUnion, SpanSet,
+            GetForUpdateRequest, GetRequest, PutRequest, Request, RequestMetadata, RequestUnion,
+            SpanSet,
         },
         hlc::timestamp::Timestamp,
         latch_manager::latch_interval_btree::Range,
         lock_table::lock_table::{
-            LockStateLink, LockTable, LockTableGuard, LockTableGuardLink, WaitingState,
+            LockStateLink, LockStrength, LockTable, LockTableGuard, LockTableGuardLink,
+            WaitingState,
         },
         storage::{serialized_to_value, str_to_key, txn::Txn, Key},
     };
@@ -119,7 +121,12 @@ pub mod test {
                 end_key: k.clone(),
             })
             .collect::<SpanSet<Key>>();
-        let lg = LockTableGuard::new_lock_table_guard_link(txn.clone(), is_read_only, spans);
+        let lg = LockTableGuard::new_lock_table_guard_link(
+            txn.clone(),
+            is_read_only,
+            LockStrength::Intent,
+            spans,
+        );
         (txn_id, txn, lg)
     }
 
@@ -133,7 +140,7 @@ pub mod test {
         let txn = Txn::new_link(txn_id, timestamp);
         (
             Request {
-                metadata: RequestMetadata { txn: txn.clone() },
+                metadata: RequestMetadata::new(txn.clone()),
                 request_union,
             },
             txn,
@@ -148,7 +155,25 @@ pub mod test {
         let txn = Txn::new_link(txn_id, timestamp);
         (
             Request {
-                metadata: RequestMetadata { txn: txn.clone() },
+                metadata: RequestMetadata::new(txn.clone()),
+                request_union,
+            },
+            txn,
+        )
+    }
+
+    pub fn create_test_locking_read_request(
+        key: &str,
+        strength: LockStrength,
+    ) -> (Request, TxnLink) {
+        let request_union = RequestUnion::GetForUpdate(GetForUpdateRequest {
+            key: str_to_key(key),
+            strength,
+        });
+        let txn = Txn::new_link(Uuid::new_v4(), Timestamp::new(1, 2));
+        (
+            Request {
+                metadata: RequestMetadata::new(txn.clone()),
                 request_union,
             },
             txn,
@@ -415,7 +440,7 @@ pub mod test {
 
                 let lock_table_2 = lock_table.clone();
                 let task_1 = tokio::spawn(async move {
-                    lock_table_2.wait_for(lg.clone()).await;
+                    lock_table_2.wait_for(lg.clone(), None).await;
                     println!("FINISHED!!")
                 });
 
@@ -711,5 +736,156 @@ pub mod test {
                 assert_lock_state(&lock_table, str_to_key(key_str), test_lock_state);
             }
         }
+
+        mod locking_reads {
+            use uuid::Uuid;
+
+            use crate::{
+                hlc::timestamp::Timestamp,
+                lock_table::{
+                    lock_table::{
+                        AbortUpdateLock, AcquireLockError, CommitUpdateLock, LockStrength,
+                        LockTable, UpdateLock, WaitingState,
+                    },
+                    lock_table_test::test::{
+                        assert_lock_state, assert_lock_table_guard_wait_state,
+                        create_test_locking_read_request, create_test_put_request,
+                        create_test_read_request, get_guard_id, TestLockState,
+                    },
+                },
+                storage::{str_to_key, txn::Txn},
+            };
+
+            #[tokio::test]
+            async fn shared_locks_are_compatible() {
+                let key_str = "foo";
+                let lock_table = LockTable::new_with_defaults();
+                let txn_1 = Txn::new_link(Uuid::new_v4(), Timestamp::new(1, 1));
+                lock_table
+                    .acquire_lock(str_to_key(key_str), txn_1.clone(), LockStrength::Shared)
+                    .await
+                    .unwrap();
+
+                let (request, txn_2) =
+                    create_test_locking_read_request(key_str, LockStrength::Shared);
+                let (should_wait, _) = lock_table.scan_and_enqueue(&request).await;
+                assert!(!should_wait);
+                lock_table
+                    .acquire_lock(str_to_key(key_str), txn_2.clone(), LockStrength::Shared)
+                    .await
+                    .unwrap();
+
+                let lock_state = lock_table.get_lock_state(&str_to_key(key_str)).unwrap();
+                assert_eq!(
+                    lock_state.get_holder_txn_ids(),
+                    Vec::from([txn_1.read().unwrap().txn_id, txn_2.read().unwrap().txn_id])
+                );
+                assert!(lock_state.lock_holder.read().unwrap().is_none());
+            }
+
+            #[tokio::test]
+            async fn writer_waits_for_shared_holders() {
+                let key_str = "foo";
+                let lock_table = LockTable::new_with_defaults();
+                let txn_1 = Txn::new_link(Uuid::new_v4(), Timestamp::new(1, 1));
+                let txn_1_id = txn_1.read().unwrap().txn_id;
+                lock_table
+                    .acquire_lock(str_to_key(key_str), txn_1.clone(), LockStrength::Shared)
+                    .await
+                    .unwrap();
+
+                let (request, _) = create_test_put_request(key_str);
+                let (should_wait, guard) = lock_table.scan_and_enqueue(&request).await;
+                assert!(should_wait);
+                assert_lock_table_guard_wait_state(guard.clone(), WaitingState::Waiting);
+
+                let can_gc_lock = lock_table
+                    .update_locks(
+                        str_to_key(key_str),
+                        &UpdateLock::Commit(CommitUpdateLock {
+                            txn_id: txn_1_id,
+                            commit_timestamp: Timestamp::new(2, 2),
+                        }),
+                    )
+                    .await;
+                assert!(!can_gc_lock);
+                assert_lock_table_guard_wait_state(guard.clone(), WaitingState::DoneWaiting);
+
+                // a shared lock doesn't leave a committed value behind
+                let test_lock_state = TestLockState {
+                    queued_writers: Vec::from([]),
+                    waiting_readers: Vec::from([]),
+                    lock_holder: None,
+                    reservation: Some(get_guard_id(guard)),
+                    last_commit_timestamp: None,
+                };
+                assert_lock_state(&lock_table, str_to_key(key_str), test_lock_state);
+            }
+
+            #[tokio::test]
+            async fn acquiring_lock_shared_by_other_txn_conflicts() {
+                let key_str = "foo";
+                let lock_table = LockTable::new_with_defaults();
+                let txn_1 = Txn::new_link(Uuid::new_v4(), Timestamp::new(1, 1));
+                let txn_1_id = txn_1.read().unwrap().txn_id;
+                lock_table
+                    .acquire_lock(str_to_key(key_str), txn_1.clone(), LockStrength::Shared)
+                    .await
+                    .unwrap();
+
+                let txn_2 = Txn::new_link(Uuid::new_v4(), Timestamp::new(2, 2));
+                let res = lock_table
+                    .acquire_lock(str_to_key(key_str), txn_2.clone(), LockStrength::Exclusive)
+                    .await;
+                assert_eq!(
+                    res,
+                    Err(AcquireLockError::LockConflict {
+                        key: str_to_key(key_str),
+                        holder_txn: txn_1_id,
+                    })
+                );
+                let lock_state = lock_table.get_lock_state(&str_to_key(key_str)).unwrap();
+                assert_eq!(lock_state.get_holder_txn_ids(), Vec::from([txn_1_id]));
+            }
+
+            #[tokio::test]
+            async fn shared_request_waits_for_exclusive_holder() {
+                let key_str = "foo";
+                let lock_table = LockTable::new_with_defaults();
+                let txn_1 = Txn::new_link(Uuid::new_v4(), Timestamp::new(1, 1));
+                let txn_1_id = txn_1.read().unwrap().txn_id;
+                lock_table
+                    .acquire_lock(str_to_key(key_str), txn_1.clone(), LockStrength::Exclusive)
+                    .await
+                    .unwrap();
+
+                // non-locking reads don't wait for locks that aren't intents
+                let (read_request, _) = create_test_read_request(key_str, Timestamp::new(5, 5));
+                let (should_wait, _) = lock_table.scan_and_enqueue(&read_request).await;
+                assert!(!should_wait);
+
+                let (request, _) = create_test_locking_read_request(key_str, LockStrength::Shared);
+                let (should_wait, guard) = lock_table.scan_and_enqueue(&request).await;
+                assert!(should_wait);
+
+                lock_table
+                    .update_locks(
+                        str_to_key(key_str),
+                        &UpdateLock::Abort(AbortUpdateLock { txn_id: txn_1_id }),
+                    )
+                    .await;
+                assert_lock_table_guard_wait_state(guard.clone(), WaitingState::DoneWaiting);
+
+                // the shared request proceeds without reserving the lock
+                let test_lock_state = TestLockState {
+                    queued_writers: Vec::from([]),
+                    waiting_readers: Vec::from([]),
+                    lock_holder: None,
+                    reservation: None,
+                    last_commit_timestamp: None,
+                };
+                assert_lock_state(&lock_table, str_to_key(key_str), test_lock_state);
+            }
+        }
     }
 }
@@ -0,0 +1,88 @@
+use std::{
+    ffi::CStr,
+    sync::{Arc, RwLock},
+};
+
+use rocksdb::{
+    compaction_filter::{CompactionFilter, Decision},
+    compaction_filter_factory::{CompactionFilterContext, CompactionFilterFactory},
+};
+
+use crate::hlc::timestamp::Timestamp;
+
+use super::{mvcc_key::decode_mvcc_key, Key};
+
+/**
+ * The GC threshold published to the compaction filters. None until the history is
+ * garbage collected for the first time.
+ */
+pub type GcThreshold = Arc<RwLock<Option<Timestamp>>>;
+
+/**
+ * Creates a GcCompactionFilter for every compaction of the mvcc column family.
+ */
+pub struct GcCompactionFilterFactory {
+    pub gc_threshold: GcThreshold,
+}
+
+/**
+ * Drops the versions of a key that are shadowed by a newer version at or beneath the
+ * GC threshold. Reads at or above the threshold never see them and reads beneath
+ * the threshold are rejected.
+ *
+ * The compaction passes the keys in the order of the comparator, so the versions of
+ * a key are filtered from the most recent to the oldest. A version is only dropped
+ * once the newer version shadowing it was kept by the same compaction. Tombstones are
+ * kept since dropping the most recent version of a key would expose older versions
+ * that aren't part of the compaction.
+ */
+pub struct GcCompactionFilter {
+    // the threshold when the compaction started, so that it doesn't change midway
+    gc_threshold: Option<Timestamp>,
+    current_key: Option<Key>,
+    found_version_beneath_threshold: bool,
+}
+
+impl CompactionFilterFactory for GcCompactionFilterFactory {
+    type Filter = GcCompactionFilter;
+
+    fn create(&mut self, _: CompactionFilterContext) -> Self::Filter {
+        GcCompactionFilter {
+            gc_threshold: *self.gc_threshold.read().unwrap(),
+            current_key: None,
+            found_version_beneath_threshold: false,
+        }
+    }
+
+    fn name(&self) -> &CStr {
+        c"mvcc_gc_factory"
+    }
+}
+
+impl CompactionFilter for GcCompactionFilter {
+    fn filter(&mut self, _level: u32, key: &[u8], _value: &[u8]) -> Decision {
+        let Some(threshold) = self.gc_threshold else {
+            return Decision::Keep;
+        };
+        let Some(mvcc_key) = decode_mvcc_key(key) else {
+            return Decision::Keep;
+        };
+        if self.current_key.as_ref() != Some(&mvcc_key.key) {
+            self.current_key = Some(mvcc_key.key.clone());
+            self.found_version_beneath_threshold = false;
+        }
+        if mvcc_key.is_intent_key() || mvcc_key.timestamp > threshold {
+            return Decision::Keep;
+        }
+        if self.found_version_beneath_threshold {
+            Decision::Remove
+        } else {
+            self.found_version_beneath_threshold = true;
+            Decision::Keep
+        }
+    }
+
+    fn name(&self) -> &CStr {
+        c"mvcc_gc"
+    }
+}
@@ -0,0 +1,264 @@
+use std::{cmp::Ordering, path::Path};
+
+use rocksdb::{ColumnFamilyDescriptor, ErrorKind, IteratorMode, DB};
+
+use crate::hlc::timestamp::Timestamp;
+
+use super::{
+    mvcc_key::{encode_mvcc_key, MVCCKey},
+    storage::{Storage, COLUMN_FAMILIES, MVCC_COLUMN_FAMILY},
+};
+
+/**
+ * Databases created before the order-preserving key encoding sort their keys with
+ * a custom comparator that decodes the legacy encoding:
+ * [key] [wall_time] [logical_time] [hardcoded mvcc_key]
+ * key: (variable length)
+ * wall_time: uint64 in little-endian
+ * logical_time: uint32 in little-endian
+ * hardcoded_mvcc (4 bytes): "mvcc"
+ *
+ * RocksDB refuses to open a database with another comparator than the one it was
+ * created with, so such a database is migrated to the new encoding before it's opened.
+ */
+pub static LEGACY_COMPARATOR: &str = "mvcc_ordering";
+
+// the number of keys migrated per write batch
+const MIGRATION_BATCH_SIZE: usize = 1024;
+
+/**
+ * Returns whether the database couldn't be opened because it was created with the
+ * legacy comparator.
+ */
+pub fn is_legacy_key_encoding_error(err: &rocksdb::Error) -> bool {
+    err.kind() == ErrorKind::InvalidArgument && err.as_ref().contains(LEGACY_COMPARATOR)
+}
+
+/**
+ * Rewrites the database at the path with the order-preserving key encoding.
+ *
+ * The database is copied to a new database next to it, which then replaces it. The
+ * original database is only removed once the copy is complete, so a crash during the
+ * migration leaves either the original database or the complete copy behind. See
+ * finish_interrupted_migration.
+ */
+pub fn migrate_legacy_key_encoding(path: &str) {
+    let (legacy_path, migration_path) = get_migration_paths(path);
+    {
+        let legacy_db = open_legacy_db(path);
+        let storage = Storage::new_cleaned(&migration_path);
+        for cf_name in COLUMN_FAMILIES {
+            let legacy_cf = legacy_db.cf_handle(cf_name).unwrap();
+            let cf = storage.db.cf_handle(cf_name).unwrap();
+            let mut batch = rocksdb::WriteBatch::default();
+            for kv in legacy_db.iterator_cf(legacy_cf, IteratorMode::Start) {
+                let (key, value) = kv.unwrap();
+                let key = match decode_legacy_mvcc_key(&key) {
+                    Some(mvcc_key) if cf_name == MVCC_COLUMN_FAMILY => encode_mvcc_key(&mvcc_key),
+                    _ => key.to_vec(),
+                };
+                batch.put_cf(cf, key, value);
+                if batch.len() == MIGRATION_BATCH_SIZE {
+                    storage.db.write(std::mem::take(&mut batch)).unwrap();
+                }
+            }
+            storage.db.write(batch).unwrap();
+        }
+        storage.db.flush_wal(true).unwrap();
+    }
+    std::fs::rename(path, &legacy_path).unwrap();
+    std::fs::rename(&migration_path, path).unwrap();
+    std::fs::remove_dir_all(&legacy_path).unwrap();
+}
+
+/**
+ * Cleans up after a migration that was interrupted by a crash. If the original database
+ * was already moved aside, the copy is complete and replaces it. Otherwise, the partial
+ * copy is removed and the migration starts over when the database is opened.
+ */
+pub fn finish_interrupted_migration(path: &str) {
+    let (legacy_path, migration_path) = get_migration_paths(path);
+    if !Path::new(path).exists() && Path::new(&legacy_path).exists() {
+        std::fs::rename(&migration_path, path).unwrap();
+    }
+    for leftover_path in [legacy_path, migration_path] {
+        if Path::new(&leftover_path).exists() {
+            std::fs::remove_dir_all(&leftover_path).unwrap();
+        }
+    }
+}
+
+fn get_migration_paths(path: &str) -> (String, String) {
+    let path = path.trim_end_matches('/');
+    (format!("{}.legacy", path), format!("{}.migration", path))
+}
+
+fn open_legacy_db(path: &str) -> DB {
+    let mut options = rocksdb::Options::default();
+    options.create_missing_column_families(true);
+    let cf_descriptors = COLUMN_FAMILIES.into_iter().map(|cf_name| {
+        let mut cf_options = rocksdb::Options::default();
+        cf_options.set_comparator(LEGACY_COMPARATOR, compare_legacy);
+        ColumnFamilyDescriptor::new(cf_name, cf_options)
+    });
+    DB::open_cf_descriptors(&options, path, cf_descriptors).unwrap()
+}
+
+pub fn decode_legacy_mvcc_key(encoded_mvcc_key: &[u8]) -> Option<MVCCKey> {
+    let hardcoded_len = 4;
+    let timestamp_len = 12; // 4 + 8 bytes
+    if encoded_mvcc_key.len() < hardcoded_len + timestamp_len
+        || !encoded_mvcc_key.ends_with("mvcc".as_bytes())
+    {
+        return None;
+    }
+    let hardcoded_start = encoded_mvcc_key.len() - hardcoded_len;
+    let key_end = hardcoded_start - timestamp_len;
+    let encoded_timestamp = &encoded_mvcc_key[key_end..hardcoded_start];
+    Some(MVCCKey {
+        key: encoded_mvcc_key[..key_end].to_vec(),
+        timestamp: Timestamp {
+            wall_time: u64::from_le_bytes(encoded_timestamp[0..8].try_into().unwrap()),
+            logical_time: u32::from_le_bytes(encoded_timestamp[8..12].try_into().unwrap()),
+        },
+    })
+}
+
+/**
+ * The legacy comparator. Keys are sorted by key, then intents first, then the most
+ * recent timestamp first. Keys that aren't MVCC keys are compared bytewise.
+ */
+fn compare_legacy(first: &[u8], second: &[u8]) -> Ordering {
+    match (
+        decode_legacy_mvcc_key(first),
+        decode_legacy_mvcc_key(second),
+    ) {
+        (Some(first_mvcc), Some(second_mvcc)) => {
+            first_mvcc.key.cmp(&second_mvcc.key).then_with(|| {
+                match (first_mvcc.is_intent_key(), second_mvcc.is_intent_key()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Less,
+                    (false, true) => Ordering::Greater,
+                    (false, false) => second_mvcc.timestamp.cmp(&first_mvcc.timestamp),
+                }
+            })
+        }
+        _ => first.cmp(second),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rocksdb::DB;
+    use uuid::Uuid;
+
+    use crate::{
+        helpers::test_helpers::create_temp_dir,
+        hlc::timestamp::Timestamp,
+        storage::{
+            mvcc_iterator::{IterOptions, MVCCIterator},
+            mvcc_key::MVCCKey,
+            storage::{Storage, MVCC_COLUMN_FAMILY, TRANSACTION_RECORD_COLUMN_FAMILY},
+            str_to_key,
+        },
+    };
+
+    use super::{get_migration_paths, open_legacy_db};
+
+    fn encode_legacy_mvcc_key(mvcc_key: &MVCCKey) -> Vec<u8> {
+        let mut encoded = mvcc_key.key.clone();
+        encoded.extend(mvcc_key.timestamp.wall_time.to_le_bytes());
+        encoded.extend(mvcc_key.timestamp.logical_time.to_le_bytes());
+        encoded.extend("mvcc".as_bytes());
+        encoded
+    }
+
+    fn put_legacy(db: &DB, cf_name: &str, key: Vec<u8>, value: &str) {
+        db.put_cf(db.cf_handle(cf_name).unwrap(), key, value)
+            .unwrap();
+    }
+
+    #[test]
+    fn migrates_legacy_key_encoding() {
+        let path = create_temp_dir();
+        let versions = [
+            MVCCKey::create_intent_key_with_str("apple"),
+            MVCCKey::new(str_to_key("apple"), Timestamp::new(300, 2)),
+            MVCCKey::new(str_to_key("apple"), Timestamp::new(12, 1)),
+            MVCCKey::new(str_to_key("banana"), Timestamp::new(5, 0)),
+        ];
+        let txn_id = Uuid::new_v4();
+        {
+            let legacy_db = open_legacy_db_for_test(&path);
+            for (i, version) in versions.iter().enumerate() {
+                let value = i.to_string();
+                put_legacy(
+                    &legacy_db,
+                    MVCC_COLUMN_FAMILY,
+                    encode_legacy_mvcc_key(version),
+                    &value,
+                );
+            }
+            put_legacy(
+                &legacy_db,
+                TRANSACTION_RECORD_COLUMN_FAMILY,
+                txn_id.to_string().into_bytes(),
+                "record",
+            );
+        }
+
+        let storage = Storage::new(&path);
+        let mut it = MVCCIterator::new(&storage, IterOptions { prefix: false });
+        for (i, version) in versions.iter().enumerate() {
+            assert!(it.valid());
+            assert_eq!(&it.current_key(), version);
+            assert_eq!(it.current_value(), i.to_string().into_bytes());
+            it.next();
+        }
+        assert!(!it.valid());
+        assert_eq!(
+            storage
+                .get_normal_iterator(TRANSACTION_RECORD_COLUMN_FAMILY)
+                .unwrap()
+                .map(|kv| kv.unwrap().0.to_vec())
+                .collect::<Vec<_>>(),
+            Vec::from([txn_id.to_string().into_bytes()])
+        );
+
+        let (legacy_path, migration_path) = get_migration_paths(&path);
+        assert!(!Path::new(&legacy_path).exists());
+        assert!(!Path::new(&migration_path).exists());
+    }
+
+    #[test]
+    fn finishes_migration_interrupted_after_moving_legacy_db_aside() {
+        let path = create_temp_dir();
+        let (legacy_path, migration_path) = get_migration_paths(&path);
+        let mvcc_key = MVCCKey::new(str_to_key("apple"), Timestamp::new(12, 1));
+        {
+            let storage = Storage::new_cleaned(&migration_path);
+            storage.put_serialized_with_mvcc_key(&mvcc_key, 12).unwrap();
+            drop(open_legacy_db_for_test(&legacy_path));
+        }
+
+        let storage = Storage::new(&path);
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<i32>(&mvcc_key)
+                .unwrap(),
+            Some(12)
+        );
+        assert!(!Path::new(&legacy_path).exists());
+        assert!(!Path::new(&migration_path).exists());
+    }
+
+    fn open_legacy_db_for_test(path: &str) -> DB {
+        std::fs::create_dir_all(path).unwrap();
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        drop(DB::open(&options, path).unwrap());
+        open_legacy_db(path)
+    }
+}
@@ -1,7 +1,10 @@
-use serde::Serialize;
+use bincode::Options;
+use serde::{de::DeserializeOwned, Serialize};
 
-use crate::hlc::timestamp::Timestamp;
+use crate::{hlc::timestamp::Timestamp, StorageError, StorageResult};
 
+pub mod gc_compaction_filter;
+pub mod migration;
 pub mod mvcc;
 pub mod mvcc_iterator;
 pub mod mvcc_key;
@@ -19,6 +22,79 @@ pub fn serialized_to_value<T: Serialize>(value: T) -> Value {
     serde_json::to_string(&value).unwrap().into_bytes()
 }
 
+/**
+ * A deleted key is represented by a version (or intent) with an empty value.
+ * Stored values are never empty (see to_stored_value) so they can't be mistaken
+ * for a tombstone.
+ */
+pub fn create_tombstone() -> Value {
+    Vec::new()
+}
+
+pub fn is_tombstone(value: &Value) -> bool {
+    value.is_empty()
+}
+
+// prefixes the stored values that would otherwise be empty or start with the prefix
+const ESCAPED_VALUE_PREFIX: u8 = 0xFF;
+
+/**
+ * Converts a written value to the value stored in the MVCC column family. Values are
+ * stored as is, except that an empty value and a value starting with
+ * ESCAPED_VALUE_PREFIX are prefixed with it. Neither JSON nor the values stored before
+ * start with the prefix, so they are read unchanged.
+ */
+pub fn to_stored_value(value: &[u8]) -> Value {
+    match value.first() {
+        None | Some(&ESCAPED_VALUE_PREFIX) => {
+            let mut stored = Vec::with_capacity(value.len() + 1);
+            stored.push(ESCAPED_VALUE_PREFIX);
+            stored.extend_from_slice(value);
+            stored
+        }
+        Some(_) => value.to_vec(),
+    }
+}
+
+/**
+ * Converts a stored value that isn't a tombstone back to the written value.
+ */
+pub fn from_stored_value(mut stored: Value) -> Value {
+    if stored.first() == Some(&ESCAPED_VALUE_PREFIX) {
+        stored.remove(0);
+    }
+    stored
+}
+
+// the first byte of values in the internal binary encoding. JSON never starts with it
+const BINARY_ENCODING_TAG: u8 = 0x01;
+
+/**
+ * Encodes the internal values of the storage, such as intents and txn records, in a
+ * compact binary encoding.
+ */
+pub fn encode_internal<T: Serialize>(value: &T) -> StorageResult<Vec<u8>> {
+    let mut encoded = Vec::from([BINARY_ENCODING_TAG]);
+    bincode::DefaultOptions::new()
+        .serialize_into(&mut encoded, value)
+        .map_err(|err| StorageError::new("encode_error".to_owned(), err.to_string()))?;
+    Ok(encoded)
+}
+
+/**
+ * Decodes an internal value. Values written before the binary encoding are JSON and
+ * are still decoded.
+ */
+pub fn decode_internal<T: DeserializeOwned>(value: &[u8]) -> StorageResult<T> {
+    let decoded = match value.split_first() {
+        Some((&BINARY_ENCODING_TAG, encoded)) => bincode::DefaultOptions::new()
+            .deserialize(encoded)
+            .map_err(|err| err.to_string()),
+        _ => serde_json::from_slice(value).map_err(|err| err.to_string()),
+    };
+    decoded.map_err(|err| StorageError::new("decode_error".to_owned(), err))
+}
+
 pub fn str_to_key(str: &str) -> Key {
     str.as_bytes().to_vec()
 }
@@ -1,25 +1,41 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    sync::Mutex,
+    time::SystemTime,
+};
+
 use rand::distributions::{Alphanumeric, DistString};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{db::db::TxnLink, hlc::timestamp::Timestamp};
+use crate::{db::db::TxnLink, hlc::timestamp::Timestamp, StorageError, StorageResult};
 
 use super::{
+    create_tombstone, is_tombstone,
     mvcc_iterator::{IterOptions, MVCCIterator},
     mvcc_key::{create_intent_key, decode_mvcc_key, MVCCKey},
     mvcc_scanner::MVCCScanner,
-    storage::Storage,
-    txn::{TransactionStatus, TxnIntent, TxnMetadata, TxnRecord, UncommittedValue},
+    storage::{Storage, WriteBatch},
+    txn::{SequencedValue, TransactionStatus, TxnIntent, TxnMetadata, TxnRecord, UncommittedValue},
     Key, Value,
 };
 
 pub struct KVStore {
     pub storage: Storage,
+    // serializes the updates of txn records that depend on the current record
+    txn_record_lock: Mutex<()>,
+    // serializes garbage collections
+    gc_lock: Mutex<()>,
 }
 
 pub struct MVCCScanParams {
-    max_result_count: usize,
-    transaction: Option<TxnLink>,
+    pub max_result_count: usize,
+    pub transaction: Option<TxnLink>,
+    // whether the end key of the scan is excluded
+    pub end_key_exclusive: bool,
+    // whether tombstones are returned in the results
+    pub tombstones: bool,
 }
 
 pub struct MVCCGetParams {
@@ -31,14 +47,35 @@ pub struct WriteIntentError {
     pub intent: TxnIntent,
 }
 
+/**
+ * A write fails if the key has an intent of another txn or if the storage fails.
+ */
+#[derive(Debug)]
+pub enum MVCCWriteError {
+    WriteIntentError(WriteIntentError),
+    StorageError(StorageError),
+}
+
+impl From<StorageError> for MVCCWriteError {
+    fn from(err: StorageError) -> Self {
+        MVCCWriteError::StorageError(err)
+    }
+}
+
 pub struct MVCCGetResult {
     pub value: Option<(MVCCKey, Value)>,
     pub intent: Option<(TxnIntent, Value)>,
 }
 
 pub struct MVCCScanResult {
-    results: Vec<(MVCCKey, Value)>,
-    intents: Vec<(TxnIntent, Value)>,
+    pub results: Vec<(MVCCKey, Value)>,
+    pub intents: Vec<(TxnIntent, Value)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    pub versions_removed: usize,
+    pub txn_records_removed: usize,
 }
 
 pub fn serialize<T: Serialize>(value: T) -> Value {
@@ -49,14 +86,18 @@ pub fn serialize<T: Serialize>(value: T) -> Value {
 impl KVStore {
     // path example: "./tmp/data";
     pub fn new_cleaned(path: &str) -> Self {
-        KVStore {
-            storage: Storage::new_cleaned(path),
-        }
+        KVStore::from_storage(Storage::new_cleaned(path))
     }
 
     pub fn new(path: &str) -> Self {
+        KVStore::from_storage(Storage::new(path))
+    }
+
+    fn from_storage(storage: Storage) -> Self {
         KVStore {
-            storage: Storage::new(path),
+            storage,
+            txn_record_lock: Mutex::new(()),
+            gc_lock: Mutex::new(()),
         }
     }
 
@@ -74,6 +115,8 @@ impl KVStore {
         let scan_params = MVCCScanParams {
             max_result_count: 1,
             transaction: params.transaction,
+            end_key_exclusive: false,
+            tombstones: false,
         };
         let res = self.mvcc_scan(key.to_owned(), key.to_owned(), timestamp, scan_params);
         let results = res.results;
@@ -92,8 +135,8 @@ impl KVStore {
         }
     }
 
-    pub fn mvcc_delete(&self, key: MVCCKey) {
-        self.storage.delete_mvcc(&key);
+    pub fn mvcc_delete(&self, key: MVCCKey) -> StorageResult<()> {
+        self.storage.delete_mvcc(&key)
     }
 
     pub fn mvcc_scan(
@@ -112,6 +155,8 @@ impl KVStore {
             scan_params.max_result_count,
             scan_params.transaction,
         );
+        scanner.end_key_exclusive = scan_params.end_key_exclusive;
+        scanner.tombstones = scan_params.tombstones;
         scanner.scan();
         MVCCScanResult {
             results: scanner.results,
@@ -137,7 +182,7 @@ impl KVStore {
         timestamp: Option<Timestamp>,
         txn: Option<TxnLink>,
         value: T,
-    ) -> Result<MVCCKey, WriteIntentError> {
+    ) -> Result<MVCCKey, MVCCWriteError> {
         self.mvcc_put_raw(key, timestamp, txn, serialize(value))
     }
 
@@ -147,8 +192,9 @@ impl KVStore {
         timestamp: Option<Timestamp>,
         txn: Option<TxnLink>,
         value: Value,
-    ) -> Result<MVCCKey, WriteIntentError> {
+    ) -> Result<MVCCKey, MVCCWriteError> {
         let intent = self.mvcc_get_uncommited_value(&key);
+        let mut intent_history = Vec::new();
 
         match intent {
             Some((metadata, transaction_record)) => match &txn {
@@ -156,18 +202,32 @@ impl KVStore {
                     // this means we're overwriting our own transaction
                     // TODO: epoch - transaction retries
                     if metadata.txn_id == put_txn.read().unwrap().txn_id {
+                        // keep the overwritten value in case the txn rolls back to a savepoint
+                        if let Some(previous) = self.get_uncommitted_value(&create_intent_key(&key))
+                        {
+                            intent_history = previous.intent_history;
+                            intent_history.push(SequencedValue {
+                                sequence: previous.sequence,
+                                value: previous.value,
+                            });
+                        }
                     } else {
                         match transaction_record.status {
-                            TransactionStatus::PENDING => {
-                                return Err(WriteIntentError {
+                            // the intent of a committed txn could still be waiting for
+                            // its resolution. The caller resolves it and retries
+                            TransactionStatus::PENDING | TransactionStatus::COMMITTED => {
+                                return Err(MVCCWriteError::WriteIntentError(WriteIntentError {
                                     intent: TxnIntent {
                                         txn_meta: metadata,
                                         key,
                                     },
-                                })
+                                }))
+                            }
+                            // the aborted txn's intent wasn't cleaned up yet, e.g. because
+                            // the txn was aborted by a pusher. It's removed and overwritten
+                            TransactionStatus::ABORTED => {
+                                self.storage.delete_mvcc(&create_intent_key(&key))?;
                             }
-                            TransactionStatus::COMMITTED => todo!(),
-                            TransactionStatus::ABORTED => todo!(), // clean up
                         }
                     }
                 }
@@ -190,27 +250,98 @@ impl KVStore {
         let version_key = MVCCKey::new(key.clone(), write_timestamp);
 
         if let Some(ref transaction) = txn {
-            self.storage
-                .put_serialized_with_mvcc_key(
-                    &create_intent_key(&key),
-                    UncommittedValue {
-                        value: value.clone(), // is this correct?
-                        txn_metadata: TxnMetadata {
-                            txn_id: transaction.read().unwrap().txn_id,
-                            write_timestamp: write_timestamp,
-                        },
+            let mut transaction = transaction.write().unwrap();
+            let sequence = transaction.next_sequence();
+            self.storage.put_serialized_with_mvcc_key(
+                &create_intent_key(&key),
+                UncommittedValue {
+                    value: value.clone(), // is this correct?
+                    txn_metadata: TxnMetadata {
+                        txn_id: transaction.txn_id,
+                        write_timestamp,
+                        priority: transaction.priority,
                     },
-                )
-                .unwrap();
+                    sequence,
+                    intent_history,
+                },
+            )?;
         } else {
             self.storage
-                .put_raw_with_mvcc_key(&version_key, value.clone())
-                .unwrap()
+                .put_raw_with_mvcc_key(&version_key, value.clone())?;
         }
 
         Ok(version_key)
     }
 
+    /**
+     * Writes committed versions of the keys at the timestamp in a single write batch
+     * without intents, e.g. to commit a txn in one phase. If a key has an intent,
+     * a WriteIntentError is returned and nothing is written.
+     */
+    pub fn mvcc_put_committed(
+        &self,
+        writes: &[(Key, Value)],
+        timestamp: Timestamp,
+    ) -> Result<(), MVCCWriteError> {
+        for (key, _) in writes.iter() {
+            if let Some(uncommitted_value) = self.get_uncommitted_value(&create_intent_key(key)) {
+                return Err(MVCCWriteError::WriteIntentError(WriteIntentError {
+                    intent: TxnIntent {
+                        txn_meta: uncommitted_value.txn_metadata,
+                        key: key.clone(),
+                    },
+                }));
+            }
+        }
+        let mut batch = self.storage.new_write_batch();
+        for (key, value) in writes.iter() {
+            batch.put_raw_with_mvcc_key(&MVCCKey::new(key.clone(), timestamp), value.clone());
+        }
+        self.storage.write(batch)?;
+        Ok(())
+    }
+
+    /**
+     * Deletes the key by writing a tombstone. Like mvcc_put, the tombstone is written
+     * as an intent if a transaction is provided. Older versions of the key are kept
+     * so that reads at earlier timestamps still see the previous value.
+     */
+    pub fn mvcc_put_tombstone(
+        &self,
+        key: Key,
+        timestamp: Option<Timestamp>,
+        txn: Option<TxnLink>,
+    ) -> Result<MVCCKey, MVCCWriteError> {
+        self.mvcc_put_raw(key, timestamp, txn, create_tombstone())
+    }
+
+    /**
+     * Returns the intents of the keys within the span.
+     * Start and end keys are both inclusive.
+     */
+    pub fn mvcc_scan_intents(&self, start_key: &Key, end_key: &Key) -> Vec<TxnIntent> {
+        let mut intents = Vec::new();
+        let mut it = self.storage.new_mvcc_iterator(IterOptions { prefix: true });
+        it.seek_ge(&create_intent_key(start_key));
+        while it.valid() {
+            let current_key = it.current_key();
+            if &current_key.key > end_key {
+                break;
+            }
+            if current_key.is_intent_key() {
+                let txn_metadata = it
+                    .current_value_serialized::<UncommittedValue>()
+                    .txn_metadata;
+                intents.push(TxnIntent {
+                    txn_meta: txn_metadata,
+                    key: current_key.key,
+                });
+            }
+            it.next();
+        }
+        intents
+    }
+
     pub fn mvcc_get_uncommited_value(&self, key: &Key) -> Option<(TxnMetadata, TxnRecord)> {
         let options = IterOptions { prefix: true };
         let mut it = self.storage.new_mvcc_iterator(options);
@@ -236,13 +367,12 @@ impl KVStore {
         &self,
         txn_id: Uuid,
         write_timestamp: Timestamp,
-    ) -> () {
+    ) -> StorageResult<()> {
         let record = TxnRecord {
             status: TransactionStatus::PENDING,
-            metadata: TxnMetadata {
-                txn_id: txn_id.clone(),
-                write_timestamp: write_timestamp,
-            },
+            metadata: TxnMetadata::new(txn_id, write_timestamp),
+            last_heartbeat: Some(SystemTime::now()),
+            ignored_sequences: Vec::new(),
         };
         self.put_transaction_record(txn_id, &record)
     }
@@ -252,21 +382,78 @@ impl KVStore {
     }
 
     // This can be used to create or overwrite transaction record.
-    pub fn put_transaction_record(&self, transaction_id: Uuid, record: &TxnRecord) {
-        self.storage
-            .put_transaction_record(&transaction_id, record)
-            .unwrap();
+    pub fn put_transaction_record(
+        &self,
+        transaction_id: Uuid,
+        record: &TxnRecord,
+    ) -> StorageResult<()> {
+        self.storage.put_transaction_record(&transaction_id, record)
     }
 
-    pub fn commit_transaction_record(&self, transaction_id: Uuid, write_timestamp: Timestamp) {
+    /**
+     * Commits the txn record at the write timestamp. If the txn's timestamp was pushed
+     * above the write timestamp, the record isn't committed and the pushed timestamp is
+     * returned instead. The ignored sequences are recorded so that the txn's intents can
+     * be resolved from the record alone.
+     */
+    pub fn commit_transaction_record(
+        &self,
+        transaction_id: Uuid,
+        write_timestamp: Timestamp,
+        ignored_sequences: &[RangeInclusive<u64>],
+    ) -> StorageResult<Option<Timestamp>> {
+        let _lock = self.txn_record_lock.lock().unwrap();
+        if let Some(record) = self.get_transaction_record(transaction_id) {
+            if record.metadata.write_timestamp > write_timestamp {
+                return Ok(Some(record.metadata.write_timestamp));
+            }
+        }
         let record = TxnRecord {
             status: TransactionStatus::COMMITTED,
-            metadata: TxnMetadata {
-                txn_id: transaction_id.clone(),
-                write_timestamp: write_timestamp,
-            },
+            metadata: TxnMetadata::new(transaction_id, write_timestamp),
+            last_heartbeat: None,
+            ignored_sequences: ignored_sequences.to_vec(),
         };
-        self.put_transaction_record(transaction_id, &record)
+        self.put_transaction_record(transaction_id, &record)?;
+        Ok(None)
+    }
+
+    /**
+     * Records a heartbeat of the txn's coordinator. Returns false if the txn
+     * doesn't have a pending record anymore.
+     */
+    pub fn heartbeat_transaction_record(&self, transaction_id: Uuid) -> StorageResult<bool> {
+        let _lock = self.txn_record_lock.lock().unwrap();
+        match self.get_transaction_record(transaction_id) {
+            Some(mut record) if record.status == TransactionStatus::PENDING => {
+                record.last_heartbeat = Some(SystemTime::now());
+                self.put_transaction_record(transaction_id, &record)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /**
+     * Advances the write timestamp of a pending txn record to the timestamp. Returns
+     * the updated record, or None if the txn doesn't have a record.
+     */
+    pub fn push_transaction_record(
+        &self,
+        transaction_id: Uuid,
+        timestamp: Timestamp,
+    ) -> StorageResult<Option<TxnRecord>> {
+        let _lock = self.txn_record_lock.lock().unwrap();
+        let Some(mut record) = self.get_transaction_record(transaction_id) else {
+            return Ok(None);
+        };
+        if record.status == TransactionStatus::PENDING
+            && record.metadata.write_timestamp < timestamp
+        {
+            record.metadata.write_timestamp = timestamp;
+            self.put_transaction_record(transaction_id, &record)?;
+        }
+        Ok(Some(record))
     }
 
     // TODO: Do we really need a write_timestamp here?
@@ -274,16 +461,61 @@ impl KVStore {
         &self,
         transaction_id: Uuid,
         write_timestamp: Timestamp,
-    ) {
-        // TODO: Delete all uncomitted intents from DB
-        self.put_transaction_record(
-            transaction_id,
+    ) -> StorageResult<()> {
+        self.abort_transaction(transaction_id, write_timestamp, &[])
+    }
+
+    /**
+     * Aborts the txn record and removes the txn's intents on the keys in the same
+     * write batch.
+     */
+    pub fn abort_transaction(
+        &self,
+        transaction_id: Uuid,
+        write_timestamp: Timestamp,
+        intent_keys: &[Key],
+    ) -> StorageResult<()> {
+        let _lock = self.txn_record_lock.lock().unwrap();
+        let mut batch = self.storage.new_write_batch();
+        KVStore::abort_transaction_record_in_batch(&mut batch, transaction_id, write_timestamp)?;
+        for key in intent_keys {
+            batch.delete_mvcc(&create_intent_key(key));
+        }
+        self.storage.write(batch)
+    }
+
+    /**
+     * Aborts the txn record if it's still pending. The txn's intents are left for the
+     * requests that run into them to clean up.
+     */
+    pub fn abort_pending_transaction_record(&self, transaction_id: Uuid) -> StorageResult<()> {
+        let _lock = self.txn_record_lock.lock().unwrap();
+        match self.get_transaction_record(transaction_id) {
+            Some(record) if record.status == TransactionStatus::PENDING => {
+                let mut batch = self.storage.new_write_batch();
+                KVStore::abort_transaction_record_in_batch(
+                    &mut batch,
+                    transaction_id,
+                    record.metadata.write_timestamp,
+                )?;
+                self.storage.write(batch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn abort_transaction_record_in_batch(
+        batch: &mut WriteBatch,
+        transaction_id: Uuid,
+        write_timestamp: Timestamp,
+    ) -> StorageResult<()> {
+        batch.put_transaction_record(
+            &transaction_id,
             &TxnRecord {
                 status: TransactionStatus::ABORTED,
-                metadata: TxnMetadata {
-                    txn_id: transaction_id.clone(),
-                    write_timestamp: write_timestamp,
-                },
+                metadata: TxnMetadata::new(transaction_id, write_timestamp),
+                last_heartbeat: None,
+                ignored_sequences: Vec::new(),
             },
         )
     }
@@ -294,7 +526,59 @@ impl KVStore {
             .unwrap()
     }
 
-    pub fn mvcc_resolve_intent(&self, key: Key, commit_timestamp: Timestamp, txn_id: Uuid) {
+    /**
+     * Replaces the intent with a committed version of the most recent value that
+     * wasn't rolled back. If every write to the key was rolled back, the intent is
+     * just removed.
+     */
+    pub fn mvcc_resolve_intent(
+        &self,
+        key: Key,
+        commit_timestamp: Timestamp,
+        txn_id: Uuid,
+        ignored_sequences: &[RangeInclusive<u64>],
+    ) -> StorageResult<()> {
+        let mut batch = self.storage.new_write_batch();
+        self.resolve_intent_in_batch(&mut batch, key, commit_timestamp, txn_id, ignored_sequences);
+        self.storage.write(batch)
+    }
+
+    /**
+     * Resolves the committed txn's intents on the keys in a single write batch.
+     */
+    pub fn resolve_intents(
+        &self,
+        txn_id: Uuid,
+        commit_timestamp: Timestamp,
+        ignored_sequences: &[RangeInclusive<u64>],
+        keys: &[Key],
+    ) -> StorageResult<()> {
+        let mut batch = self.storage.new_write_batch();
+        for key in keys {
+            self.resolve_intent_in_batch(
+                &mut batch,
+                key.clone(),
+                commit_timestamp,
+                txn_id,
+                ignored_sequences,
+            );
+        }
+        self.storage.write(batch)
+    }
+
+    /**
+     * Adds the resolution of the intent to the batch. The intent is removed and the
+     * committed version is added in the same batch so that a crash can't leave the key
+     * with neither.
+     */
+    fn resolve_intent_in_batch(
+        &self,
+        batch: &mut WriteBatch,
+        key: Key,
+        commit_timestamp: Timestamp,
+        txn_id: Uuid,
+        ignored_sequences: &[RangeInclusive<u64>],
+    ) {
         let intent_key = MVCCKey::create_intent_key(&key);
 
         if let Some(uncommitted_value) = self.get_uncommitted_value(&intent_key) {
@@ -302,13 +586,175 @@ impl KVStore {
                 println!("MVCC intent owned by another transaction. Failed to resolve");
                 return;
             }
-            self.storage.delete_mvcc(&intent_key);
-            let value = uncommitted_value.value;
+            batch.delete_mvcc(&intent_key);
+            if let Some(value) = uncommitted_value.get_visible_value(ignored_sequences) {
+                batch.put_raw_with_mvcc_key(&MVCCKey::new(key, commit_timestamp), value.clone());
+            }
+        }
+    }
 
+    /**
+     * Advances the timestamp of the txn's intent on the key to the timestamp so that
+     * reads beneath the timestamp ignore the intent.
+     */
+    pub fn mvcc_push_intent(
+        &self,
+        key: &Key,
+        txn_id: Uuid,
+        timestamp: Timestamp,
+    ) -> StorageResult<()> {
+        let intent_key = MVCCKey::create_intent_key(key);
+        if let Some(mut uncommitted_value) = self.get_uncommitted_value(&intent_key) {
+            let txn_metadata = &mut uncommitted_value.txn_metadata;
+            if txn_metadata.txn_id != txn_id || txn_metadata.write_timestamp >= timestamp {
+                return Ok(());
+            }
+            txn_metadata.write_timestamp = timestamp;
             self.storage
-                .put_raw_with_mvcc_key(&MVCCKey::new(key, commit_timestamp), value)
-                .unwrap();
+                .put_serialized_with_mvcc_key(&intent_key, uncommitted_value)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Recovers the database after a restart. The coordinators of the txns that were
+     * pending before the restart are gone, so their records are aborted. Then the
+     * intents of committed txns are resolved and the intents of aborted txns are
+     * removed, so that no intent outlives its txn. Recovery is applied in a single
+     * write batch.
+     *
+     * Returns the highest timestamp found in the database, if any.
+     */
+    pub fn recover(&self) -> StorageResult<Option<Timestamp>> {
+        let mut batch = self.storage.new_write_batch();
+        let mut max_timestamp = None;
+        let mut records = HashMap::new();
+        for (txn_id, mut record) in self.storage.get_transaction_records() {
+            if record.status == TransactionStatus::PENDING {
+                KVStore::abort_transaction_record_in_batch(
+                    &mut batch,
+                    txn_id,
+                    record.metadata.write_timestamp,
+                )?;
+                record.status = TransactionStatus::ABORTED;
+            }
+            max_timestamp = max_timestamp.max(Some(record.metadata.write_timestamp));
+            records.insert(txn_id, record);
+        }
+
+        let mut intents = Vec::new();
+        let mut it = MVCCIterator::new(&self.storage, IterOptions { prefix: false });
+        while it.valid() {
+            let current_key = it.current_key();
+            if current_key.is_intent_key() {
+                let txn_metadata = it
+                    .current_value_serialized::<UncommittedValue>()
+                    .txn_metadata;
+                intents.push((current_key.key, txn_metadata));
+            } else {
+                max_timestamp = max_timestamp.max(Some(current_key.timestamp));
+            }
+            it.next();
+        }
+
+        for (key, txn_metadata) in intents {
+            match records.get(&txn_metadata.txn_id) {
+                Some(record) if record.status == TransactionStatus::COMMITTED => {
+                    self.resolve_intent_in_batch(
+                        &mut batch,
+                        key,
+                        record.metadata.write_timestamp,
+                        txn_metadata.txn_id,
+                        &record.ignored_sequences,
+                    );
+                }
+                // the intent of an aborted txn, or of a txn whose record was never written
+                _ => batch.delete_mvcc(&create_intent_key(&key)),
+            }
+        }
+        self.storage.write(batch)?;
+        Ok(max_timestamp)
+    }
+
+    /**
+     * Reads beneath the GC threshold are rejected since the versions they would read
+     * may have been garbage collected.
+     */
+    pub fn get_gc_threshold(&self) -> Option<Timestamp> {
+        self.storage.get_gc_threshold()
+    }
+
+    /**
+     * Garbage collects the MVCC history beneath the threshold. For each key, the most
+     * recent version at or beneath the threshold is kept since reads at the threshold
+     * still see it, and the versions shadowed by it are removed. The kept version is
+     * removed as well if it's a tombstone. Keys with an intent are skipped because the
+     * intent could still be resolved beneath the threshold.
+     *
+     * The txn records of finalized txns at or beneath the threshold are removed once the
+     * txn has no intents left.
+     *
+     * The threshold is published before anything is removed so that reads beneath the
+     * threshold are rejected instead of seeing partial history. The threshold never
+     * moves backwards.
+     *
+     * GC doesn't acquire latches. The iterator reads a snapshot and committed versions
+     * are never modified, so a version that's shadowed in the snapshot stays shadowed.
+     * A write racing with GC lands above the newest committed version of its key, and
+     * an intent missing from the snapshot can only be committed by a request that
+     * checked the threshold before it was published, which is above the shadowed
+     * versions too.
+     */
+    pub fn mvcc_gc(&self, threshold: Timestamp) -> StorageResult<GcStats> {
+        let _lock = self.gc_lock.lock().unwrap();
+        let threshold = self.storage.advance_gc_threshold(threshold)?;
+
+        let mut stats = GcStats::default();
+        let mut batch = self.storage.new_write_batch();
+        // the records are read before the intents so that a record that's finalized by
+        // then can't get new intents after the scan
+        let records = self.storage.get_transaction_records();
+        let mut txns_with_intents = HashSet::new();
+
+        let mut it = MVCCIterator::new(&self.storage, IterOptions { prefix: false });
+        let mut current_key = None;
+        let mut has_intent = false;
+        let mut found_visible_version = false;
+        while it.valid() {
+            let mvcc_key = it.current_key();
+            if current_key.as_ref() != Some(&mvcc_key.key) {
+                current_key = Some(mvcc_key.key.clone());
+                has_intent = false;
+                found_visible_version = false;
+            }
+            if mvcc_key.is_intent_key() {
+                // intents are sorted before the versions of the key
+                has_intent = true;
+                let txn_metadata = it
+                    .current_value_serialized::<UncommittedValue>()
+                    .txn_metadata;
+                txns_with_intents.insert(txn_metadata.txn_id);
+            } else if !has_intent && mvcc_key.timestamp <= threshold {
+                if found_visible_version || is_tombstone(&it.current_value()) {
+                    batch.delete_mvcc(&mvcc_key);
+                    stats.versions_removed += 1;
+                }
+                found_visible_version = true;
+            }
+            it.next();
+        }
+
+        for (txn_id, record) in records {
+            if record.status != TransactionStatus::PENDING
+                && record.metadata.write_timestamp <= threshold
+                && !txns_with_intents.contains(&txn_id)
+            {
+                batch.delete_transaction_record(&txn_id);
+                stats.txn_records_removed += 1;
+            }
         }
+        self.storage.write(batch)?;
+        Ok(stats)
     }
 
     // Debugger method to help collect all MVCCKey-Value pairs
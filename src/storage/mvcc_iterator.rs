@@ -4,7 +4,7 @@ use rocksdb::*;
 use serde::de::DeserializeOwned;
 
 use super::{
-    boxed_byte_to_byte_vec,
+    boxed_byte_to_byte_vec, decode_internal,
     mvcc_key::{decode_mvcc_key, MVCCKey},
     storage::Storage,
     Value,
@@ -99,7 +99,7 @@ impl<'a> MVCCIterator<'a> {
     pub fn current_value_serialized<T: DeserializeOwned>(&mut self) -> T {
         let (_, v) = self.curr_kv.as_ref().unwrap();
         let vec = boxed_byte_to_byte_vec(v);
-        serde_json::from_slice::<T>(&vec).unwrap()
+        decode_internal::<T>(&vec).unwrap()
     }
 
     // Valid returns whether or not the iterator is pointing at a valid entry.
@@ -116,6 +116,9 @@ impl<'a> MVCCIterator<'a> {
     // a smaller timestamp. But because our custom comparator stores
     // the most recent timestamp first, the ordering is opposite to the
     // semantic comparison of timestamps
+    //
+    // If there is no such entry for the key, the iterator stops at the first
+    // entry of the next key (or becomes invalid) and false is returned.
     pub fn seek_ge(&mut self, key: &MVCCKey) -> bool {
         let mut found_valid = false;
         if !self.valid() {
@@ -125,6 +128,9 @@ impl<'a> MVCCIterator<'a> {
         if curr_key.key == key.key && curr_key.timestamp <= key.timestamp {
             return true;
         }
+        if curr_key.key > key.key {
+            return false;
+        }
         loop {
             let peeked = self.it.peek();
 
@@ -136,14 +142,24 @@ impl<'a> MVCCIterator<'a> {
                             found_valid = true;
                             self.next();
                             break;
+                        } else if peeked_key.key > key.key {
+                            self.next();
+                            break;
                         } else {
                             self.next();
                             continue;
                         }
                     }
-                    _ => break,
+                    _ => {
+                        self.next();
+                        break;
+                    }
                 },
-                None => break,
+                None => {
+                    // there is no entry >= key, so the iterator becomes invalid
+                    self.next();
+                    break;
+                }
             }
         }
         found_valid
@@ -45,11 +45,6 @@ impl MVCCKey {
         self.timestamp.is_intent_timestamp()
     }
 
-    pub fn to_string(&self) -> String {
-        let encoded = encode_mvcc_key(self);
-        String::from_utf8(encoded).unwrap()
-    }
-
     pub fn to_encoded(&self) -> Vec<u8> {
         encode_mvcc_key(self)
     }
@@ -70,59 +65,74 @@ pub fn create_intent_key(key: &Key) -> MVCCKey {
     }
 }
 
-// TODO: byte-buffer?
 // inspired by CockroachDB's encodeMVCCKey: https://github.com/cockroachdb/cockroach/blob/master/pkg/storage/mvcc_key.go#L161
 /**
  * encoded key takes the form:
- * [key] [wall_time] [logical_time] [hardcoded mvcc_key]
- * key: (variable length)
- * wall_time: uint64
- * logical_time: uint32
- * hardcoded_mvcc (4 bytes): "mvcc" - just a hack for now to tell whether a key is mvcc
+ * [escaped key] [terminator] [timestamp]
+ * escaped key: (variable length) the key with every 0x00 byte escaped as 0x00 0xFF
+ * terminator (2 bytes): 0x00 0x01, which can't appear in the escaped key
+ * timestamp: empty for intent keys. Otherwise the inverted wall_time (uint64) and
+ *   logical_time (uint32) in big-endian
+ *
+ * The encoding preserves the order of MVCCKeys under the default bytewise comparator:
+ * keys are sorted by key since the terminator sorts before any escaped byte, the intent
+ * of a key is sorted before its versions, and the inverted timestamps sort the most
+ * recent version first.
  */
 pub fn encode_mvcc_key(mvcc_key: &MVCCKey) -> Vec<u8> {
-    let mut key_vec = mvcc_key.key.to_vec();
-    let timestamp_vec = encode_timestamp(mvcc_key.timestamp);
-    key_vec.extend(timestamp_vec);
-    let hardcoded = "mvcc".as_bytes();
-    key_vec.extend(hardcoded);
-    key_vec
+    let mut encoded = Vec::with_capacity(mvcc_key.key.len() + 2 + ENCODED_TIMESTAMP_LEN);
+    for byte in mvcc_key.key.iter() {
+        encoded.push(*byte);
+        if *byte == ESCAPE {
+            encoded.push(ESCAPED_NULL);
+        }
+    }
+    encoded.extend(TERMINATOR);
+    if !mvcc_key.is_intent_key() {
+        encoded.extend(encode_timestamp(mvcc_key.timestamp));
+    }
+    encoded
 }
 
-pub fn encode_timestamp(timestamp: Timestamp) -> Vec<u8> {
-    let mut wall_time_bytes = timestamp.wall_time.to_le_bytes().to_vec();
-    let logical_time_bytes = timestamp.logical_time.to_le_bytes().to_vec();
-    wall_time_bytes.extend(logical_time_bytes);
-    wall_time_bytes
+const ESCAPE: u8 = 0x00;
+const ESCAPED_NULL: u8 = 0xFF;
+const TERMINATOR: [u8; 2] = [ESCAPE, 0x01];
+const ENCODED_TIMESTAMP_LEN: usize = 12; // 8 + 4 bytes
+
+fn encode_timestamp(timestamp: Timestamp) -> Vec<u8> {
+    let mut encoded = (!timestamp.wall_time).to_be_bytes().to_vec();
+    encoded.extend((!timestamp.logical_time).to_be_bytes());
+    encoded
 }
 
-pub fn decode_mvcc_key(encoded_mvcc_key: &Vec<u8>) -> Option<MVCCKey> {
-    // just a hack
-    if encoded_mvcc_key.len() < 16 {
-        return None;
-    }
-    let hardcoded_len = 4;
-    let encoded_mvcc_key_len = encoded_mvcc_key.len();
-    let hardcoded_start = encoded_mvcc_key_len - 4;
-    let encoded_timestamp = encoded_mvcc_key[hardcoded_start..].to_owned();
-    if encoded_timestamp != "mvcc".as_bytes() {
-        return None;
-    }
-
-    let timestamp_len = 12; // 4 + 8 bytes
-    let key_end = encoded_mvcc_key_len - timestamp_len - hardcoded_len;
-    let encoded_key = encoded_mvcc_key[..key_end].to_owned();
-    let encoded_timestamp = encoded_mvcc_key[key_end..hardcoded_start].to_owned();
-    let timestamp = decode_timestamp(encoded_timestamp);
-    Some(MVCCKey {
-        key: encoded_key,
-        timestamp: timestamp,
-    })
+/**
+ * Returns None if the bytes aren't an encoded MVCCKey.
+ */
+pub fn decode_mvcc_key(encoded_mvcc_key: &[u8]) -> Option<MVCCKey> {
+    let mut key = Vec::with_capacity(encoded_mvcc_key.len());
+    let mut bytes = encoded_mvcc_key.iter();
+    loop {
+        match *bytes.next()? {
+            ESCAPE => match *bytes.next()? {
+                ESCAPED_NULL => key.push(ESCAPE),
+                byte if byte == TERMINATOR[1] => break,
+                _ => return None,
+            },
+            byte => key.push(byte),
+        }
+    }
+    let encoded_timestamp = bytes.as_slice();
+    let timestamp = match encoded_timestamp.len() {
+        0 => get_intent_timestamp(),
+        ENCODED_TIMESTAMP_LEN => decode_timestamp(encoded_timestamp),
+        _ => return None,
+    };
+    Some(MVCCKey { key, timestamp })
 }
 
-fn decode_timestamp(encoded_timestamp: Vec<u8>) -> Timestamp {
-    let wall_time = u64::from_le_bytes(encoded_timestamp[0..8].try_into().unwrap());
-    let logical_time = u32::from_le_bytes(encoded_timestamp[8..12].try_into().unwrap());
+fn decode_timestamp(encoded_timestamp: &[u8]) -> Timestamp {
+    let wall_time = !u64::from_be_bytes(encoded_timestamp[0..8].try_into().unwrap());
+    let logical_time = !u32::from_be_bytes(encoded_timestamp[8..12].try_into().unwrap());
 
     Timestamp {
         wall_time,
@@ -150,6 +160,40 @@ mod tests {
         assert_eq!(mvcc_key, decoded.unwrap());
     }
 
+    #[test]
+    fn encode_decode_arbitrary_bytes() {
+        for key in [
+            Vec::from([0xFF, 0x00, 0x01, 0x00]),
+            "foomvcc".as_bytes().to_vec(),
+            Vec::new(),
+        ] {
+            for timestamp in [Timestamp::intent_timestamp(), Timestamp::new(u64::MAX, 200)] {
+                let mvcc_key = MVCCKey::new(key.clone(), timestamp);
+                assert_eq!(decode_mvcc_key(&encode_mvcc_key(&mvcc_key)), Some(mvcc_key));
+            }
+        }
+        assert_eq!(decode_mvcc_key("foo".as_bytes()), None);
+    }
+
+    #[test]
+    fn encoding_preserves_order() {
+        // sorted by key, then intent first, then the most recent version first
+        let sorted = [
+            MVCCKey::create_intent_key_with_str("a"),
+            MVCCKey::new(Vec::from([b'a']), Timestamp::new(300, 1)),
+            MVCCKey::new(Vec::from([b'a']), Timestamp::new(300, 0)),
+            MVCCKey::new(Vec::from([b'a']), Timestamp::new(2, 5)),
+            MVCCKey::new(Vec::from([b'a', 0x00]), Timestamp::new(1, 0)),
+            MVCCKey::new(Vec::from([b'a', 0x00, 0x00]), Timestamp::new(1, 0)),
+            MVCCKey::new(Vec::from([b'a', 0x01]), Timestamp::new(1, 0)),
+            MVCCKey::new(Vec::from([b'a', 0xFF]), Timestamp::new(1, 0)),
+            MVCCKey::new(Vec::from([b'b']), Timestamp::new(1, 0)),
+        ];
+        for pair in sorted.windows(2) {
+            assert!(encode_mvcc_key(&pair[0]) < encode_mvcc_key(&pair[1]));
+        }
+    }
+
     mod order {
         use crate::{
             hlc::timestamp::Timestamp,
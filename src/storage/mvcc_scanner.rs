@@ -1,6 +1,7 @@
 use crate::{db::db::TxnLink, hlc::timestamp::Timestamp};
 
 use super::{
+    is_tombstone,
     mvcc_iterator::MVCCIterator,
     mvcc_key::{create_intent_key, MVCCKey},
     txn::{TxnIntent, UncommittedValue},
@@ -20,6 +21,13 @@ pub struct MVCCScanner<'a> {
     // end of the scan (doesn't contain MVCC timestamp)
     pub end_key: Option<Key>,
 
+    // whether the end_key is excluded from the scan. Defaults to false.
+    pub end_key_exclusive: bool,
+
+    // whether tombstones are added to the results. Defaults to false, which
+    // means deleted keys are treated as not found.
+    pub tombstones: bool,
+
     // Timestamp that MVCCScan/MVCCGet was called
     pub timestamp: Timestamp,
 
@@ -51,6 +59,8 @@ impl<'a> MVCCScanner<'a> {
             it,
             start_key: start_key,
             end_key: end_key,
+            end_key_exclusive: false,
+            tombstones: false,
             timestamp,
             found_intents: Vec::new(),
             results: Vec::new(),
@@ -71,22 +81,26 @@ impl<'a> MVCCScanner<'a> {
                 println!("invalid!");
                 return;
             }
+            let current_key = self.it.current_key().key;
             match &self.end_key {
                 Some(end_key) => {
-                    if &self.it.current_key().key > end_key {
+                    if &current_key > end_key || (self.end_key_exclusive && &current_key == end_key)
+                    {
                         return;
                     }
                 }
                 None => {
                     // if there is no end_key, then the end_key defaults to start_key
-                    if self.it.current_key().key > self.start_key {
+                    if current_key > self.start_key {
                         return;
                     }
                 }
             }
             self.get_current_key();
-            self.advance_to_next_key();
-            // advance to next one
+            // reading an older version could have already moved the iterator to the next key
+            if self.it.valid() && self.it.current_key().key == current_key {
+                self.advance_to_next_key();
+            }
         }
     }
 
@@ -108,51 +122,50 @@ impl<'a> MVCCScanner<'a> {
             let current_value = self.it.current_value_serialized::<UncommittedValue>();
 
             if let Some(scanner_transaction) = &self.txn {
-                if current_value.txn_metadata.txn_id == scanner_transaction.read().unwrap().txn_id {
+                let scanner_transaction = scanner_transaction.read().unwrap();
+                if current_value.txn_metadata.txn_id == scanner_transaction.txn_id {
                     // TODO: Resolve based on epoch
-                    self.found_intents.push((
-                        TxnIntent {
-                            txn_meta: current_value.txn_metadata,
-                            key: current_key.key.clone(),
-                        },
-                        current_value.value,
-                    ));
-                } else {
-                    self.found_intents.push((
-                        TxnIntent {
-                            txn_meta: current_value.txn_metadata,
-                            key: current_key.key.clone(),
-                        },
-                        current_value.value,
-                    ));
+                    // the transaction reads its own uncommitted write unless it was rolled back
+                    let visible_value = current_value
+                        .get_visible_value(&scanner_transaction.ignored_sequences)
+                        .cloned();
+                    drop(scanner_transaction);
+                    return match visible_value {
+                        Some(value) => self.add_result(current_key, value),
+                        None => self.seek_older_version(current_key.key, self.timestamp),
+                    };
                 }
-            } else {
-                self.found_intents.push((
-                    TxnIntent {
-                        txn_meta: current_value.txn_metadata,
-                        key: current_key.key.clone(),
-                    },
-                    current_value.value,
-                ));
             }
 
+            let intent_timestamp = current_value.txn_metadata.write_timestamp;
+            self.found_intents.push((
+                TxnIntent {
+                    txn_meta: current_value.txn_metadata,
+                    key: current_key.key.clone(),
+                },
+                current_value.value,
+            ));
+
+            // An intent with a higher timestamp doesn't affect the read,
+            // so the most recent committed version is read instead.
+            if self.timestamp < intent_timestamp {
+                return self.seek_older_version(current_key.key.to_owned(), self.timestamp);
+            }
             return false;
         } else {
             let key_timestamp = current_key.timestamp;
 
             if self.timestamp > key_timestamp {
                 // the scanner's timestamp is greater, so just add
-                self.results
-                    .push((self.it.current_key(), self.it.current_value()));
-                return true;
+                let value = self.it.current_value();
+                self.add_result(current_key, value)
             } else if self.timestamp < key_timestamp {
                 // seek to older version
                 return self.seek_older_version(current_key.key.to_owned(), self.timestamp);
             } else {
                 // the scanner's timestamp is sufficient (equal), so just add
-                self.results
-                    .push((self.it.current_key(), self.it.current_value()));
-                return true;
+                let value = self.it.current_value();
+                self.add_result(current_key, value)
             }
         }
     }
@@ -170,8 +183,8 @@ impl<'a> MVCCScanner<'a> {
         if is_valid {
             let current_key = self.it.current_key();
             if current_key.key == key {
-                self.results.push((current_key, self.it.current_value()));
-                return true;
+                let value = self.it.current_value();
+                self.add_result(current_key, value)
             } else {
                 return false;
             }
@@ -180,6 +193,18 @@ impl<'a> MVCCScanner<'a> {
         }
     }
 
+    /**
+     * Adds the key-value pair to the result set unless the value is a tombstone
+     * and tombstones are not requested. Returns true if added.
+     */
+    fn add_result(&mut self, key: MVCCKey, value: Value) -> bool {
+        if is_tombstone(&value) && !self.tombstones {
+            return false;
+        }
+        self.results.push((key, value));
+        true
+    }
+
     pub fn advance_to_next_key(&mut self) -> () {
         if !self.it.valid() {
             return;
@@ -320,6 +320,49 @@ mod tests {
             assert_eq!(scanner.found_intents, vec);
         }
 
+        #[test]
+        fn start_key_not_found_and_intent_with_higher_timestamp() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let committed_timestamp = Timestamp::new(2, 0);
+            let key1 = "banana";
+            kv_store
+                .mvcc_put(str_to_key(key1), Some(committed_timestamp), None, 12)
+                .unwrap();
+
+            let txn = Txn::new_link(Uuid::new_v4(), Timestamp::new(12, 0));
+            kv_store
+                .mvcc_put(str_to_key(key1), None, Some(txn.clone()), 13)
+                .unwrap();
+
+            let key2 = "cherry";
+            kv_store
+                .mvcc_put(str_to_key(key2), Some(committed_timestamp), None, 14)
+                .unwrap();
+
+            let iterator = MVCCIterator::new(&kv_store.storage, IterOptions { prefix: true });
+            let mut scanner = MVCCScanner::new(
+                iterator,
+                str_to_key("apple"),
+                Some(str_to_key(key2)),
+                Timestamp::new(5, 0),
+                5,
+                None,
+            );
+            scanner.end_key_exclusive = true;
+            scanner.scan();
+            assert_eq!(
+                scanner.results,
+                Vec::from([(
+                    MVCCKey {
+                        key: str_to_key(key1),
+                        timestamp: committed_timestamp,
+                    },
+                    serialized_to_value(12),
+                )])
+            );
+            assert_eq!(scanner.found_intents.len(), 1);
+        }
+
         // #[test]
         // fn multiple_keys() {
         //     let kv_store = KVStore::new_cleaned("./tmp/data");
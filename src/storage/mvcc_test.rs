@@ -1,5 +1,7 @@
 #[cfg(test)]
 mod tests {
+    use std::time::{Duration, SystemTime};
+
     use uuid::Uuid;
 
     use crate::{
@@ -19,18 +21,201 @@ mod tests {
             wall_time: 0,
             logical_time: 0,
         };
-        kv_store.create_pending_transaction_record(transaction_id, write_timestamp);
+        kv_store
+            .create_pending_transaction_record(transaction_id, write_timestamp)
+            .unwrap();
         let transaction_record = kv_store.get_transaction_record(transaction_id).unwrap();
+        assert_eq!(transaction_record.status, TransactionStatus::PENDING);
         assert_eq!(
-            transaction_record,
-            TxnRecord {
-                status: TransactionStatus::PENDING,
-                metadata: TxnMetadata {
-                    txn_id: transaction_id.clone(),
-                    write_timestamp
-                }
+            transaction_record.metadata,
+            TxnMetadata::new(transaction_id, write_timestamp)
+        );
+        assert!(!transaction_record.is_expired());
+    }
+
+    #[test]
+    fn heartbeat_transaction_record() {
+        let kv_store = KVStore::new_cleaned(&create_temp_dir());
+        let transaction_id = Uuid::new_v4();
+        let write_timestamp = Timestamp::new(10, 0);
+        kv_store
+            .put_transaction_record(
+                transaction_id,
+                &TxnRecord {
+                    status: TransactionStatus::PENDING,
+                    metadata: TxnMetadata::new(transaction_id, write_timestamp),
+                    last_heartbeat: Some(SystemTime::now() - Duration::from_secs(60)),
+                    ignored_sequences: Vec::new(),
+                },
+            )
+            .unwrap();
+        let transaction_record = kv_store.get_transaction_record(transaction_id).unwrap();
+        assert!(transaction_record.is_expired());
+
+        assert!(kv_store
+            .heartbeat_transaction_record(transaction_id)
+            .unwrap());
+        let transaction_record = kv_store.get_transaction_record(transaction_id).unwrap();
+        assert!(!transaction_record.is_expired());
+
+        // finalized txns aren't heartbeated
+        kv_store
+            .commit_transaction_record(transaction_id, write_timestamp, &[])
+            .unwrap();
+        assert!(!kv_store
+            .heartbeat_transaction_record(transaction_id)
+            .unwrap());
+    }
+
+    #[test]
+    fn push_and_commit_transaction_record() {
+        let kv_store = KVStore::new_cleaned(&create_temp_dir());
+        let transaction_id = Uuid::new_v4();
+        let write_timestamp = Timestamp::new(10, 0);
+        kv_store
+            .create_pending_transaction_record(transaction_id, write_timestamp)
+            .unwrap();
+
+        let pushed_timestamp = Timestamp::new(12, 0);
+        let record = kv_store
+            .push_transaction_record(transaction_id, pushed_timestamp)
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.metadata.write_timestamp, pushed_timestamp);
+
+        // the txn can't commit beneath the pushed timestamp
+        assert_eq!(
+            kv_store.commit_transaction_record(transaction_id, write_timestamp, &[]),
+            Ok(Some(pushed_timestamp))
+        );
+        assert_eq!(
+            kv_store.commit_transaction_record(transaction_id, pushed_timestamp, &[]),
+            Ok(None)
+        );
+
+        // finalized txns can't be pushed
+        let record = kv_store
+            .push_transaction_record(transaction_id, Timestamp::new(14, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.status, TransactionStatus::COMMITTED);
+        assert_eq!(record.metadata.write_timestamp, pushed_timestamp);
+    }
+
+    mod finalize_transaction {
+        use uuid::Uuid;
+
+        use crate::{
+            helpers::test_helpers::create_temp_dir,
+            hlc::timestamp::Timestamp,
+            storage::{
+                mvcc::{KVStore, MVCCGetParams},
+                serialized_to_value, str_to_key,
+                txn::{TransactionStatus, Txn},
+            },
+        };
+
+        #[test]
+        fn resolve_intents_of_committed_transaction() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let txn_id = Uuid::new_v4();
+            let txn = Txn::new_link(txn_id, Timestamp::new(10, 0));
+            kv_store
+                .create_pending_transaction_record(txn_id, Timestamp::new(10, 0))
+                .unwrap();
+            let keys = Vec::from([str_to_key("apple"), str_to_key("banana")]);
+            for key in keys.iter() {
+                kv_store
+                    .mvcc_put(key.clone(), None, Some(txn.clone()), 12)
+                    .unwrap();
+            }
+
+            kv_store
+                .commit_transaction_record(txn_id, Timestamp::new(11, 0), &[])
+                .unwrap();
+            kv_store
+                .resolve_intents(txn_id, Timestamp::new(11, 0), &[], &keys)
+                .unwrap();
+            assert_eq!(
+                kv_store.get_transaction_record(txn_id).unwrap().status,
+                TransactionStatus::COMMITTED
+            );
+            for key in keys.iter() {
+                let res = kv_store.mvcc_get(
+                    key,
+                    Timestamp::new(11, 0),
+                    MVCCGetParams { transaction: None },
+                );
+                assert_eq!(
+                    res.value.map(|(_, value)| value),
+                    Some(serialized_to_value(12))
+                );
+                assert_eq!(res.intent, None);
+            }
+        }
+
+        #[test]
+        fn abort_transaction_removes_intents() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let txn_id = Uuid::new_v4();
+            let txn = Txn::new_link(txn_id, Timestamp::new(10, 0));
+            kv_store
+                .create_pending_transaction_record(txn_id, Timestamp::new(10, 0))
+                .unwrap();
+            let key = str_to_key("apple");
+            kv_store
+                .mvcc_put(key.clone(), None, Some(txn.clone()), 12)
+                .unwrap();
+
+            kv_store
+                .abort_transaction(txn_id, Timestamp::new(10, 0), std::slice::from_ref(&key))
+                .unwrap();
+            assert_eq!(
+                kv_store.get_transaction_record(txn_id).unwrap().status,
+                TransactionStatus::ABORTED
+            );
+            let res = kv_store.mvcc_get(
+                &key,
+                Timestamp::new(11, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(res.value, None);
+            assert_eq!(res.intent, None);
+        }
+
+        #[test]
+        fn abort_pending_transaction_record_skips_finalized_records() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let pending_txn_id = Uuid::new_v4();
+            kv_store
+                .create_pending_transaction_record(pending_txn_id, Timestamp::new(10, 0))
+                .unwrap();
+            let committed_txn_id = Uuid::new_v4();
+            kv_store
+                .create_pending_transaction_record(committed_txn_id, Timestamp::new(10, 0))
+                .unwrap();
+            kv_store
+                .commit_transaction_record(committed_txn_id, Timestamp::new(10, 0), &[])
+                .unwrap();
+
+            for txn_id in [pending_txn_id, committed_txn_id, Uuid::new_v4()] {
+                kv_store.abort_pending_transaction_record(txn_id).unwrap();
             }
-        )
+            assert_eq!(
+                kv_store
+                    .get_transaction_record(pending_txn_id)
+                    .unwrap()
+                    .status,
+                TransactionStatus::ABORTED
+            );
+            assert_eq!(
+                kv_store
+                    .get_transaction_record(committed_txn_id)
+                    .unwrap()
+                    .status,
+                TransactionStatus::COMMITTED
+            );
+        }
     }
 
     mod mvcc_put {
@@ -78,7 +263,9 @@ mod tests {
             };
             let txn = Txn::new_link(txn1_id, timestamp);
 
-            kv_store.create_pending_transaction_record(txn1_id, timestamp.to_owned());
+            kv_store
+                .create_pending_transaction_record(txn1_id, timestamp.to_owned())
+                .unwrap();
             let _current_keys = kv_store.collect_all_mvcc_kvs();
 
             kv_store
@@ -98,6 +285,32 @@ mod tests {
             let res = kv_store.mvcc_put(str_to_key(key), None, Some(second_txn.clone()), 12);
             assert!(res.is_err());
         }
+
+        #[test]
+        fn overwrite_intent_of_aborted_transaction() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let key = str_to_key("foo");
+            let aborted_txn_id = Uuid::new_v4();
+            let aborted_txn = Txn::new_link(aborted_txn_id, Timestamp::new(10, 0));
+            kv_store
+                .create_pending_transaction_record(aborted_txn_id, Timestamp::new(10, 0))
+                .unwrap();
+            kv_store
+                .mvcc_put(key.clone(), None, Some(aborted_txn), 12)
+                .unwrap();
+            // the record is aborted without removing the intent
+            kv_store
+                .abort_transaction(aborted_txn_id, Timestamp::new(10, 0), &[])
+                .unwrap();
+
+            let txn_id = Uuid::new_v4();
+            let txn = Txn::new_link(txn_id, Timestamp::new(11, 0));
+            kv_store.mvcc_put(key.clone(), None, Some(txn), 13).unwrap();
+            let intent = kv_store
+                .get_uncommitted_value(&MVCCKey::create_intent_key(&key))
+                .unwrap();
+            assert_eq!(intent.txn_metadata.txn_id, txn_id);
+        }
     }
 
     mod mvcc_get {
@@ -133,7 +346,10 @@ mod tests {
             let most_recent_value = 11;
             kv_store
                 .storage
-                .put_serialized_with_mvcc_key(&most_recent_key.to_owned(), most_recent_value)
+                .put_raw_with_mvcc_key(
+                    &most_recent_key.to_owned(),
+                    serialized_to_value(most_recent_value),
+                )
                 .unwrap();
 
             kv_store
@@ -160,6 +376,36 @@ mod tests {
             );
         }
 
+        #[test]
+        fn get_pushed_intent() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let key = str_to_key("foo");
+            let txn_id = Uuid::new_v4();
+            let timestamp = Timestamp::new(10, 0);
+            let txn = Txn::new_link(txn_id, timestamp);
+            kv_store
+                .mvcc_put(key.clone(), None, Some(txn.clone()), 12)
+                .unwrap();
+
+            let read_timestamp = Timestamp::new(11, 0);
+            let get_intent_timestamp = || {
+                let res =
+                    kv_store.mvcc_get(&key, read_timestamp, MVCCGetParams { transaction: None });
+                res.intent.unwrap().0.txn_meta.write_timestamp
+            };
+            assert_eq!(get_intent_timestamp(), timestamp);
+
+            let pushed_timestamp = read_timestamp.next_logical_timestamp();
+            kv_store
+                .mvcc_push_intent(&key, txn_id, pushed_timestamp)
+                .unwrap();
+            assert_eq!(get_intent_timestamp(), pushed_timestamp);
+
+            // intents aren't pushed backwards
+            kv_store.mvcc_push_intent(&key, txn_id, timestamp).unwrap();
+            assert_eq!(get_intent_timestamp(), pushed_timestamp);
+        }
+
         #[test]
         fn get_intent() {
             let kv_store = KVStore::new_cleaned(&create_temp_dir());
@@ -172,7 +418,9 @@ mod tests {
             };
             let txn = Txn::new_link(txn1_id, timestamp);
 
-            kv_store.create_pending_transaction_record(txn1_id, timestamp);
+            kv_store
+                .create_pending_transaction_record(txn1_id, timestamp)
+                .unwrap();
 
             kv_store
                 .mvcc_put(str_to_key(key), None, Some(txn.clone()), 12)
@@ -188,10 +436,7 @@ mod tests {
                 res.intent,
                 Some((
                     TxnIntent {
-                        txn_meta: TxnMetadata {
-                            txn_id: txn1_id,
-                            write_timestamp: timestamp,
-                        },
+                        txn_meta: TxnMetadata::new(txn1_id, timestamp),
                         key: str_to_key(key),
                     },
                     serialized_to_value(12)
@@ -243,7 +488,9 @@ mod tests {
 
             let commit_timestamp = Timestamp::new(12, 0);
 
-            kv_store.mvcc_resolve_intent(key.clone(), commit_timestamp, txn_id);
+            kv_store
+                .mvcc_resolve_intent(key.clone(), commit_timestamp, txn_id, &[])
+                .unwrap();
 
             let uncommitted_value_option_2 = kv_store
                 .storage
@@ -292,7 +539,9 @@ mod tests {
 
             let txn_2_id = Uuid::new_v4();
 
-            kv_store.mvcc_resolve_intent(key.clone(), commit_timestamp, txn_2_id);
+            kv_store
+                .mvcc_resolve_intent(key.clone(), commit_timestamp, txn_2_id, &[])
+                .unwrap();
 
             // uncommitted intent not deleted as it's owned by another txn
             let uncommitted_value_option_2 = kv_store
@@ -301,5 +550,354 @@ mod tests {
                 .unwrap();
             assert!(uncommitted_value_option_2.is_some());
         }
+
+        #[test]
+        fn resolve_intent_ignores_rolled_back_writes() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let timestamp = Timestamp::new(10, 10);
+            let key = str_to_key("apple");
+            let txn_id = Uuid::new_v4();
+            let txn = Txn::new_link(txn_id, timestamp);
+            kv_store
+                .create_pending_transaction_record(txn_id, timestamp)
+                .unwrap();
+
+            kv_store
+                .mvcc_put(key.clone(), None, Some(txn.clone()), 1)
+                .unwrap();
+            kv_store
+                .mvcc_put(key.clone(), None, Some(txn.clone()), 2)
+                .unwrap();
+            let uncommitted_value = kv_store
+                .storage
+                .get_serialized_with_mvcc_key::<UncommittedValue>(&create_intent_key(&key))
+                .unwrap()
+                .unwrap();
+            assert_eq!(uncommitted_value.sequence, 2);
+            assert_eq!(uncommitted_value.intent_history.len(), 1);
+
+            let commit_timestamp = Timestamp::new(12, 0);
+            kv_store
+                .mvcc_resolve_intent(key.clone(), commit_timestamp, txn_id, &[2..=2])
+                .unwrap();
+            let resolved_value = kv_store
+                .storage
+                .get_serialized_with_mvcc_key::<i32>(&MVCCKey {
+                    key: key.clone(),
+                    timestamp: commit_timestamp,
+                })
+                .unwrap();
+            assert_eq!(resolved_value, Some(1));
+        }
+    }
+
+    mod mvcc_put_committed {
+        use uuid::Uuid;
+
+        use crate::{
+            helpers::test_helpers::create_temp_dir,
+            hlc::timestamp::Timestamp,
+            storage::{
+                mvcc::{KVStore, MVCCGetParams, MVCCWriteError},
+                serialized_to_value, str_to_key,
+                txn::Txn,
+            },
+        };
+
+        #[test]
+        fn nothing_is_written_if_a_key_has_an_intent() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let txn = Txn::new_link(Uuid::new_v4(), Timestamp::new(10, 0));
+            kv_store
+                .mvcc_put(str_to_key("banana"), None, Some(txn.clone()), 12)
+                .unwrap();
+
+            let writes = Vec::from([
+                (str_to_key("apple"), serialized_to_value(13)),
+                (str_to_key("banana"), serialized_to_value(14)),
+            ]);
+            match kv_store.mvcc_put_committed(&writes, Timestamp::new(11, 0)) {
+                Err(MVCCWriteError::WriteIntentError(err)) => assert_eq!(
+                    err.intent,
+                    txn.read().unwrap().to_intent(str_to_key("banana"))
+                ),
+                _ => panic!("expected a write intent error"),
+            }
+            let res = kv_store.mvcc_get(
+                &str_to_key("apple"),
+                Timestamp::new(11, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(res.value, None);
+
+            // committed versions are written without intents
+            let writes = Vec::from([(str_to_key("apple"), serialized_to_value(13))]);
+            kv_store
+                .mvcc_put_committed(&writes, Timestamp::new(11, 0))
+                .unwrap();
+            let res = kv_store.mvcc_get(
+                &str_to_key("apple"),
+                Timestamp::new(11, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(
+                res.value.map(|(_, value)| value),
+                Some(serialized_to_value(13))
+            );
+            assert_eq!(res.intent, None);
+        }
+    }
+
+    mod mvcc_put_tombstone {
+        use uuid::Uuid;
+
+        use crate::{
+            helpers::test_helpers::create_temp_dir,
+            hlc::timestamp::Timestamp,
+            storage::{
+                mvcc::{KVStore, MVCCGetParams},
+                serialized_to_value, str_to_key,
+                txn::Txn,
+            },
+        };
+
+        #[test]
+        fn resolved_tombstone_hides_older_versions() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let key = str_to_key("apple");
+            kv_store
+                .mvcc_put(key.clone(), Some(Timestamp::new(5, 0)), None, 12)
+                .unwrap();
+
+            let txn_id = Uuid::new_v4();
+            let txn = Txn::new_link(txn_id, Timestamp::new(10, 0));
+            kv_store
+                .mvcc_put_tombstone(key.clone(), None, Some(txn.clone()))
+                .unwrap();
+
+            // the txn doesn't see the key it deleted
+            let res = kv_store.mvcc_get(
+                &key,
+                Timestamp::new(10, 0),
+                MVCCGetParams {
+                    transaction: Some(txn),
+                },
+            );
+            assert_eq!(res.value, None);
+            assert_eq!(res.intent, None);
+
+            kv_store
+                .mvcc_resolve_intent(key.clone(), Timestamp::new(10, 0), txn_id, &[])
+                .unwrap();
+
+            let res = kv_store.mvcc_get(
+                &key,
+                Timestamp::new(12, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(res.value, None);
+
+            let res = kv_store.mvcc_get(
+                &key,
+                Timestamp::new(8, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(
+                res.value.map(|(_, value)| value),
+                Some(serialized_to_value(12))
+            );
+        }
+    }
+
+    mod recover {
+        use uuid::Uuid;
+
+        use crate::{
+            helpers::test_helpers::create_temp_dir,
+            hlc::timestamp::Timestamp,
+            storage::{
+                mvcc::{KVStore, MVCCGetParams},
+                serialized_to_value, str_to_key,
+                txn::{TransactionStatus, Txn},
+            },
+        };
+
+        #[test]
+        fn resolves_intents_of_finalized_txns_after_reopening() {
+            let path = create_temp_dir();
+            let committed_key = str_to_key("apple");
+            let pending_key = str_to_key("banana");
+            let committed_txn_id = Uuid::new_v4();
+            let pending_txn_id = Uuid::new_v4();
+            {
+                let kv_store = KVStore::new_cleaned(&path);
+                let committed_txn = Txn::new_link(committed_txn_id, Timestamp::new(10, 0));
+                kv_store
+                    .create_pending_transaction_record(committed_txn_id, Timestamp::new(10, 0))
+                    .unwrap();
+                kv_store
+                    .mvcc_put(committed_key.clone(), None, Some(committed_txn), 12)
+                    .unwrap();
+                // the txn committed but its intent wasn't resolved before the restart
+                kv_store
+                    .commit_transaction_record(committed_txn_id, Timestamp::new(15, 0), &[])
+                    .unwrap();
+
+                let pending_txn = Txn::new_link(pending_txn_id, Timestamp::new(11, 0));
+                kv_store
+                    .create_pending_transaction_record(pending_txn_id, Timestamp::new(11, 0))
+                    .unwrap();
+                kv_store
+                    .mvcc_put(pending_key.clone(), None, Some(pending_txn), 13)
+                    .unwrap();
+            }
+
+            let kv_store = KVStore::new(&path);
+            assert_eq!(kv_store.recover(), Ok(Some(Timestamp::new(15, 0))));
+            assert_eq!(
+                kv_store
+                    .get_transaction_record(pending_txn_id)
+                    .unwrap()
+                    .status,
+                TransactionStatus::ABORTED
+            );
+
+            let res = kv_store.mvcc_get(
+                &committed_key,
+                Timestamp::new(15, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(
+                res.value.map(|(_, value)| value),
+                Some(serialized_to_value(12))
+            );
+            assert_eq!(res.intent, None);
+
+            let res = kv_store.mvcc_get(
+                &pending_key,
+                Timestamp::new(15, 0),
+                MVCCGetParams { transaction: None },
+            );
+            assert_eq!(res.value, None);
+            assert_eq!(res.intent, None);
+        }
+    }
+
+    mod mvcc_gc {
+        use uuid::Uuid;
+
+        use crate::{
+            helpers::test_helpers::create_temp_dir,
+            hlc::timestamp::Timestamp,
+            storage::{
+                mvcc::{GcStats, KVStore},
+                mvcc_key::MVCCKey,
+                str_to_key,
+                txn::Txn,
+            },
+        };
+
+        #[test]
+        fn removes_versions_shadowed_beneath_threshold() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let apple = str_to_key("apple");
+            for (wall_time, value) in [(1, 10), (3, 11), (5, 12), (7, 13)] {
+                kv_store
+                    .mvcc_put(
+                        apple.clone(),
+                        Some(Timestamp::new(wall_time, 0)),
+                        None,
+                        value,
+                    )
+                    .unwrap();
+            }
+            // the deleted key disappears entirely
+            let banana = str_to_key("banana");
+            kv_store
+                .mvcc_put(banana.clone(), Some(Timestamp::new(1, 0)), None, 14)
+                .unwrap();
+            kv_store
+                .mvcc_put_tombstone(banana.clone(), Some(Timestamp::new(2, 0)), None)
+                .unwrap();
+
+            let stats = kv_store.mvcc_gc(Timestamp::new(5, 0)).unwrap();
+            assert_eq!(
+                stats,
+                GcStats {
+                    versions_removed: 4,
+                    txn_records_removed: 0
+                }
+            );
+            assert_eq!(kv_store.get_gc_threshold(), Some(Timestamp::new(5, 0)));
+            assert_eq!(
+                kv_store.collect_all_mvcc_kvs(),
+                Vec::from([
+                    MVCCKey::new(apple.clone(), Timestamp::new(7, 0)),
+                    MVCCKey::new(apple, Timestamp::new(5, 0)),
+                ])
+            );
+        }
+
+        #[test]
+        fn skips_keys_with_intents_and_their_txn_records() {
+            let kv_store = KVStore::new_cleaned(&create_temp_dir());
+            let apple = str_to_key("apple");
+            kv_store
+                .mvcc_put(apple.clone(), Some(Timestamp::new(1, 0)), None, 10)
+                .unwrap();
+            kv_store
+                .mvcc_put(apple.clone(), Some(Timestamp::new(2, 0)), None, 11)
+                .unwrap();
+            // the txn committed but its intent isn't resolved yet
+            let committed_txn_id = Uuid::new_v4();
+            let committed_txn = Txn::new_link(committed_txn_id, Timestamp::new(3, 0));
+            kv_store
+                .create_pending_transaction_record(committed_txn_id, Timestamp::new(3, 0))
+                .unwrap();
+            kv_store
+                .mvcc_put(apple.clone(), None, Some(committed_txn), 12)
+                .unwrap();
+            kv_store
+                .commit_transaction_record(committed_txn_id, Timestamp::new(3, 0), &[])
+                .unwrap();
+
+            let finalized_txn_id = Uuid::new_v4();
+            kv_store
+                .create_pending_transaction_record(finalized_txn_id, Timestamp::new(2, 0))
+                .unwrap();
+            kv_store
+                .commit_transaction_record(finalized_txn_id, Timestamp::new(2, 0), &[])
+                .unwrap();
+            let pending_txn_id = Uuid::new_v4();
+            kv_store
+                .create_pending_transaction_record(pending_txn_id, Timestamp::new(2, 0))
+                .unwrap();
+
+            let stats = kv_store.mvcc_gc(Timestamp::new(5, 0)).unwrap();
+            assert_eq!(
+                stats,
+                GcStats {
+                    versions_removed: 0,
+                    txn_records_removed: 1
+                }
+            );
+            assert_eq!(kv_store.collect_all_mvcc_kvs().len(), 3);
+            assert!(kv_store.get_transaction_record(finalized_txn_id).is_none());
+            assert!(kv_store.get_transaction_record(committed_txn_id).is_some());
+            assert!(kv_store.get_transaction_record(pending_txn_id).is_some());
+        }
+
+        #[test]
+        fn threshold_survives_reopening_and_never_moves_backwards() {
+            let path = create_temp_dir();
+            {
+                let kv_store = KVStore::new_cleaned(&path);
+                kv_store.mvcc_gc(Timestamp::new(5, 0)).unwrap();
+                kv_store.mvcc_gc(Timestamp::new(3, 0)).unwrap();
+                assert_eq!(kv_store.get_gc_threshold(), Some(Timestamp::new(5, 0)));
+            }
+            let kv_store = KVStore::new(&path);
+            assert_eq!(kv_store.get_gc_threshold(), Some(Timestamp::new(5, 0)));
+        }
     }
 }
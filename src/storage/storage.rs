@@ -1,40 +1,92 @@
-use std::{cmp::Ordering, iter::Peekable, path::Path};
+use std::{iter::Peekable, path::Path};
 
 use rand::distributions::{Alphanumeric, DistString};
-use rocksdb::{ColumnFamily, DBIterator, DB};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, DBIterator, DB};
 use serde::{de::DeserializeOwned, Serialize};
 use uuid::Uuid;
 
-use crate::{StorageError, StorageResult};
+use crate::{hlc::timestamp::Timestamp, StorageError, StorageResult};
 
 use super::{
+    decode_internal, encode_internal,
+    gc_compaction_filter::{GcCompactionFilterFactory, GcThreshold},
+    migration,
     mvcc_iterator::{IterOptions, MVCCIterator},
-    mvcc_key::{decode_mvcc_key, MVCCKey},
+    mvcc_key::MVCCKey,
     txn::TxnRecord,
 };
 
 pub struct Storage {
     pub db: DB,
+    // shared with the compaction filters of the mvcc column family
+    gc_threshold: GcThreshold,
 }
 
 pub static MVCC_COLUMN_FAMILY: &str = "mvcc";
 pub static TRANSACTION_RECORD_COLUMN_FAMILY: &str = "txn";
+// metadata of the database itself, such as the GC threshold
+pub static METADATA_COLUMN_FAMILY: &str = "metadata";
+
+pub static COLUMN_FAMILIES: [&str; 3] = [
+    MVCC_COLUMN_FAMILY,
+    TRANSACTION_RECORD_COLUMN_FAMILY,
+    METADATA_COLUMN_FAMILY,
+];
+
+static GC_THRESHOLD_KEY: &str = "gc_threshold";
+
+/**
+ * A group of writes that Storage::write applies atomically. Either all writes of the
+ * batch are persisted or none of them are, even if the process crashes in between.
+ */
+pub struct WriteBatch<'a> {
+    storage: &'a Storage,
+    batch: rocksdb::WriteBatch,
+}
 
 impl Storage {
     // path example: "./tmp/data";
     pub fn new(path: &str) -> Storage {
+        migration::finish_interrupted_migration(path);
+        let gc_threshold = GcThreshold::default();
+        let db = Storage::open(path, &gc_threshold)
+            .or_else(|err| {
+                if !migration::is_legacy_key_encoding_error(&err) {
+                    return Err(err);
+                }
+                migration::migrate_legacy_key_encoding(path);
+                Storage::open(path, &gc_threshold)
+            })
+            .unwrap();
+        let storage = Storage { db, gc_threshold };
+        let persisted_threshold = storage
+            .get_serialized(METADATA_COLUMN_FAMILY, GC_THRESHOLD_KEY)
+            .unwrap();
+        *storage.gc_threshold.write().unwrap() = persisted_threshold;
+        storage
+    }
+
+    /**
+     * Column families are created if the database is new and reopened otherwise.
+     * MVCC keys are encoded so that the default bytewise comparator sorts them.
+     */
+    fn open(path: &str, gc_threshold: &GcThreshold) -> Result<DB, rocksdb::Error> {
         let mut options = rocksdb::Options::default();
         options.create_if_missing(true);
 
         options.create_missing_column_families(true);
 
-        let mut db = DB::open(&options, path).unwrap();
-        let mut options = rocksdb::Options::default();
-        options.set_comparator("mvcc_ordering", Storage::compare);
-        db.create_cf(MVCC_COLUMN_FAMILY, &options).unwrap();
-        db.create_cf(TRANSACTION_RECORD_COLUMN_FAMILY, &options)
-            .unwrap();
-        Storage { db }
+        let cf_descriptors = COLUMN_FAMILIES.into_iter().map(|cf_name| {
+            let mut cf_options = rocksdb::Options::default();
+            if cf_name == MVCC_COLUMN_FAMILY {
+                // old versions are dropped when they're compacted
+                cf_options.set_compaction_filter_factory(GcCompactionFilterFactory {
+                    gc_threshold: gc_threshold.clone(),
+                });
+            }
+            ColumnFamilyDescriptor::new(cf_name, cf_options)
+        });
+        DB::open_cf_descriptors(&options, path, cf_descriptors)
     }
 
     // path example: "./tmp/data";
@@ -45,41 +97,17 @@ impl Storage {
         Storage::new(path)
     }
 
-    // A very non-performant way to sort keys...
-    // MVCCKeys are sorted in descending orders since we want the most recent
-    // timestamp to be sorted first
-    fn compare(first: &[u8], second: &[u8]) -> Ordering {
-        let first_mvcc = decode_mvcc_key(&first.to_vec());
-        let second_mvcc = decode_mvcc_key(&second.to_vec());
-        match (first_mvcc, second_mvcc) {
-            (None, None) => first.cmp(second),
-            (None, Some(_)) => first.cmp(second),
-            (Some(_), None) => first.cmp(second),
-            (Some(first_mvcc), Some(second_mvcc)) => {
-                let key_ordering = first_mvcc.key.cmp(&second_mvcc.key);
-                match key_ordering {
-                    Ordering::Less => key_ordering,
-                    Ordering::Equal => {
-                        if first_mvcc.is_intent_key() {
-                            // intent keys (keys with empty timestamps) are always sorted
-                            // on top of timestamped keys
-                            if second_mvcc.is_intent_key() {
-                                Ordering::Equal
-                            } else {
-                                Ordering::Less
-                            }
-                        } else if second_mvcc.is_intent_key() {
-                            Ordering::Greater
-                        } else {
-                            second_mvcc.timestamp.cmp(&first_mvcc.timestamp)
-                        }
-                    }
-                    Ordering::Greater => key_ordering,
-                }
-            }
+    pub fn new_write_batch(&self) -> WriteBatch<'_> {
+        WriteBatch {
+            storage: self,
+            batch: rocksdb::WriteBatch::default(),
         }
     }
 
+    pub fn write(&self, batch: WriteBatch) -> StorageResult<()> {
+        self.db.write(batch.batch).map_err(StorageError::from)
+    }
+
     pub fn new_mvcc_iterator(&self, iter_options: IterOptions) -> MVCCIterator {
         MVCCIterator::new(&self, iter_options)
     }
@@ -92,37 +120,33 @@ impl Storage {
         self.db.cf_handle(&cf_name).unwrap()
     }
 
-    fn put_raw(&self, cf_name: &str, key: &str, value: Vec<u8>) -> StorageResult<()> {
+    fn put_raw<K: AsRef<[u8]>>(&self, cf_name: &str, key: K, value: Vec<u8>) -> StorageResult<()> {
         let cf = self.get_column_family(cf_name);
         self.db
             .put_cf(cf, key, value)
             .map_err(|e| StorageError::from(e))
     }
 
-    fn delete(&self, cf_name: &str, key: &str) {
+    fn delete<K: AsRef<[u8]>>(&self, cf_name: &str, key: K) -> StorageResult<()> {
         let cf = self.get_column_family(cf_name);
-        self.db.delete_cf(cf, key).unwrap();
+        self.db.delete_cf(cf, key).map_err(StorageError::from)
     }
 
-    pub fn delete_mvcc(&self, key: &MVCCKey) {
-        self.delete(MVCC_COLUMN_FAMILY, &key.to_string())
+    pub fn delete_mvcc(&self, key: &MVCCKey) -> StorageResult<()> {
+        self.delete(MVCC_COLUMN_FAMILY, key.to_encoded())
     }
 
-    pub fn put_serialized<T: Serialize>(
+    pub fn put_serialized<K: AsRef<[u8]>, T: Serialize>(
         &self,
         cf_name: &str,
-        key: &str,
+        key: K,
         value: &T,
     ) -> StorageResult<()> {
-        let str_res = serde_json::to_string(value);
-        match str_res {
-            Ok(serialized) => self.put_raw(cf_name, &key, serialized.into_bytes()),
-            Err(err) => Err(StorageError::new("put_error".to_owned(), err.to_string())),
-        }
+        self.put_raw(cf_name, key, encode_internal(value)?)
     }
 
     pub fn put_raw_with_mvcc_key(&self, key: &MVCCKey, value: Vec<u8>) -> StorageResult<()> {
-        self.put_raw(MVCC_COLUMN_FAMILY, &key.to_string(), value)
+        self.put_raw(MVCC_COLUMN_FAMILY, key.to_encoded(), value)
     }
 
     pub fn put_serialized_with_mvcc_key<T: Serialize>(
@@ -130,21 +154,37 @@ impl Storage {
         key: &MVCCKey,
         value: T,
     ) -> StorageResult<()> {
-        self.put_serialized(MVCC_COLUMN_FAMILY, &key.to_string(), &value)
+        self.put_serialized(MVCC_COLUMN_FAMILY, key.to_encoded(), &value)
     }
 
     pub fn get_serialized_with_mvcc_key<T: DeserializeOwned>(
         &self,
         key: &MVCCKey,
     ) -> StorageResult<Option<T>> {
-        self.get_serialized(MVCC_COLUMN_FAMILY, &key.to_string())
+        self.get_serialized(MVCC_COLUMN_FAMILY, key.to_encoded())
     }
 
     pub fn get_transaction_record(&self, txn_id: Uuid) -> Option<TxnRecord> {
-        self.get_serialized(TRANSACTION_RECORD_COLUMN_FAMILY, &txn_id.to_string())
+        self.get_serialized(TRANSACTION_RECORD_COLUMN_FAMILY, txn_id.to_string())
             .unwrap()
     }
 
+    /**
+     * Returns every txn record in the database.
+     */
+    pub fn get_transaction_records(&self) -> Vec<(Uuid, TxnRecord)> {
+        let it = self
+            .get_normal_iterator(TRANSACTION_RECORD_COLUMN_FAMILY)
+            .unwrap();
+        it.map(|res| {
+            let (key, value) = res.unwrap();
+            let txn_id = Uuid::parse_str(std::str::from_utf8(&key).unwrap()).unwrap();
+            let record = decode_internal::<TxnRecord>(&value).unwrap();
+            (txn_id, record)
+        })
+        .collect()
+    }
+
     pub fn put_transaction_record(
         &self,
         txn_id: &Uuid,
@@ -152,21 +192,51 @@ impl Storage {
     ) -> Result<(), StorageError> {
         self.put_serialized(
             TRANSACTION_RECORD_COLUMN_FAMILY,
-            &txn_id.to_string(),
+            txn_id.to_string(),
             txn_record,
         )
     }
 
-    pub fn get_serialized<T: DeserializeOwned>(
+    /**
+     * Returns the timestamp beneath which the MVCC history can be garbage collected,
+     * if it ever was advanced.
+     */
+    pub fn get_gc_threshold(&self) -> Option<Timestamp> {
+        *self.gc_threshold.read().unwrap()
+    }
+
+    /**
+     * Persists and publishes the GC threshold. From then on, compactions drop the
+     * versions shadowed beneath the threshold. The threshold never moves backwards,
+     * so the threshold in effect is returned.
+     */
+    pub fn advance_gc_threshold(&self, threshold: Timestamp) -> StorageResult<Timestamp> {
+        let mut gc_threshold = self.gc_threshold.write().unwrap();
+        let threshold = gc_threshold.map_or(threshold, |current| current.max(threshold));
+        self.put_serialized(METADATA_COLUMN_FAMILY, GC_THRESHOLD_KEY, &threshold)?;
+        *gc_threshold = Some(threshold);
+        Ok(threshold)
+    }
+
+    /**
+     * Compacts the whole mvcc column family, which drops the versions shadowed
+     * beneath the GC threshold.
+     */
+    pub fn compact_mvcc(&self) {
+        let cf = self.get_column_family(MVCC_COLUMN_FAMILY);
+        self.db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+    }
+
+    pub fn get_serialized<K: AsRef<[u8]>, T: DeserializeOwned>(
         &self,
         cf_name: &str,
-        key: &str,
+        key: K,
     ) -> StorageResult<Option<T>> {
         let cf = self.get_column_family(cf_name);
         let res = self.db.get_cf(cf, key);
         match res {
             Ok(optional) => match optional {
-                Some(value) => Ok(Some(serde_json::from_slice::<T>(&value).unwrap())),
+                Some(value) => Ok(Some(decode_internal::<T>(&value)?)),
                 None => Ok(None),
             },
             Err(err) => Err(StorageError::new(
@@ -211,14 +281,52 @@ impl Storage {
     }
 }
 
+impl<'a> WriteBatch<'a> {
+    pub fn put_raw_with_mvcc_key(&mut self, key: &MVCCKey, value: Vec<u8>) {
+        let cf = self.storage.get_column_family(MVCC_COLUMN_FAMILY);
+        self.batch.put_cf(cf, key.to_encoded(), value);
+    }
+
+    pub fn delete_mvcc(&mut self, key: &MVCCKey) {
+        let cf = self.storage.get_column_family(MVCC_COLUMN_FAMILY);
+        self.batch.delete_cf(cf, key.to_encoded());
+    }
+
+    pub fn delete_transaction_record(&mut self, txn_id: &Uuid) {
+        let cf = self
+            .storage
+            .get_column_family(TRANSACTION_RECORD_COLUMN_FAMILY);
+        self.batch.delete_cf(cf, txn_id.to_string());
+    }
+
+    pub fn put_transaction_record(
+        &mut self,
+        txn_id: &Uuid,
+        txn_record: &TxnRecord,
+    ) -> StorageResult<()> {
+        let encoded = encode_internal(txn_record)?;
+        let cf = self
+            .storage
+            .get_column_family(TRANSACTION_RECORD_COLUMN_FAMILY);
+        self.batch.put_cf(cf, txn_id.to_string(), encoded);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod Test {
     use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
 
     use crate::{
         helpers::test_helpers::create_temp_dir,
         hlc::timestamp::Timestamp,
-        storage::{mvcc_key::MVCCKey, str_to_key},
+        storage::{
+            encode_internal,
+            mvcc_key::MVCCKey,
+            str_to_key,
+            txn::{TxnMetadata, UncommittedValue},
+        },
     };
 
     use super::Storage;
@@ -246,6 +354,121 @@ mod Test {
         assert_eq!(retrieved, 12);
     }
 
+    #[test]
+    fn write_batch() {
+        let storage = Storage::new_cleaned(&create_temp_dir());
+        let intent_key = MVCCKey::create_intent_key_with_str("hello");
+        let mvcc_key = MVCCKey::new(str_to_key("hello"), Timestamp::new(12, 0));
+        storage
+            .put_serialized_with_mvcc_key(&intent_key, 12)
+            .unwrap();
+
+        let mut batch = storage.new_write_batch();
+        batch.delete_mvcc(&intent_key);
+        batch.put_raw_with_mvcc_key(&mvcc_key, serde_json::to_vec(&13).unwrap());
+        // nothing is written until the batch is
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<i32>(&intent_key)
+                .unwrap(),
+            Some(12)
+        );
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<i32>(&mvcc_key)
+                .unwrap(),
+            None
+        );
+
+        storage.write(batch).unwrap();
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<i32>(&intent_key)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<i32>(&mvcc_key)
+                .unwrap(),
+            Some(13)
+        );
+    }
+
+    #[test]
+    fn internal_values_written_as_json_are_still_read() {
+        let storage = Storage::new_cleaned(&create_temp_dir());
+        let uncommitted_value = UncommittedValue {
+            value: Vec::from([0, 1, 255]),
+            txn_metadata: TxnMetadata::new(Uuid::new_v4(), Timestamp::new(12, 3)),
+            sequence: 2,
+            intent_history: Vec::new(),
+        };
+        let json_key = MVCCKey::create_intent_key_with_str("json");
+        let json = serde_json::to_vec(&uncommitted_value).unwrap();
+        storage
+            .put_raw_with_mvcc_key(&json_key, json.clone())
+            .unwrap();
+        let binary_key = MVCCKey::create_intent_key_with_str("binary");
+        storage
+            .put_serialized_with_mvcc_key(&binary_key, &uncommitted_value)
+            .unwrap();
+
+        for key in [json_key, binary_key] {
+            assert_eq!(
+                storage
+                    .get_serialized_with_mvcc_key::<UncommittedValue>(&key)
+                    .unwrap(),
+                Some(uncommitted_value.clone())
+            );
+        }
+        assert!(encode_internal(&uncommitted_value).unwrap().len() < json.len() / 2);
+    }
+
+    #[test]
+    fn compaction_drops_versions_shadowed_beneath_gc_threshold() {
+        let storage = Storage::new_cleaned(&create_temp_dir());
+        let version = |wall_time| MVCCKey::new(str_to_key("apple"), Timestamp::new(wall_time, 0));
+        for wall_time in [1, 3, 5, 7] {
+            storage
+                .put_serialized_with_mvcc_key(&version(wall_time), wall_time)
+                .unwrap();
+        }
+        let intent_key = MVCCKey::create_intent_key_with_str("apple");
+        storage
+            .put_serialized_with_mvcc_key(&intent_key, 9u64)
+            .unwrap();
+
+        // nothing is dropped before the threshold is advanced
+        storage.compact_mvcc();
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<u64>(&version(1))
+                .unwrap(),
+            Some(1)
+        );
+
+        assert_eq!(
+            storage.advance_gc_threshold(Timestamp::new(5, 0)),
+            Ok(Timestamp::new(5, 0))
+        );
+        storage.compact_mvcc();
+        for (wall_time, expected) in [(1, None), (3, None), (5, Some(5)), (7, Some(7))] {
+            assert_eq!(
+                storage
+                    .get_serialized_with_mvcc_key::<u64>(&version(wall_time))
+                    .unwrap(),
+                expected
+            );
+        }
+        assert_eq!(
+            storage
+                .get_serialized_with_mvcc_key::<u64>(&intent_key)
+                .unwrap(),
+            Some(9)
+        );
+    }
+
     mod storage_order {
 
         use crate::{
@@ -310,7 +533,6 @@ mod Test {
             hlc::timestamp::Timestamp,
             storage::{
                 mvcc_key::{encode_mvcc_key, MVCCKey},
-                storage::Storage,
                 str_to_key,
             },
         };
@@ -319,10 +541,7 @@ mod Test {
         fn different_key() {
             let first_mvcc_key = MVCCKey::new(str_to_key("a"), Timestamp::new(12, 12));
             let second_mvcc_key = MVCCKey::new(str_to_key("b"), Timestamp::new(12, 12));
-            let ordering = Storage::compare(
-                &encode_mvcc_key(&first_mvcc_key),
-                &encode_mvcc_key(&second_mvcc_key),
-            );
+            let ordering = encode_mvcc_key(&first_mvcc_key).cmp(&encode_mvcc_key(&second_mvcc_key));
             assert_eq!(ordering, Ordering::Less)
         }
 
@@ -330,10 +549,7 @@ mod Test {
         fn same_key() {
             let first_mvcc_key = MVCCKey::new(str_to_key("a"), Timestamp::new(10, 12));
             let second_mvcc_key = MVCCKey::new(str_to_key("a"), Timestamp::new(12, 12));
-            let ordering = Storage::compare(
-                &encode_mvcc_key(&first_mvcc_key),
-                &encode_mvcc_key(&second_mvcc_key),
-            );
+            let ordering = encode_mvcc_key(&first_mvcc_key).cmp(&encode_mvcc_key(&second_mvcc_key));
             assert_eq!(ordering, Ordering::Greater)
         }
 
@@ -341,16 +557,10 @@ mod Test {
         fn intent_key() {
             let intent_key = MVCCKey::create_intent_key_with_str("a");
             let second_mvcc_key = MVCCKey::new(str_to_key("a"), Timestamp::new(12, 12));
-            let ordering = Storage::compare(
-                &encode_mvcc_key(&intent_key),
-                &encode_mvcc_key(&second_mvcc_key),
-            );
+            let ordering = encode_mvcc_key(&intent_key).cmp(&encode_mvcc_key(&second_mvcc_key));
             assert_eq!(ordering, Ordering::Less);
 
-            let ordering = Storage::compare(
-                &encode_mvcc_key(&second_mvcc_key),
-                &encode_mvcc_key(&intent_key),
-            );
+            let ordering = encode_mvcc_key(&second_mvcc_key).cmp(&encode_mvcc_key(&intent_key));
             assert_eq!(ordering, Ordering::Greater)
         }
 
@@ -358,16 +568,10 @@ mod Test {
         fn two_intent_keys() {
             let intent_key = MVCCKey::create_intent_key_with_str("a");
             let second_intent_key = MVCCKey::create_intent_key_with_str("a");
-            let ordering = Storage::compare(
-                &encode_mvcc_key(&intent_key),
-                &encode_mvcc_key(&second_intent_key),
-            );
+            let ordering = encode_mvcc_key(&intent_key).cmp(&encode_mvcc_key(&second_intent_key));
             assert_eq!(ordering, Ordering::Equal);
 
-            let ordering = Storage::compare(
-                &encode_mvcc_key(&second_intent_key),
-                &encode_mvcc_key(&intent_key),
-            );
+            let ordering = encode_mvcc_key(&second_intent_key).cmp(&encode_mvcc_key(&intent_key));
             assert_eq!(ordering, Ordering::Equal)
         }
 
@@ -376,7 +580,7 @@ mod Test {
             let first_key = "a";
             let second_key = "b";
 
-            let ordering = Storage::compare(&str_to_key(first_key), &str_to_key(second_key));
+            let ordering = str_to_key(first_key).cmp(&str_to_key(second_key));
             assert_eq!(ordering, Ordering::Less)
         }
     }
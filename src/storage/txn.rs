@@ -1,16 +1,70 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{db::db::TxnLink, hlc::timestamp::Timestamp};
+use crate::{
+    db::db::TxnLink, execute::request::SpanSet, hlc::timestamp::Timestamp,
+    latch_manager::latch_interval_btree::Range,
+};
 
 use super::{Key, Value};
 
+/**
+ * The priority decides which txn wins a conflict. Priorities are grouped in the
+ * classes LOW, NORMAL and HIGH. A pusher of a higher class than the pushee aborts the
+ * pushee instead of waiting for it to finalize. Within a class, the txn with the lower
+ * priority is aborted when the txns deadlock.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TxnPriority(pub u32);
+
+impl TxnPriority {
+    pub const LOW: TxnPriority = TxnPriority(1_000);
+    pub const NORMAL: TxnPriority = TxnPriority(2_000);
+    pub const HIGH: TxnPriority = TxnPriority(3_000);
+
+    // the range of priorities in a class
+    const CLASS_WIDTH: u32 = 1_000;
+
+    /**
+     * Returns the priority of a retry of a txn with this priority. Retried txns gain
+     * priority so that they are eventually able to win deadlocks with txns of the
+     * same class instead of being starved. The priority never leaves its class, so a
+     * retried txn doesn't abort the txns of its class right away.
+     */
+    pub fn raise(self) -> Self {
+        let class_max = (self.class() + 1)
+            .saturating_mul(TxnPriority::CLASS_WIDTH)
+            .saturating_sub(1);
+        TxnPriority(self.0.saturating_add(1).min(class_max))
+    }
+
+    pub fn is_higher_class_than(self, other: TxnPriority) -> bool {
+        self.class() > other.class()
+    }
+
+    fn class(self) -> u32 {
+        self.0 / TxnPriority::CLASS_WIDTH
+    }
+}
+
+impl Default for TxnPriority {
+    fn default() -> Self {
+        TxnPriority::NORMAL
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TxnMetadata {
     pub txn_id: Uuid,
     pub write_timestamp: Timestamp,
+    #[serde(default)]
+    pub priority: TxnPriority,
 }
 
 impl TxnMetadata {
@@ -18,6 +72,7 @@ impl TxnMetadata {
         TxnMetadata {
             txn_id,
             write_timestamp,
+            priority: TxnPriority::default(),
         }
     }
 }
@@ -28,10 +83,48 @@ pub struct TxnIntent {
     pub key: Key,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SequencedValue {
+    pub sequence: u64,
+    pub value: Value,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct UncommittedValue {
     pub value: Value,
     pub txn_metadata: TxnMetadata,
+    // the sequence number of the txn's write that wrote the value
+    #[serde(default)]
+    pub sequence: u64,
+    /**
+     * Earlier values written by the same txn to the key, ordered by sequence.
+     * They are kept so that the key can be restored if later writes are rolled
+     * back to a savepoint.
+     */
+    #[serde(default)]
+    pub intent_history: Vec<SequencedValue>,
+}
+
+impl UncommittedValue {
+    /**
+     * Returns the most recent value written by the txn that wasn't rolled back.
+     * Returns None if every write to the key was rolled back.
+     */
+    pub fn get_visible_value(&self, ignored_sequences: &[RangeInclusive<u64>]) -> Option<&Value> {
+        let is_ignored = |sequence: u64| {
+            ignored_sequences
+                .iter()
+                .any(|range| range.contains(&sequence))
+        };
+        if !is_ignored(self.sequence) {
+            return Some(&self.value);
+        }
+        self.intent_history
+            .iter()
+            .rev()
+            .find(|sequenced_value| !is_ignored(sequenced_value.sequence))
+            .map(|sequenced_value| &sequenced_value.value)
+    }
 }
 
 #[derive(Debug)]
@@ -44,13 +137,36 @@ pub struct Txn {
     pub read_timestamp: Timestamp,
 
     /**
-     * List of keys that the transaction has acquired locks.
+     * List of spans that the transaction has acquired locks.
      * These should be resolved when the txn finalizes.
-     * Each of these map to an intent in the database laid by the txn.
+     * A span covers one or more intents in the database laid by the txn.
+     * Point writes are stored as spans where the start key equals the end key.
+     */
+    pub lock_spans: RwLock<SpanSet<Key>>,
+
+    /**
+     * Spans that the transaction has read. Point reads are stored as
+     * spans where the start key equals the end key.
      */
-    pub lock_spans: RwLock<Vec<Key>>,
+    pub read_set: RwLock<SpanSet<Key>>,
+
+    // The sequence number of the txn's latest write. Each write gets the next sequence.
+    pub sequence: u64,
+
+    // Sequence numbers of the writes rolled back to a savepoint. Reads and intent
+    // resolution ignore the values written with these sequences.
+    pub ignored_sequences: Vec<RangeInclusive<u64>>,
 
-    pub read_set: RwLock<Vec<Key>>,
+    // Read-only txns are pinned to their read timestamp. They have no txn record,
+    // can't write and are never sequenced.
+    pub read_only: bool,
+
+    pub priority: TxnPriority,
+
+    // The number of requests of the client that are being executed and when the last
+    // one finished. The record is only heartbeated while the client is active.
+    active_client_requests: usize,
+    last_client_activity: Instant,
 }
 
 impl Txn {
@@ -61,6 +177,12 @@ impl Txn {
             read_timestamp: timestamp,
             lock_spans: RwLock::new(Vec::new()),
             read_set: RwLock::new(Vec::new()),
+            sequence: 0,
+            ignored_sequences: Vec::new(),
+            read_only: false,
+            priority: TxnPriority::default(),
+            active_client_requests: 0,
+            last_client_activity: Instant::now(),
         }
     }
 
@@ -68,12 +190,15 @@ impl Txn {
         Arc::new(RwLock::new(Txn::new(transaction_id, timestamp)))
     }
 
+    pub fn new_read_only_link(transaction_id: Uuid, timestamp: Timestamp) -> TxnLink {
+        let mut txn = Txn::new(transaction_id, timestamp);
+        txn.read_only = true;
+        Arc::new(RwLock::new(txn))
+    }
+
     pub fn to_intent(&self, key: Key) -> TxnIntent {
         TxnIntent {
-            txn_meta: TxnMetadata {
-                txn_id: self.txn_id,
-                write_timestamp: self.write_timestamp,
-            },
+            txn_meta: self.to_txn_metadata(),
             key,
         }
     }
@@ -82,37 +207,117 @@ impl Txn {
         TxnMetadata {
             txn_id: self.txn_id,
             write_timestamp: self.write_timestamp,
+            priority: self.priority,
         }
     }
 
-    pub fn append_lock_span(&self, key: Key) {
+    pub fn append_lock_span(&self, span: Range<Key>) {
         let mut lock_spans = self.lock_spans.write().unwrap();
-        // avoid duplicate keys
-        if !lock_spans.contains(&key) {
-            lock_spans.push(key);
+        // avoid spans that are already covered
+        let is_covered = lock_spans
+            .iter()
+            .any(|s| s.start_key <= span.start_key && span.end_key <= s.end_key);
+        if !is_covered {
+            lock_spans.push(span);
         }
     }
 
     pub fn append_read_sets(&self, key: Key) {
+        self.append_read_span(Range {
+            start_key: key.clone(),
+            end_key: key,
+        });
+    }
+
+    pub fn append_read_span(&self, span: Range<Key>) {
         let mut read_set = self.read_set.write().unwrap();
-        read_set.push(key);
+        read_set.push(span);
+    }
+
+    pub fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    /**
+     * Ignores every write made after the sequence number.
+     */
+    pub fn rollback_to_sequence(&mut self, sequence: u64) {
+        if sequence < self.sequence {
+            self.ignored_sequences.push(sequence + 1..=self.sequence);
+        }
     }
 
     pub fn bump_write_timestamp(&mut self, new_timestamp: Timestamp) {
         self.write_timestamp = self.write_timestamp.advance_to(new_timestamp);
     }
 
+    pub fn start_client_request(&mut self) {
+        self.active_client_requests += 1;
+    }
+
+    pub fn finish_client_request(&mut self) {
+        self.active_client_requests -= 1;
+        self.last_client_activity = Instant::now();
+    }
+
+    /**
+     * Whether the client abandoned the txn: none of its requests are being executed and
+     * it didn't send one within TXN_CLIENT_IDLE_TIMEOUT.
+     */
+    pub fn is_client_idle(&self) -> bool {
+        self.active_client_requests == 0
+            && self.last_client_activity.elapsed() > TXN_CLIENT_IDLE_TIMEOUT
+    }
+
     pub fn get_txn_properties(txn_link: TxnLink) -> (Uuid, Timestamp, Timestamp) {
         let txn = txn_link.read().unwrap();
         (txn.txn_id, txn.read_timestamp, txn.write_timestamp)
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
+// How often the coordinator heartbeats the records of its pending txns
+pub const TXN_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+// A pending txn whose record wasn't heartbeated within the threshold is considered
+// abandoned by its coordinator. Pushers are allowed to abort it.
+pub const TXN_LIVENESS_THRESHOLD: Duration = Duration::from_secs(5);
+
+// The coordinator stops heartbeating the record of a txn whose client didn't send a
+// request within the timeout, so that the txn expires if the client went away.
+pub const TXN_CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
 // What's stored in the database
 pub struct TxnRecord {
     pub status: TransactionStatus,
     pub metadata: TxnMetadata,
+    // The last time the txn's coordinator heartbeated the record
+    #[serde(default)]
+    pub last_heartbeat: Option<SystemTime>,
+    // The sequences rolled back by a committed txn so that its intents can be
+    // resolved without the txn, e.g. during recovery
+    #[serde(default)]
+    pub ignored_sequences: Vec<RangeInclusive<u64>>,
+}
+
+impl TxnRecord {
+    /**
+     * Whether the txn is pending but its coordinator stopped heartbeating the record.
+     * A pending record that was never heartbeated is expired.
+     */
+    pub fn is_expired(&self) -> bool {
+        if self.status != TransactionStatus::PENDING {
+            return false;
+        }
+        match self.last_heartbeat {
+            // the system clock could have moved backwards since the heartbeat
+            Some(last_heartbeat) => last_heartbeat
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed > TXN_LIVENESS_THRESHOLD),
+            None => true,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -125,10 +330,7 @@ pub enum TransactionStatus {
 impl TxnIntent {
     pub fn new(txn_id: Uuid, write_timestamp: Timestamp, key: Key) -> Self {
         TxnIntent {
-            txn_meta: TxnMetadata {
-                txn_id: txn_id,
-                write_timestamp: write_timestamp,
-            },
+            txn_meta: TxnMetadata::new(txn_id, write_timestamp),
             key: key,
         }
     }
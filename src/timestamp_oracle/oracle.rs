@@ -3,7 +3,8 @@ use std::sync::RwLock;
 use uuid::Uuid;
 
 use crate::{
-    execute::request::{Command, Request},
+    db::db::TxnLink,
+    execute::request::{Command, Request, SpanSet},
     hlc::timestamp::Timestamp,
     interval::interval_tree::IntervalTree,
     latch_manager::latch_interval_btree::Range,
@@ -36,14 +37,21 @@ impl TimestampOracle {
     }
 
     pub fn update_cache(&self, request: &Request) {
-        let txn = request.metadata.txn.read().unwrap();
-        let txn_id = Some(txn.txn_id);
-        let timestamp = txn.read_timestamp;
         // TODO: We already executed this earlier in execute_request_with_concurrency_retries,
         // we should be able to avoid calling collect_spans twice
         let spans = request
             .request_union
             .collect_spans(request.metadata.txn.clone());
+        self.update_cache_for_spans(request.metadata.txn.clone(), &spans);
+    }
+
+    /**
+     * Adds the txn's read timestamp to the oracle for each span.
+     */
+    pub fn update_cache_for_spans(&self, txn: TxnLink, spans: &SpanSet<Key>) {
+        let txn = txn.read().unwrap();
+        let txn_id = Some(txn.txn_id);
+        let timestamp = txn.read_timestamp;
         for span in spans.iter() {
             let Range { start_key, end_key } = span;
 
@@ -14,12 +14,16 @@ use tokio::{
 use uuid::Uuid;
 
 use crate::{
-    db::{
-        request_queue::{
-            AbortTxnQueueRequest, QueueResponseUnion, TaskQueueRequest, TaskQueueRequestUnion,
-        },
+    db::request_queue::{
+        AbortTxnQueueRequest, QueueResponseUnion, TaskQueueRequest, TaskQueueRequestUnion,
+    },
+    hlc::timestamp::Timestamp,
+    storage::{
+        mvcc::KVStore,
+        txn::{TransactionStatus, TxnMetadata},
+        Key,
     },
-    storage::{mvcc::KVStore, txn::TransactionStatus},
+    StorageResult,
 };
 
 /**
@@ -180,31 +184,78 @@ impl TxnWaitQueue {
     }
 
     /**
-     * This function will first create a waiting_push and add it to the txn waitQueue.
+     * Aborts the pushee through the task queue and waits for the abort to finish.
+     */
+    async fn abort_pushee(&self, pushee_txn_id: Uuid) {
+        let (push_txn_sender, mut push_txn_rx) = channel(1);
+        self.request_sender
+            .send(TaskQueueRequest {
+                done_sender: Arc::new(push_txn_sender),
+                request: TaskQueueRequestUnion::AbortTxn(AbortTxnQueueRequest {
+                    txn_id: pushee_txn_id,
+                }),
+            })
+            .await
+            .unwrap();
+        let response = push_txn_rx.recv().await.unwrap();
+        match response {
+            QueueResponseUnion::AbortTxn(_) => {}
+            QueueResponseUnion::ResolveIntents(_) => unreachable!(),
+        }
+    }
+
+    /**
+     * Pushes the timestamp of a pending pushee to the timestamp so that the pusher can
+     * read beneath the pushee's intent on the key instead of waiting for the pushee to
+     * finalize. The pushee commits at the pushed timestamp if its read refresh succeeds.
+     *
+     * The caller must hold the latch on the key so that the intent isn't resolved
+     * while it's pushed. Returns whether the push succeeded.
+     */
+    pub fn push_timestamp(
+        &self,
+        pushee_txn_id: Uuid,
+        key: &Key,
+        timestamp: Timestamp,
+    ) -> StorageResult<bool> {
+        let is_pending = self
+            .store
+            .push_transaction_record(pushee_txn_id, timestamp)?
+            .is_some_and(|record| record.status == TransactionStatus::PENDING);
+        if is_pending {
+            self.store.mvcc_push_intent(key, pushee_txn_id, timestamp)?;
+        }
+        Ok(is_pending)
+    }
+
+    /**
+     * If the pusher has a higher priority class than the pushee, the pushee is aborted right away.
+     * Otherwise, this function will first create a waiting_push and add it to the txn waitQueue.
      * It will then wait for the waiting_push's pushee to be finalized (aborted/committed).
+     * A pushee whose record expired is aborted. In a dependency cycle, the pushee is only
+     * aborted if it has a lower priority than the pusher (or the same priority and a
+     * lower txn ID). Otherwise, the pusher keeps waiting until the pushee aborts it.
      */
     pub async fn wait_for_push(
         &self,
-        pusher_txn_id: Uuid,
-        pushee_txn_id: Uuid,
+        pusher: TxnMetadata,
+        pushee: TxnMetadata,
     ) -> Result<PushTxnResponse, WaitForPushError> {
+        let pusher_txn_id = pusher.txn_id;
+        let pushee_txn_id = pushee.txn_id;
         println!(
             "Wait_for_push called. Pusher: {}. Pushee: {}",
             pusher_txn_id, pushee_txn_id
         );
+        if pusher.priority.is_higher_class_than(pushee.priority) {
+            self.abort_pushee(pushee_txn_id).await;
+            return Ok(PushTxnResponse {});
+        }
         let waiting_push_link = self.enqueue_and_push(pusher_txn_id, pushee_txn_id);
         let (query_dependents_handle, mut dependents_rx) =
             self.start_query_pusher_txn_dependents(pusher_txn_id, waiting_push_link.clone());
         let mut rx = waiting_push_link.pushee_finalized_receiver.lock().await;
-        let mut loop_count = 0;
         loop {
-            loop_count += 1;
-            if loop_count > 25 {
-                panic!(
-                    "Exceeded count waiting for push. Pusher: {}. Pushee: {}",
-                    pusher_txn_id, pushee_txn_id
-                );
-            }
             tokio::select! {
                 Some((dependents, txn_status)) = dependents_rx.recv() => {
                     // if the pusher's transaciton is finalized, then the push itself failed.
@@ -219,24 +270,25 @@ impl TxnWaitQueue {
                     }
                     let is_cycle_detected = dependents.contains(&pushee_txn_id);
                     // check if there is a dependency
-                    if is_cycle_detected {
+                    let pushee_loses = (pushee.priority, pushee_txn_id) < (pusher.priority, pusher_txn_id);
+                    if is_cycle_detected && pushee_loses {
                         query_dependents_handle.abort();
-                        let (push_txn_sender, mut push_txn_rx) = channel(1);
-                        self.request_sender
-                            .send(TaskQueueRequest{
-                                done_sender: Arc::new(push_txn_sender),
-                                request: TaskQueueRequestUnion::AbortTxn(
-                                    AbortTxnQueueRequest { txn_id: pushee_txn_id }
-                                )
-                            })
-                            .await
-                            .unwrap();
-                        let response = push_txn_rx.recv().await.unwrap();
-                        match response {
-                            QueueResponseUnion::AbortTxn(_) => {
-                                println!("Ending waitForPush, finished aborting pushee: {}", pushee_txn_id);
-                                return Ok(PushTxnResponse{})
-                            },
+                        self.abort_pushee(pushee_txn_id).await;
+                        println!("Ending waitForPush, finished aborting pushee: {}", pushee_txn_id);
+                        return Ok(PushTxnResponse{})
+                    }
+                    let pushee_record = self.store.get_transaction_record(pushee_txn_id);
+                    if let Some(pushee_record) = pushee_record {
+                        // the pushee could have finalized before the push was enqueued
+                        if pushee_record.status != TransactionStatus::PENDING {
+                            query_dependents_handle.abort();
+                            return Ok(PushTxnResponse{});
+                        }
+                        // the pushee's coordinator stopped heartbeating the txn
+                        if pushee_record.is_expired() {
+                            query_dependents_handle.abort();
+                            self.abort_pushee(pushee_txn_id).await;
+                            return Ok(PushTxnResponse{});
                         }
                     }
                     println!("Received dependents. No cycles detected yet! Dependents: {:?}", dependents);
@@ -290,7 +342,10 @@ impl TxnWaitQueue {
             loop {
                 // TODO: We need a way to terminate the loop
                 let dependents = TxnWaitQueue::get_transitive_dependents(txns.clone(), txn_id);
-                let txn_record = store.get_transaction_record(txn_id).unwrap();
+                // non-transactional pushers don't have a txn record and are never finalized
+                let txn_status = store
+                    .get_transaction_record(txn_id)
+                    .map_or(TransactionStatus::PENDING, |record| record.status);
                 // add dependents to waitingPush's dependents
                 pusher_waiting_push_link
                     .as_ref()
@@ -299,7 +354,7 @@ impl TxnWaitQueue {
                     .unwrap()
                     .extend(dependents.iter());
                 // update waitingPush
-                let res = tx.send((dependents, txn_record.status)).await;
+                let res = tx.send((dependents, txn_status)).await;
                 match res {
                     Ok(_) => {}
                     Err(err) => {
@@ -313,3 +368,93 @@ impl TxnWaitQueue {
         (handle, rx)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use tokio::sync::mpsc::{channel, error::TryRecvError};
+    use uuid::Uuid;
+
+    use crate::{
+        db::request_queue::{
+            AbortTxnThreadPoolResponse, QueueResponseUnion, TaskQueueRequestUnion,
+        },
+        helpers::test_helpers::create_temp_dir,
+        hlc::timestamp::Timestamp,
+        storage::{
+            mvcc::KVStore,
+            txn::{TransactionStatus, TxnMetadata, TxnRecord},
+        },
+    };
+
+    use super::TxnWaitQueue;
+
+    fn create_pending_txn(store: &KVStore, last_heartbeat: SystemTime) -> TxnMetadata {
+        let txn_meta = TxnMetadata::new(Uuid::new_v4(), Timestamp::new(10, 0));
+        store
+            .put_transaction_record(
+                txn_meta.txn_id,
+                &TxnRecord {
+                    status: TransactionStatus::PENDING,
+                    metadata: txn_meta,
+                    last_heartbeat: Some(last_heartbeat),
+                    ignored_sequences: Vec::new(),
+                },
+            )
+            .unwrap();
+        txn_meta
+    }
+
+    #[tokio::test]
+    async fn aborts_expired_pushee() {
+        let store = Arc::new(KVStore::new_cleaned(&create_temp_dir()));
+        let (sender, mut receiver) = channel(1);
+        let txn_wait_queue = TxnWaitQueue::new(Arc::new(sender), store.clone());
+        let pusher = create_pending_txn(&store, SystemTime::now());
+        let pushee = create_pending_txn(&store, SystemTime::now() - Duration::from_secs(60));
+
+        // stands in for the task queue that aborts the pushee
+        let abort_task = tokio::spawn(async move {
+            let request = receiver.recv().await.unwrap();
+            match request.request {
+                TaskQueueRequestUnion::AbortTxn(abort_request) => {
+                    assert_eq!(abort_request.txn_id, pushee.txn_id);
+                }
+                TaskQueueRequestUnion::ResolveIntents(_) => unreachable!(),
+            }
+            request
+                .done_sender
+                .send(QueueResponseUnion::AbortTxn(AbortTxnThreadPoolResponse {}))
+                .await
+                .unwrap();
+        });
+        assert!(txn_wait_queue.wait_for_push(pusher, pushee).await.is_ok());
+        abort_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn waits_for_live_pushee_to_finalize() {
+        let store = Arc::new(KVStore::new_cleaned(&create_temp_dir()));
+        let (sender, mut receiver) = channel(1);
+        let txn_wait_queue = Arc::new(TxnWaitQueue::new(Arc::new(sender), store.clone()));
+        let pusher = create_pending_txn(&store, SystemTime::now());
+        let pushee = create_pending_txn(&store, SystemTime::now());
+
+        let txn_wait_queue_2 = txn_wait_queue.clone();
+        let push_task =
+            tokio::spawn(async move { txn_wait_queue_2.wait_for_push(pusher, pushee).await });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!push_task.is_finished());
+        assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        store
+            .commit_transaction_record(pushee.txn_id, pushee.write_timestamp, &[])
+            .unwrap();
+        txn_wait_queue.finalize_txn(pushee.txn_id).await;
+        assert!(push_task.await.unwrap().is_ok());
+    }
+}